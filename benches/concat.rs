@@ -0,0 +1,47 @@
+use std::time::Instant;
+
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence, Value};
+
+// Builds a large string by repeated concatenation, which is the case `Rope` strings are meant to
+// make cheap.
+fn main() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    let start = Instant::now();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local s = ""
+                        for i = 1, 100000 do
+                            s = s .. "line " .. i .. "\n"
+                        end
+                        return string.len(s)
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| match res[..] {
+            [Value::Integer(len)] => len,
+            _ => panic!("unexpected results {:?}", res),
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+    println!("concat 100000 lines: {:?}", start.elapsed());
+
+    Ok(())
+}
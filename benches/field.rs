@@ -0,0 +1,50 @@
+use std::time::Instant;
+
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence, Value};
+
+// Reads and writes named table fields in a tight loop, both directly and through an `__index`
+// chain, which exercises the constant key forms of the table opcodes.
+fn main() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    let start = Instant::now();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local point = { x = 0, y = 0 }
+                        local proto = { step = 1 }
+                        local derived = setmetatable({}, { __index = proto })
+                        for i = 1, 1000000 do
+                            point.x = point.x + derived.step
+                            point.y = point.y + point.x
+                        end
+                        return point.x
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| match res[..] {
+            [Value::Integer(x)] => x,
+            _ => panic!("unexpected results {:?}", res),
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+    println!("field access 1000000 iterations: {:?}", start.elapsed());
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use alloc::alloc::{alloc, dealloc, Layout};
+
+/// The source of memory for every object allocated in an arena, set with
+/// `ArenaParameters::set_allocator`.  This allows embedding an arena in environments with their own
+/// memory management, or instrumenting its allocations.
+///
+/// # Safety
+///
+/// `alloc_box` must return either null or a pointer to a fresh block of memory that fits the given
+/// layout, which remains valid until it is passed back to `dealloc_box` with the same layout.
+pub unsafe trait GcAllocator {
+    /// Allocates memory for a single object, returning null if the allocation fails.
+    ///
+    /// # Safety
+    ///
+    /// The layout must not have a size of zero.
+    unsafe fn alloc_box(&self, layout: Layout) -> *mut u8;
+
+    /// Frees memory previously returned by `alloc_box`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc_box` on this allocator with the same layout, and must
+    /// not have been freed already.
+    unsafe fn dealloc_box(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// The default `GcAllocator`, which uses the global allocator.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct GlobalGcAllocator;
+
+unsafe impl GcAllocator for GlobalGcAllocator {
+    unsafe fn alloc_box(&self, layout: Layout) -> *mut u8 {
+        alloc(layout)
+    }
+
+    unsafe fn dealloc_box(&self, ptr: *mut u8, layout: Layout) {
+        dealloc(ptr, layout)
+    }
+}
@@ -1,16 +1,34 @@
-use std::{f64, usize};
+use alloc::rc::Rc;
+use core::fmt;
+use core::{f64, usize};
 
+use crate::allocator::{GcAllocator, GlobalGcAllocator};
 use crate::context::{Context, MutationContext};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ArenaParameters {
     pub(crate) pause_factor: f64,
     pub(crate) timing_factor: f64,
     pub(crate) min_sleep: usize,
+    pub(crate) max_heap: Option<usize>,
+    pub(crate) max_object_size: Option<usize>,
+    pub(crate) allocator: Rc<dyn GcAllocator>,
+}
+
+impl fmt::Debug for ArenaParameters {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ArenaParameters")
+            .field("pause_factor", &self.pause_factor)
+            .field("timing_factor", &self.timing_factor)
+            .field("min_sleep", &self.min_sleep)
+            .field("max_heap", &self.max_heap)
+            .field("max_object_size", &self.max_object_size)
+            .finish()
+    }
 }
 
 /// Creates a default ArenaParameters with `pause_factor` set to 0.5, `timing_factor` set to 1.5,
-/// and `min_sleep` set to 4096.
+/// `min_sleep` set to 4096, no `max_heap` or `max_object_size`, and the global allocator.
 impl Default for ArenaParameters {
     fn default() -> ArenaParameters {
         const PAUSE_FACTOR: f64 = 0.5;
@@ -21,6 +39,9 @@ impl Default for ArenaParameters {
             pause_factor: PAUSE_FACTOR,
             timing_factor: TIMING_FACTOR,
             min_sleep: MIN_SLEEP,
+            max_heap: None,
+            max_object_size: None,
+            allocator: Rc::new(GlobalGcAllocator),
         }
     }
 }
@@ -53,6 +74,28 @@ impl ArenaParameters {
         self.min_sleep = min_sleep;
         self
     }
+
+    /// A hard ceiling on the total allocated size of the arena, in bytes.  If set, allocations
+    /// which would push the arena past this size fail with `AllocError` when made through a
+    /// `try_allocate` method, and panic when made through `allocate`.
+    pub fn set_max_heap(mut self, max_heap: Option<usize>) -> ArenaParameters {
+        self.max_heap = max_heap;
+        self
+    }
+
+    /// A ceiling on the size of any single allocation, in bytes.  Allocations larger than this fail
+    /// in the same way as allocations which would exceed `max_heap`.
+    pub fn set_max_object_size(mut self, max_object_size: Option<usize>) -> ArenaParameters {
+        self.max_object_size = max_object_size;
+        self
+    }
+
+    /// The allocator which every object in the arena, including the root, is allocated from and
+    /// freed back to.
+    pub fn set_allocator(mut self, allocator: Rc<dyn GcAllocator>) -> ArenaParameters {
+        self.allocator = allocator;
+        self
+    }
 }
 
 /// Creates a new "garbage collected arena" type.  The macro takes two parameters, the name you
@@ -104,7 +147,7 @@ macro_rules! make_arena {
     (@impl $v:vis $arena:ident, $root:ident) => {
         $v struct $arena {
             context: $crate::Context,
-            root: ::std::mem::ManuallyDrop<$root<'static>>,
+            root: ::core::mem::ManuallyDrop<$root<'static>>,
         }
 
         impl $arena {
@@ -119,14 +162,35 @@ macro_rules! make_arena {
             {
                 unsafe {
                     let context = $crate::Context::new(arena_parameters);
-                    let root: $root<'static> = ::std::mem::transmute(f(context.mutation_context()));
+                    let root: $root<'static> = ::core::mem::transmute(f(context.mutation_context()));
                     $arena {
                         context: context,
-                        root: ::std::mem::ManuallyDrop::new(root),
+                        root: ::core::mem::ManuallyDrop::new(root),
                     }
                 }
             }
 
+            /// Similar to `new`, but also sets `max_heap` and `max_object_size` on the given
+            /// parameters, so that the limits are in effect from the very first allocation,
+            /// including any made while constructing the root.
+            #[allow(unused)]
+            pub fn with_limits<F>(
+                arena_parameters: $crate::ArenaParameters,
+                max_heap: usize,
+                max_object_size: usize,
+                f: F,
+            ) -> $arena
+            where
+                F: for<'gc> FnOnce($crate::MutationContext<'gc, '_>) -> $root<'gc>,
+            {
+                $arena::new(
+                    arena_parameters
+                        .set_max_heap(Some(max_heap))
+                        .set_max_object_size(Some(max_object_size)),
+                    f,
+                )
+            }
+
             /// Similar to `new`, but allows for constructor that can fail.
             #[allow(unused)]
             pub fn try_new<F, E>(
@@ -139,10 +203,10 @@ macro_rules! make_arena {
                 unsafe {
                     let context = $crate::Context::new(arena_parameters);
                     let root: $root = f(context.mutation_context())?;
-                    let root: $root<'static> = ::std::mem::transmute(root);
+                    let root: $root<'static> = ::core::mem::transmute(root);
                     Ok($arena {
                         context: context,
-                        root: ::std::mem::ManuallyDrop::new(root),
+                        root: ::core::mem::ManuallyDrop::new(root),
                     })
                 }
             }
@@ -160,7 +224,7 @@ macro_rules! make_arena {
                 unsafe {
                     f(
                         self.context.mutation_context(),
-                        ::std::mem::transmute::<&$root<'static>, _>(&*self.root),
+                        ::core::mem::transmute::<&$root<'static>, _>(&*self.root),
                     )
                 }
             }
@@ -172,6 +236,31 @@ macro_rules! make_arena {
                 self.context.total_allocated()
             }
 
+            /// Returns the current phase of the garbage collector.
+            #[allow(unused)]
+            #[inline]
+            pub fn phase(&self) -> $crate::GcPhase {
+                self.context.phase()
+            }
+
+            /// Sets an observer which will be notified of garbage collector phase changes and
+            /// freed objects.  Setting this to `None` removes any current observer.
+            #[allow(unused)]
+            pub fn set_observer(
+                &self,
+                observer: Option<$crate::__alloc::rc::Rc<dyn $crate::GcObserver>>,
+            ) {
+                self.context.set_observer(observer)
+            }
+
+            /// Returns the last several phases the collector has entered, oldest first, along with
+            /// the arena's `total_allocated` at the time.  Only recorded with the `gc-debug`
+            /// feature, otherwise this is always empty.
+            #[allow(unused)]
+            pub fn phase_history(&self) -> $crate::__alloc::vec::Vec<($crate::GcPhase, usize)> {
+                self.context.phase_history()
+            }
+
             /// When the garbage collector is not sleeping, all allocated objects cause the arena to
             /// accumulate "allocation debt".  This debt is then be used to time incremental garbage
             /// collection based on the tuning parameters set in `ArenaParameters`.  The allocation
@@ -205,15 +294,71 @@ macro_rules! make_arena {
                 self.context.wake();
                 unsafe {
                     self.context
-                        .do_collection(&*self.root, ::std::f64::INFINITY);
+                        .do_collection(&*self.root, ::core::f64::INFINITY);
+                }
+            }
+
+            /// Performs roughly `work` bytes worth of collection, regardless of the allocation
+            /// debt, waking the collector first if it is sleeping.  Returns true if this finished
+            /// the current collection cycle.
+            #[allow(unused)]
+            pub fn step(&mut self, work: usize) -> bool {
+                self.context.wake();
+                unsafe {
+                    self.context.do_collection(&*self.root, work as f64);
+                }
+                self.context.phase() == $crate::GcPhase::Sleep
+            }
+
+            /// Like `collect_all`, but reports how much was freed and whether a new cycle had to be
+            /// started.
+            #[allow(unused)]
+            pub fn collect_all_outcome(&mut self) -> $crate::CollectionOutcome {
+                // A cycle in the `Wake` phase has not traced anything yet, so finishing it is the
+                // same as starting a new one.
+                let started_cycle = matches!(
+                    self.context.phase(),
+                    $crate::GcPhase::Sleep | $crate::GcPhase::Wake
+                );
+                let bytes_freed = self.context.bytes_freed();
+                let objects_freed = self.context.objects_freed();
+                self.collect_all();
+                $crate::CollectionOutcome {
+                    bytes_freed: self.context.bytes_freed() - bytes_freed,
+                    objects_freed: self.context.objects_freed() - objects_freed,
+                    started_cycle,
                 }
             }
+
+            /// Runs two full collections back to back and panics if the set of objects surviving
+            /// the first is not identical to the set surviving the second.  With no mutation in
+            /// between, a difference means that some `Collect` implementation is not tracing
+            /// consistently.  This is expensive, and intended for testing.
+            #[allow(unused)]
+            pub fn collect_and_verify_stable(&mut self) {
+                // `collect_all` only finishes the cycle in progress, which keeps anything that was
+                // marked before it became garbage, so a second full cycle is needed before the
+                // surviving set is exactly what is reachable.
+                self.collect_all();
+                self.collect_all();
+                let mut first = self.context.object_ids();
+                first.sort();
+
+                self.collect_all();
+                let mut second = self.context.object_ids();
+                second.sort();
+
+                assert_eq!(
+                    first, second,
+                    "live set changed between back to back collections"
+                );
+            }
         }
 
         impl Drop for $arena {
             fn drop(&mut self) {
                 unsafe {
-                    ::std::mem::ManuallyDrop::drop(&mut self.root);
+                    ::core::mem::ManuallyDrop::drop(&mut self.root);
                 }
             }
         }
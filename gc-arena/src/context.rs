@@ -1,11 +1,21 @@
-use std::cell::{Cell, RefCell, UnsafeCell};
-use std::marker::PhantomData;
-use std::ptr::NonNull;
-use std::{f64, mem, usize};
-
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::{f64, mem, usize};
+#[cfg(feature = "gc-debug")]
+use std::collections::{HashSet, VecDeque};
+#[cfg(feature = "std")]
+use std::error::Error as StdError;
+#[cfg(feature = "gc-debug")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::allocator::GcAllocator;
 use crate::arena::ArenaParameters;
 use crate::collect::Collect;
-use crate::types::{GcBox, GcColor, GcFlags, Invariant};
+use crate::types::{GcBox, GcBoxHeader, GcColor, Invariant};
 
 /// Handle value given by arena callbacks during construction and mutation.  Allows allocating new
 /// `Gc` pointers and internally mutating values held by `Gc` pointers.
@@ -20,63 +30,230 @@ impl<'gc, 'context> MutationContext<'gc, 'context> {
         self.context.allocate(t)
     }
 
-    pub(crate) unsafe fn write_barrier<T: 'gc + Collect>(self, ptr: NonNull<GcBox<T>>) {
-        self.context.write_barrier(ptr)
+    pub(crate) unsafe fn try_allocate<T: 'gc + Collect>(
+        self,
+        t: T,
+    ) -> Result<NonNull<GcBox<T>>, AllocError> {
+        self.context.try_allocate(t)
+    }
+
+    pub(crate) unsafe fn try_allocate_slice<T: 'gc + Collect>(
+        self,
+        values: Vec<T>,
+    ) -> Result<NonNull<GcBox<[T]>>, AllocError> {
+        self.context.try_allocate_slice(values)
+    }
+
+    pub(crate) unsafe fn write_barrier<T: 'gc + ?Sized>(self, ptr: NonNull<GcBox<T>>) {
+        self.context.write_barrier(ptr.cast())
+    }
+
+    #[cfg(feature = "gc-debug")]
+    pub(crate) unsafe fn reachable_from<T: 'gc + ?Sized>(
+        self,
+        ptr: NonNull<GcBox<T>>,
+    ) -> HashSet<*const ()> {
+        let reachable = Reachable::default();
+        let cc = CollectionContext {
+            context: self.context,
+            reachable: Some(&reachable),
+        };
+        cc.trace(ptr);
+
+        loop {
+            let next = reachable.pending.borrow_mut().pop();
+            if let Some(ptr) = next {
+                GcBoxHeader::trace_value(ptr, cc);
+            } else {
+                let deferred = mem::take(&mut *reachable.ephemerons.borrow_mut());
+                let mut progress = false;
+                for ephemeron in deferred {
+                    if ephemeron.as_ref().try_trace(cc) {
+                        progress = true;
+                    } else {
+                        reachable.ephemerons.borrow_mut().push(ephemeron);
+                    }
+                }
+                if !progress {
+                    break;
+                }
+            }
+        }
+
+        reachable
+            .visited
+            .into_inner()
+            .into_iter()
+            .map(|ptr| ptr.as_ptr() as *const ())
+            .collect()
+    }
+}
+
+/// Receives notifications about garbage collector activity, for logging or instrumentation.  Set
+/// on an arena with `set_observer`.
+pub trait GcObserver {
+    /// Called whenever the collector moves from one phase to another.
+    fn on_phase_change(&self, _from: GcPhase, _to: GcPhase) {}
+
+    /// Called whenever the collector frees an object, with the size of its allocation in bytes.
+    fn on_object_freed(&self, _size: usize) {}
+}
+
+/// Error returned when an allocation would exceed the arena's configured `max_heap`.
+///
+/// Garbage collection can never take place inside a `mutate` call, so unreachable objects still
+/// count against the ceiling until the arena is collected.  On this error, it may make sense to
+/// return from `mutate`, call `collect_all` on the arena, and try again.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AllocError;
+
+#[cfg(feature = "std")]
+impl StdError for AllocError {}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "allocation would exceed the arena heap limit")
     }
 }
 
+/// Describes the work done by a call to an arena's `collect_all_outcome`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CollectionOutcome {
+    /// The total size in bytes of every object freed during the call.
+    pub bytes_freed: usize,
+    /// The number of objects freed during the call.
+    pub objects_freed: usize,
+    /// True if the collector was sleeping when called, so that a new cycle was started and run to
+    /// completion, false if an in progress cycle was finished.
+    pub started_cycle: bool,
+}
+
 /// Handle value given by arena callbacks during garbage collection, which must be passed through
 /// `Collect::trace` implementations.
 #[derive(Copy, Clone)]
 pub struct CollectionContext<'context> {
     context: &'context Context,
+    // When set, tracing only records what it visits here and leaves the collector state alone.
+    #[cfg(feature = "gc-debug")]
+    reachable: Option<&'context Reachable>,
 }
 
 impl<'context> CollectionContext<'context> {
-    pub(crate) unsafe fn trace<T: Collect>(self, ptr: NonNull<GcBox<T>>) {
-        self.context.trace(ptr)
+    pub(crate) unsafe fn trace<T: ?Sized>(self, ptr: NonNull<GcBox<T>>) {
+        #[cfg(feature = "gc-debug")]
+        {
+            if let Some(reachable) = self.reachable {
+                if reachable.visited.borrow_mut().insert(ptr.cast()) {
+                    reachable.pending.borrow_mut().push(ptr.cast());
+                }
+                return;
+            }
+        }
+
+        self.context.trace(ptr.cast())
+    }
+
+    // Returns true if the given object has been reached by the collector in the current cycle.
+    pub(crate) unsafe fn is_reached<T: ?Sized>(self, ptr: NonNull<GcBox<T>>) -> bool {
+        #[cfg(feature = "gc-debug")]
+        {
+            if let Some(reachable) = self.reachable {
+                return reachable.visited.borrow().contains(&ptr.cast());
+            }
+        }
+
+        ptr.cast::<GcBoxHeader>().as_ref().flags.color() != GcColor::White
+    }
+
+    // Records an ephemeron whose key has not been reached yet, to be checked again at the end of
+    // the propagate phase.  The ephemeron must be held inside a `GcBox` that is being traced.
+    pub(crate) unsafe fn defer_ephemeron<'gc>(self, ephemeron: &(dyn ErasedEphemeron + 'gc)) {
+        let ephemeron = static_ephemeron(NonNull::from(ephemeron));
+
+        #[cfg(feature = "gc-debug")]
+        {
+            if let Some(reachable) = self.reachable {
+                reachable.ephemerons.borrow_mut().push(ephemeron);
+                return;
+            }
+        }
+
+        self.context.ephemerons.borrow_mut().push(ephemeron);
     }
 }
 
+// The state of a trace started by `MutationContext::reachable_from`, which follows the same edges
+// as the collector but keeps its own visited set rather than coloring objects.
+#[cfg(feature = "gc-debug")]
+#[derive(Default)]
+struct Reachable {
+    visited: RefCell<HashSet<NonNull<GcBoxHeader>>>,
+    pending: RefCell<Vec<NonNull<GcBoxHeader>>>,
+    ephemerons: RefCell<Vec<NonNull<dyn ErasedEphemeron>>>,
+}
+
+// Type erased access to a deferred `Ephemeron`.
+pub(crate) trait ErasedEphemeron {
+    // If the key of this ephemeron has been reached, traces the value and returns true.
+    unsafe fn try_trace(&self, cc: CollectionContext) -> bool;
+
+    // Marks this ephemeron as dead, because its key was never reached.
+    fn kill(&self);
+}
+
 // Main gc context type, public because it must be accessible from the `make_arena!` macro.
 #[doc(hidden)]
 pub struct Context {
     parameters: ArenaParameters,
 
-    phase: Cell<Phase>,
+    phase: Cell<GcPhase>,
     total_allocated: Cell<usize>,
     remembered_size: Cell<usize>,
     wakeup_total: Cell<usize>,
     allocation_debt: Cell<f64>,
-
-    all: Cell<Option<NonNull<GcBox<Collect>>>>,
-    sweep: Cell<Option<NonNull<GcBox<Collect>>>>,
-    sweep_prev: Cell<Option<NonNull<GcBox<Collect>>>>,
-
-    gray: RefCell<Vec<NonNull<GcBox<Collect>>>>,
-    gray_again: RefCell<Vec<NonNull<GcBox<Collect>>>>,
+    bytes_freed: Cell<usize>,
+    objects_freed: Cell<usize>,
+    // Set for the duration of `do_collection`, so that allocating or mutating the heap from
+    // within `Collect::trace` or a `Drop` impl is caught rather than corrupting the object lists.
+    in_collection: Cell<bool>,
+
+    all: Cell<Option<NonNull<GcBoxHeader>>>,
+    sweep: Cell<Option<NonNull<GcBoxHeader>>>,
+    sweep_prev: Cell<Option<NonNull<GcBoxHeader>>>,
+
+    gray: RefCell<Vec<NonNull<GcBoxHeader>>>,
+    gray_again: RefCell<Vec<NonNull<GcBoxHeader>>>,
+    ephemerons: RefCell<Vec<NonNull<dyn ErasedEphemeron>>>,
+
+    observer: RefCell<Option<Rc<dyn GcObserver>>>,
+
+    #[cfg(feature = "gc-debug")]
+    phase_history: RefCell<VecDeque<(GcPhase, usize)>>,
+    // Unique among all arenas, and recorded in every object this arena allocates, so that passing a
+    // pointer from another arena to the collector can be caught.
+    #[cfg(feature = "gc-debug")]
+    id: usize,
 }
 
 impl Drop for Context {
     fn drop(&mut self) {
-        struct DropAll(Option<NonNull<GcBox<Collect>>>);
+        struct DropAll<'a>(Option<NonNull<GcBoxHeader>>, &'a dyn GcAllocator);
 
-        impl Drop for DropAll {
+        impl<'a> Drop for DropAll<'a> {
             fn drop(&mut self) {
                 unsafe {
                     if let Some(ptr) = self.0.take() {
-                        let mut drop_resume = DropAll(Some(ptr));
+                        let mut drop_resume = DropAll(Some(ptr), self.1);
                         while let Some(ptr) = drop_resume.0.take() {
-                            let gc_box = ptr.as_ref();
-                            drop_resume.0 = gc_box.next.get();
-                            Box::from_raw(ptr.as_ptr());
+                            drop_resume.0 = ptr.as_ref().next.get();
+                            GcBoxHeader::free(ptr, self.1);
                         }
                     }
                 }
             }
         }
 
-        DropAll(self.all.get());
+        DropAll(self.all.get(), &*self.parameters.allocator);
     }
 }
 
@@ -84,16 +261,28 @@ impl Context {
     pub unsafe fn new(parameters: ArenaParameters) -> Context {
         Context {
             parameters,
-            phase: Cell::new(Phase::Wake),
+            phase: Cell::new(GcPhase::Wake),
             total_allocated: Cell::new(0),
             remembered_size: Cell::new(0),
             wakeup_total: Cell::new(0),
             allocation_debt: Cell::new(0.0),
+            bytes_freed: Cell::new(0),
+            objects_freed: Cell::new(0),
+            in_collection: Cell::new(false),
             all: Cell::new(None),
             sweep: Cell::new(None),
             sweep_prev: Cell::new(None),
             gray: RefCell::new(Vec::new()),
             gray_again: RefCell::new(Vec::new()),
+            ephemerons: RefCell::new(Vec::new()),
+            observer: RefCell::new(None),
+            #[cfg(feature = "gc-debug")]
+            phase_history: RefCell::new(VecDeque::new()),
+            #[cfg(feature = "gc-debug")]
+            id: {
+                static NEXT_ARENA_ID: AtomicUsize = AtomicUsize::new(1);
+                NEXT_ARENA_ID.fetch_add(1, Ordering::Relaxed)
+            },
         }
     }
 
@@ -118,10 +307,56 @@ impl Context {
         self.total_allocated.get()
     }
 
+    // The total number of bytes freed by the collector over the lifetime of this context.
+    #[inline]
+    pub fn bytes_freed(&self) -> usize {
+        self.bytes_freed.get()
+    }
+
+    // The total number of objects freed by the collector over the lifetime of this context.
+    #[inline]
+    pub fn objects_freed(&self) -> usize {
+        self.objects_freed.get()
+    }
+
+    #[inline]
+    pub fn phase(&self) -> GcPhase {
+        self.phase.get()
+    }
+
+    pub fn set_observer(&self, observer: Option<Rc<dyn GcObserver>>) {
+        *self.observer.borrow_mut() = observer;
+    }
+
+    // Returns the addresses of every object currently allocated in this context, in no particular
+    // order.
+    pub fn object_ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut next = self.all.get();
+        while let Some(ptr) = next {
+            ids.push(ptr.as_ptr() as usize);
+            next = unsafe { ptr.as_ref().next.get() };
+        }
+        ids
+    }
+
+    // Returns the most recent phase transitions, oldest first, along with the total allocated bytes
+    // at each.  Always empty unless the `gc-debug` feature is enabled.
+    pub fn phase_history(&self) -> Vec<(GcPhase, usize)> {
+        #[cfg(feature = "gc-debug")]
+        {
+            self.phase_history.borrow().iter().copied().collect()
+        }
+        #[cfg(not(feature = "gc-debug"))]
+        {
+            Vec::new()
+        }
+    }
+
     // If the garbage collector is currently in the sleep phase, transition to the wake phase.
     pub fn wake(&self) {
-        if self.phase.get() == Phase::Sleep {
-            self.phase.set(Phase::Wake);
+        if self.phase.get() == GcPhase::Sleep {
+            self.set_phase(GcPhase::Wake);
         }
     }
 
@@ -134,14 +369,38 @@ impl Context {
     // In order for this to be safe, at the time of call no `Gc` pointers can be live that are not
     // reachable from the given root object.
     pub unsafe fn do_collection<R: Collect>(&self, root: &R, work: f64) -> f64 {
+        struct InCollection<'a>(&'a Cell<bool>);
+
+        impl<'a> Drop for InCollection<'a> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+
+        debug_assert!(
+            !self.in_collection.get(),
+            "garbage collection re-entered from within a collection"
+        );
+        self.in_collection.set(true);
+        let _in_collection = InCollection(&self.in_collection);
+        // Dropped only if tracing or dropping an object panics, and forgotten otherwise.
+        let abort = AbortCollection(self);
+
         let mut work_done = 0.0;
-        let cc = CollectionContext { context: self };
+        let cc = CollectionContext {
+            context: self,
+            #[cfg(feature = "gc-debug")]
+            reachable: None,
+        };
 
         while work > work_done {
             match self.phase.get() {
-                Phase::Wake => {
+                GcPhase::Wake => {
                     // In the Wake phase, we trace the root object and add its children to the gray
-                    // queue, and transition to the propagate phase.
+                    // queue, and transition to the propagate phase.  The remembered size is
+                    // recounted during every sweep, so that the next sleep is based only on what
+                    // survived this cycle.
+                    self.remembered_size.set(0);
                     root.trace(cc);
 
                     let root_size = mem::size_of::<R>() as f64;
@@ -149,16 +408,16 @@ impl Context {
                     self.allocation_debt
                         .set((self.allocation_debt.get() - root_size).max(0.0));
 
-                    self.phase.set(Phase::Propagate);
+                    self.set_phase(GcPhase::Propagate);
                 }
-                Phase::Propagate => {
+                GcPhase::Propagate => {
                     // We look for an object first in the normal gray queue, then the "gray again"
                     // queue.  Objects from the normal gray queue count as regular work, but objects
                     // which are gray a second time have already been counted as work, so we don't
                     // double count them.  Processing "gray again" objects later also gives them
                     // more time to be mutated again without triggering another write barrier.
                     let next_gray = if let Some(ptr) = self.gray.borrow_mut().pop() {
-                        let gray_size = mem::size_of_val(ptr.as_ref()) as f64;
+                        let gray_size = GcBoxHeader::layout(ptr).size() as f64;
                         work_done += gray_size;
                         self.allocation_debt
                             .set((self.allocation_debt.get() - gray_size).max(0.0));
@@ -172,20 +431,26 @@ impl Context {
                     if let Some(ptr) = next_gray {
                         // If we have an object in the gray queue, take one, trace it, and turn it
                         // black.
-                        let gc_box = ptr.as_ref();
-                        (*gc_box.value.get()).trace(cc);
-                        gc_box.flags.set_color(GcColor::Black);
+                        GcBoxHeader::trace_value(ptr, cc);
+                        ptr.as_ref().flags.set_color(GcColor::Black);
+                    } else if self.retrace_ephemerons(cc) {
+                        // Some deferred ephemerons have had their keys reached, so their values
+                        // have been traced and there may be more gray objects to process.
                     } else {
-                        // If we have no objects left in the normal gray queue, we enter the sweep
-                        // phase.
-                        self.phase.set(Phase::Sweep);
+                        // If we have no objects left in the normal gray queue, and no more
+                        // ephemeron keys can be reached, the remaining ephemerons are dead and we
+                        // enter the sweep phase.
+                        for ephemeron in self.ephemerons.borrow_mut().drain(..) {
+                            ephemeron.as_ref().kill();
+                        }
+                        self.set_phase(GcPhase::Sweep);
                         self.sweep.set(self.all.get());
                     }
                 }
-                Phase::Sweep => {
+                GcPhase::Sweep => {
                     if let Some(sweep_ptr) = self.sweep.get() {
                         let sweep = sweep_ptr.as_ref();
-                        let sweep_size = mem::size_of_val(sweep);
+                        let sweep_size = GcBoxHeader::layout(sweep_ptr).size();
 
                         let next_ptr = sweep.next.get();
                         self.sweep.set(next_ptr);
@@ -206,10 +471,15 @@ impl Context {
                             }
                             self.total_allocated
                                 .set(self.total_allocated.get() - sweep_size);
+                            self.bytes_freed.set(self.bytes_freed.get() + sweep_size);
+                            self.objects_freed.set(self.objects_freed.get() + 1);
                             work_done += sweep_size as f64;
                             self.allocation_debt
                                 .set((self.allocation_debt.get() - sweep_size as f64).max(0.0));
-                            Box::from_raw(sweep_ptr.as_ptr());
+                            GcBoxHeader::free(sweep_ptr, &*self.parameters.allocator);
+                            if let Some(observer) = &*self.observer.borrow() {
+                                observer.on_object_freed(sweep_size);
+                            }
                         } else {
                             // If the next object in the sweep portion of the main list is black, we
                             // need to keep it but turn it back white.  No gray objects should be in
@@ -225,39 +495,145 @@ impl Context {
                     } else {
                         // We are done sweeping, so enter the sleeping phase.
                         self.sweep_prev.set(None);
-                        self.phase.set(Phase::Sleep);
+                        self.set_phase(GcPhase::Sleep);
 
                         // Do not let debt accumulate across cycles, when we enter sleep, zero the debt out.
                         self.allocation_debt.set(0.0);
 
+                        // The sleep size is rounded by hand, as `f64::round` is not in `core`.
                         self.wakeup_total.set(
                             self.total_allocated.get()
                                 + ((self.remembered_size.get() as f64
-                                    * self.parameters.pause_factor)
-                                    .round()
+                                    * self.parameters.pause_factor
+                                    + 0.5)
                                     .min(usize::MAX as f64)
                                     as usize)
                                     .max(self.parameters.min_sleep),
                         );
                     }
                 }
-                Phase::Sleep => break,
+                GcPhase::Sleep => break,
             }
         }
 
+        mem::forget(abort);
         work_done
     }
 
+    // Puts the collector back into a consistent sleeping state after a panic in the middle of a
+    // collection.  The interrupted cycle is thrown away: every object is kept and turned white
+    // again, so the next cycle starts from scratch and nothing is freed based on partial marking.
+    // Nothing here calls back into user code, since it runs during unwinding.
+    unsafe fn abort_collection(&self) {
+        self.gray.borrow_mut().clear();
+        self.gray_again.borrow_mut().clear();
+        self.ephemerons.borrow_mut().clear();
+
+        let mut next = self.all.get();
+        while let Some(ptr) = next {
+            ptr.as_ref().flags.set_color(GcColor::White);
+            next = ptr.as_ref().next.get();
+        }
+
+        self.sweep.set(None);
+        self.sweep_prev.set(None);
+        self.allocation_debt.set(0.0);
+        self.phase.set(GcPhase::Sleep);
+    }
+
+    // Traces the values of any deferred ephemerons whose keys have since been reached, returning
+    // true if there were any.
+    unsafe fn retrace_ephemerons(&self, cc: CollectionContext) -> bool {
+        let deferred = mem::take(&mut *self.ephemerons.borrow_mut());
+        let mut progress = false;
+        let mut remaining = Vec::new();
+        for ephemeron in deferred {
+            if ephemeron.as_ref().try_trace(cc) {
+                progress = true;
+            } else {
+                remaining.push(ephemeron);
+            }
+        }
+        self.ephemerons.borrow_mut().extend(remaining);
+        progress
+    }
+
+    fn set_phase(&self, phase: GcPhase) {
+        let from = self.phase.replace(phase);
+        #[cfg(feature = "gc-debug")]
+        {
+            const PHASE_HISTORY_LEN: usize = 64;
+
+            let mut history = self.phase_history.borrow_mut();
+            if history.len() == PHASE_HISTORY_LEN {
+                history.pop_front();
+            }
+            history.push_back((phase, self.total_allocated.get()));
+        }
+        if let Some(observer) = &*self.observer.borrow() {
+            observer.on_phase_change(from, phase);
+        }
+    }
+
     unsafe fn allocate<T: Collect>(&self, t: T) -> NonNull<GcBox<T>> {
-        let alloc_size = mem::size_of::<GcBox<T>>();
+        self.try_allocate(t)
+            .expect("allocation exceeded the arena's max_heap or max_object_size")
+    }
+
+    unsafe fn try_allocate<T: Collect>(&self, t: T) -> Result<NonNull<GcBox<T>>, AllocError> {
+        self.assert_not_collecting();
+        let reservation = self.reserve(mem::size_of::<GcBox<T>>())?;
+        let ptr = GcBox::new(t, &*self.parameters.allocator);
+        self.link(ptr.cast());
+        mem::forget(reservation);
+        Ok(ptr)
+    }
+
+    unsafe fn try_allocate_slice<T: Collect>(
+        &self,
+        values: Vec<T>,
+    ) -> Result<NonNull<GcBox<[T]>>, AllocError> {
+        self.assert_not_collecting();
+        let reservation = self.reserve(GcBox::<[T]>::slice_layout(values.len()).0.size())?;
+        let ptr = GcBox::new_slice(values, &*self.parameters.allocator);
+        self.link(ptr.cast());
+        mem::forget(reservation);
+        Ok(ptr)
+    }
+
+    #[inline]
+    fn assert_not_collecting(&self) {
+        debug_assert!(
+            !self.in_collection.get(),
+            "the gc heap was mutated during collection, `Collect::trace` and `Drop` impls must not \
+             allocate or write to `Gc` pointers"
+        );
+    }
+
+    // Accounts for a new allocation of the given size, failing if it is larger than
+    // `max_object_size` or would exceed `max_heap`.  The returned reservation must be forgotten once
+    // the allocation has been linked, if it is dropped instead the size is given back.
+    fn reserve(&self, alloc_size: usize) -> Result<Reservation<'_>, AllocError> {
+        if let Some(max_object_size) = self.parameters.max_object_size {
+            if alloc_size > max_object_size {
+                return Err(AllocError);
+            }
+        }
+        if let Some(max_heap) = self.parameters.max_heap {
+            if self.total_allocated.get().saturating_add(alloc_size) > max_heap {
+                return Err(AllocError);
+            }
+        }
+
         self.total_allocated
             .set(self.total_allocated.get() + alloc_size);
-        if self.phase.get() == Phase::Sleep && self.total_allocated.get() > self.wakeup_total.get()
+        if self.phase.get() == GcPhase::Sleep
+            && self.total_allocated.get() > self.wakeup_total.get()
         {
-            self.phase.set(Phase::Wake);
+            self.set_phase(GcPhase::Wake);
         }
 
-        if self.phase.get() != Phase::Sleep {
+        if self.phase.get() != GcPhase::Sleep {
             self.allocation_debt.set(
                 self.allocation_debt.get()
                     + alloc_size as f64
@@ -265,33 +641,49 @@ impl Context {
             );
         }
 
-        let gc_box = GcBox {
-            flags: GcFlags::new(),
-            next: Cell::new(self.all.get()),
-            value: UnsafeCell::new(t),
-        };
-        gc_box.flags.set_needs_trace(T::needs_trace());
-        let ptr = NonNull::new_unchecked(Box::into_raw(Box::new(gc_box)));
-        self.all.set(Some(static_gc_box(ptr)));
-        if self.phase.get() == Phase::Sweep && self.sweep_prev.get().is_none() {
+        Ok(Reservation {
+            context: self,
+            size: alloc_size,
+        })
+    }
+
+    // Adds a newly allocated object to the main object list.
+    unsafe fn link(&self, ptr: NonNull<GcBoxHeader>) {
+        #[cfg(feature = "gc-debug")]
+        ptr.as_ref().arena.set(self.id);
+        ptr.as_ref().next.set(self.all.get());
+        self.all.set(Some(ptr));
+        if self.phase.get() == GcPhase::Sweep && self.sweep_prev.get().is_none() {
             self.sweep_prev.set(self.all.get());
         }
+    }
 
-        ptr
+    #[inline]
+    unsafe fn assert_owned(&self, _ptr: NonNull<GcBoxHeader>) {
+        #[cfg(feature = "gc-debug")]
+        debug_assert_eq!(
+            _ptr.as_ref().arena.get(),
+            self.id,
+            "a `Gc` pointer from another arena was passed to this arena's collector"
+        );
     }
 
-    unsafe fn write_barrier<T: Collect>(&self, ptr: NonNull<GcBox<T>>) {
+    unsafe fn write_barrier(&self, ptr: NonNull<GcBoxHeader>) {
+        self.assert_not_collecting();
+        self.assert_owned(ptr);
+
         // During the propagating phase, if we are mutating a black object, we may add a white
         // object to it and invalidate the invariant that black objects may not point to white
         // objects.  Turn black obejcts to gray to prevent this.
         let gc_box = ptr.as_ref();
-        if self.phase.get() == Phase::Propagate && gc_box.flags.color() == GcColor::Black {
+        if self.phase.get() == GcPhase::Propagate && gc_box.flags.color() == GcColor::Black {
             gc_box.flags.set_color(GcColor::Gray);
-            self.gray_again.borrow_mut().push(static_gc_box(ptr));
+            self.gray_again.borrow_mut().push(ptr);
         }
     }
 
-    unsafe fn trace<T: Collect>(&self, ptr: NonNull<GcBox<T>>) {
+    unsafe fn trace(&self, ptr: NonNull<GcBoxHeader>) {
+        self.assert_owned(ptr);
         let gc_box = ptr.as_ref();
         match gc_box.flags.color() {
             GcColor::Black | GcColor::Gray => {}
@@ -300,7 +692,7 @@ impl Context {
                     // A white traceable object is not in the gray queue, becomes gray and enters
                     // the normal gray queue.
                     gc_box.flags.set_color(GcColor::Gray);
-                    self.gray.borrow_mut().push(static_gc_box(ptr));
+                    self.gray.borrow_mut().push(ptr);
                 } else {
                     // A white object that doesn't need tracing simply becomes black.
                     gc_box.flags.set_color(GcColor::Black);
@@ -310,14 +702,42 @@ impl Context {
     }
 }
 
+// Restores the collector to a consistent state when dropped, see `Context::abort_collection`.
+struct AbortCollection<'a>(&'a Context);
+
+impl<'a> Drop for AbortCollection<'a> {
+    fn drop(&mut self) {
+        unsafe { self.0.abort_collection() }
+    }
+}
+
+// Gives back the size accounted for by `Context::reserve` when dropped, so that an allocator which
+// panics does not leave `total_allocated` counting an object that was never created.
+struct Reservation<'a> {
+    context: &'a Context,
+    size: usize,
+}
+
+impl<'a> Drop for Reservation<'a> {
+    fn drop(&mut self) {
+        let context = self.context;
+        context
+            .total_allocated
+            .set(context.total_allocated.get() - self.size);
+    }
+}
+
+/// The phases of an incremental garbage collection cycle.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum Phase {
+pub enum GcPhase {
     Wake,
     Propagate,
     Sweep,
     Sleep,
 }
 
-unsafe fn static_gc_box<'gc>(ptr: NonNull<GcBox<Collect + 'gc>>) -> NonNull<GcBox<Collect>> {
+unsafe fn static_ephemeron<'gc>(
+    ptr: NonNull<dyn ErasedEphemeron + 'gc>,
+) -> NonNull<dyn ErasedEphemeron> {
     mem::transmute(ptr)
 }
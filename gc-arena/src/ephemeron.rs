@@ -0,0 +1,108 @@
+use core::cell::Cell;
+
+use crate::collect::Collect;
+use crate::context::{CollectionContext, ErasedEphemeron, MutationContext};
+use crate::gc::Gc;
+
+/// A garbage collected key / value pair which holds its key weakly, and only keeps its value alive
+/// for as long as its key is reachable through some path other than this ephemeron.  This is the
+/// building block for weak keyed tables.
+///
+/// Once the collector finds the key unreachable, the ephemeron becomes "dead": both its key and its
+/// value are released, and `Ephemeron::get` returns `None` from then on.  A value which refers
+/// back to its own key does not keep the key alive.
+///
+/// The key may be unsized, so an erased `Gc<'gc, dyn Collect + 'gc>` can be used to hold objects of
+/// different types weakly.
+pub struct Ephemeron<'gc, K: 'gc + ?Sized, V: 'gc + Collect>(Gc<'gc, EphemeronState<'gc, K, V>>);
+
+impl<'gc, K: 'gc + ?Sized, V: 'gc + Collect> Copy for Ephemeron<'gc, K, V> {}
+
+impl<'gc, K: 'gc + ?Sized, V: 'gc + Collect> Clone for Ephemeron<'gc, K, V> {
+    fn clone(&self) -> Ephemeron<'gc, K, V> {
+        *self
+    }
+}
+
+unsafe impl<'gc, K: 'gc + ?Sized, V: 'gc + Collect> Collect for Ephemeron<'gc, K, V> {
+    fn trace(&self, cc: CollectionContext) {
+        self.0.trace(cc)
+    }
+}
+
+impl<'gc, K: 'gc + ?Sized, V: 'gc + Collect> Ephemeron<'gc, K, V> {
+    pub fn new(mc: MutationContext<'gc, '_>, key: Gc<'gc, K>, value: V) -> Ephemeron<'gc, K, V> {
+        Ephemeron(Gc::allocate(
+            mc,
+            EphemeronState {
+                key,
+                value,
+                dead: Cell::new(false),
+            },
+        ))
+    }
+
+    /// Returns the key and value if the key is still alive.
+    pub fn get(&self) -> Option<(Gc<'gc, K>, &V)> {
+        if self.0.dead.get() {
+            None
+        } else {
+            Some((self.0.key, &self.0.value))
+        }
+    }
+
+    pub fn key(&self) -> Option<Gc<'gc, K>> {
+        self.get().map(|(k, _)| k)
+    }
+
+    pub fn value(&self) -> Option<&V> {
+        self.get().map(|(_, v)| v)
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.0.dead.get()
+    }
+
+    pub fn ptr_eq(this: Ephemeron<'gc, K, V>, other: Ephemeron<'gc, K, V>) -> bool {
+        Gc::ptr_eq(this.0, other.0)
+    }
+}
+
+struct EphemeronState<'gc, K: 'gc + ?Sized, V: 'gc + Collect> {
+    key: Gc<'gc, K>,
+    value: V,
+    dead: Cell<bool>,
+}
+
+// The key is deliberately never traced here.  If the key has already been reached, the value is
+// traced as normal, otherwise the ephemeron is deferred and the collector checks it again once it
+// runs out of other gray objects.  Ephemerons whose keys are never reached are marked dead before
+// sweeping, so the untraced pointers they hold are never accessed again.
+unsafe impl<'gc, K: 'gc + ?Sized, V: 'gc + Collect> Collect for EphemeronState<'gc, K, V> {
+    fn trace(&self, cc: CollectionContext) {
+        if self.dead.get() {
+            return;
+        }
+
+        if unsafe { cc.is_reached(self.key.ptr) } {
+            self.value.trace(cc);
+        } else {
+            unsafe { cc.defer_ephemeron(self) };
+        }
+    }
+}
+
+impl<'gc, K: 'gc + ?Sized, V: 'gc + Collect> ErasedEphemeron for EphemeronState<'gc, K, V> {
+    unsafe fn try_trace(&self, cc: CollectionContext) -> bool {
+        if cc.is_reached(self.key.ptr) {
+            self.value.trace(cc);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn kill(&self) {
+        self.dead.set(true);
+    }
+}
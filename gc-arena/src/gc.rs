@@ -1,23 +1,45 @@
-use std::fmt::{self, Debug};
-use std::marker::PhantomData;
-use std::ops::Deref;
-use std::ptr::NonNull;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::NonNull;
 
 use crate::collect::Collect;
-use crate::context::{CollectionContext, MutationContext};
-use crate::types::{GcBox, Invariant};
+use crate::context::{AllocError, CollectionContext, MutationContext};
+use crate::types::{GcBox, GcColor, Invariant};
 
 /// A garbage collected pointer to a type T.  Implements Copy, and is implemented as a plain machine
 /// pointer.  You can only allocate `Gc` pointers through an `Allocator` inside an arena type, and
 /// through "generativity" such `Gc` pointers may not escape the arena they were born in or be
 /// stored inside TLS.  This, combined with correct `Collect` implementations, means that `Gc`
 /// pointers will never be dangling and are always safe to access.
-pub struct Gc<'gc, T: 'gc + Collect> {
+///
+/// In particular, there is no way to observe a `Gc` after its arena has been dropped, so there is
+/// no "detached" state that needs to be checked for at runtime.  A `Gc` cannot even be returned
+/// from a `mutate` call:
+///
+/// ```compile_fail
+/// use gc_arena::{make_arena, ArenaParameters, Collect, Gc};
+///
+/// #[derive(Collect)]
+/// #[collect(empty_drop)]
+/// struct TestRoot<'gc>(Gc<'gc, i32>);
+/// make_arena!(TestArena, TestRoot);
+///
+/// let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+///     TestRoot(Gc::allocate(mc, 42))
+/// });
+/// let escaped = arena.mutate(|_, root| root.0);
+/// drop(arena);
+/// assert_eq!(*escaped, 42);
+/// ```
+pub struct Gc<'gc, T: 'gc + ?Sized> {
     pub(crate) ptr: NonNull<GcBox<T>>,
     _invariant: Invariant<'gc>,
 }
 
-impl<'gc, T: 'gc + Collect> Debug for Gc<'gc, T> {
+impl<'gc, T: 'gc + ?Sized> Debug for Gc<'gc, T> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Gc")
             .field("ptr", unsafe { &self.ptr.as_ref().value.get() })
@@ -25,15 +47,15 @@ impl<'gc, T: 'gc + Collect> Debug for Gc<'gc, T> {
     }
 }
 
-impl<'gc, T: Collect + 'gc> Copy for Gc<'gc, T> {}
+impl<'gc, T: 'gc + ?Sized> Copy for Gc<'gc, T> {}
 
-impl<'gc, T: Collect + 'gc> Clone for Gc<'gc, T> {
+impl<'gc, T: 'gc + ?Sized> Clone for Gc<'gc, T> {
     fn clone(&self) -> Gc<'gc, T> {
         *self
     }
 }
 
-unsafe impl<'gc, T: 'gc + Collect> Collect for Gc<'gc, T> {
+unsafe impl<'gc, T: 'gc + ?Sized> Collect for Gc<'gc, T> {
     fn trace(&self, cc: CollectionContext) {
         unsafe {
             cc.trace(self.ptr);
@@ -41,7 +63,7 @@ unsafe impl<'gc, T: 'gc + Collect> Collect for Gc<'gc, T> {
     }
 }
 
-impl<'gc, T: Collect + 'gc> Deref for Gc<'gc, T> {
+impl<'gc, T: 'gc + ?Sized> Deref for Gc<'gc, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
@@ -50,6 +72,12 @@ impl<'gc, T: Collect + 'gc> Deref for Gc<'gc, T> {
 }
 
 impl<'gc, T: 'gc + Collect> Gc<'gc, T> {
+    /// Allocates `t` in the arena.
+    ///
+    /// Collection never runs during a call to `mutate`, so the returned pointer stays valid for the
+    /// rest of the call without any rooting, and objects can be allocated and linked together in
+    /// any order.  The pointer only needs to be reachable from the root once `mutate` returns, and
+    /// the lifetime on `Gc` ensures it cannot be observed afterwards if it is not.
     pub fn allocate(mc: MutationContext<'gc, '_>, t: T) -> Gc<'gc, T> {
         Gc {
             ptr: unsafe { mc.allocate(t) },
@@ -57,6 +85,17 @@ impl<'gc, T: 'gc + Collect> Gc<'gc, T> {
         }
     }
 
+    /// Like `Gc::allocate`, but returns `AllocError` rather than panicking if the allocation would
+    /// exceed the `max_heap` set in the arena's `ArenaParameters`.
+    pub fn try_allocate(mc: MutationContext<'gc, '_>, t: T) -> Result<Gc<'gc, T>, AllocError> {
+        Ok(Gc {
+            ptr: unsafe { mc.try_allocate(t)? },
+            _invariant: PhantomData,
+        })
+    }
+}
+
+impl<'gc, T: 'gc + ?Sized> Gc<'gc, T> {
     /// When implementing `Collect` on types with internal mutability containing `Gc` pointers, this
     /// method must be used to ensure safe mutability.  Safe to call, but only necessary from unsafe
     /// code.
@@ -67,10 +106,110 @@ impl<'gc, T: 'gc + Collect> Gc<'gc, T> {
     }
 
     pub fn ptr_eq(this: Gc<'gc, T>, other: Gc<'gc, T>) -> bool {
-        Gc::as_ptr(this) == Gc::as_ptr(other)
+        Gc::as_ptr(this) as *const () == Gc::as_ptr(other) as *const ()
     }
 
     pub fn as_ptr(gc: Gc<'gc, T>) -> *const T {
         unsafe { gc.ptr.as_ref().value.get() }
     }
+
+    /// Returns the current marking color of this object, for testing the collector.
+    pub fn color_of(gc: Gc<'gc, T>) -> GcColor {
+        unsafe { gc.ptr.as_ref().header.flags.color() }
+    }
+
+    /// Returns an address which identifies the allocation this pointer points to, which for an
+    /// erased or slice pointer is the same as for the original.
+    pub fn object_id(gc: Gc<'gc, T>) -> *const () {
+        gc.ptr.as_ptr() as *const ()
+    }
+
+    /// Returns the `Gc::object_id` of every object reachable from this one, including itself.
+    /// This follows the same `Collect::trace` edges as the collector, but does not
+    /// disturb any collection in progress.
+    ///
+    /// Panics if any reachable `GcCell` is currently borrowed for writing.
+    #[cfg(feature = "gc-debug")]
+    pub fn reachable_from(
+        mc: MutationContext<'gc, '_>,
+        gc: Gc<'gc, T>,
+    ) -> std::collections::HashSet<*const ()> {
+        unsafe { mc.reachable_from(gc.ptr) }
+    }
+
+    /// Returns the backtrace captured when this pointer was allocated.
+    #[cfg(feature = "gc-debug-backtrace")]
+    pub fn alloc_backtrace<'a>(gc: &'a Gc<'gc, T>) -> &'a std::backtrace::Backtrace {
+        unsafe { &gc.ptr.as_ref().header.backtrace }
+    }
+}
+
+impl<'gc, T: 'gc + Collect> Gc<'gc, T> {
+    /// Erases the type of this pointer, which may be recovered later with `Gc::downcast`.
+    pub fn erase(gc: Gc<'gc, T>) -> Gc<'gc, dyn Collect + 'gc> {
+        Gc {
+            ptr: gc.ptr,
+            _invariant: PhantomData,
+        }
+    }
+}
+
+impl<'gc> Gc<'gc, dyn Collect + 'gc> {
+    /// Returns a pointer to the concrete type held by this erased pointer, if that type is `T`.
+    pub fn downcast<T: AnyGcObject>(gc: Gc<'gc, dyn Collect + 'gc>) -> Option<Gc<'gc, T>> {
+        if unsafe { gc.ptr.as_ref().header.type_id() } == TypeId::of::<T>() {
+            Some(Gc {
+                ptr: gc.ptr.cast(),
+                _invariant: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Marker for types that an erased `Gc` may be downcast to.
+///
+/// The type of an allocation is recorded with every lifetime erased, since it may hold `'gc`
+/// pointers.  A type with no lifetime parameters can only ever match itself, so only such types
+/// can be safely recovered this way.
+///
+/// # Safety
+///
+/// The implementing type must not have any lifetime parameters, including `'static`, either
+/// directly or through its type parameters.
+pub unsafe trait AnyGcObject: Collect + 'static {}
+
+impl<'gc, T: 'gc + Collect> Gc<'gc, [T]> {
+    /// Allocates all of the given values as a single garbage collected slice, which is traced and
+    /// freed as a unit.  The collector traces each of the values in turn, and skips the slice
+    /// entirely if `T` never needs tracing.
+    pub fn allocate_slice(mc: MutationContext<'gc, '_>, values: Vec<T>) -> Gc<'gc, [T]> {
+        Gc::try_allocate_slice(mc, values).expect("allocation exceeded the arena's max_heap")
+    }
+
+    /// Like `Gc::allocate_slice`, but returns `AllocError` rather than panicking if the allocation
+    /// would exceed the `max_heap` set in the arena's `ArenaParameters`.
+    pub fn try_allocate_slice(
+        mc: MutationContext<'gc, '_>,
+        values: Vec<T>,
+    ) -> Result<Gc<'gc, [T]>, AllocError> {
+        Ok(Gc {
+            ptr: unsafe { mc.try_allocate_slice(values)? },
+            _invariant: PhantomData,
+        })
+    }
+
+    /// Returns the held slice, which lives as long as the arena's `'gc` lifetime.
+    pub fn as_slice(gc: Gc<'gc, [T]>) -> &'gc [T] {
+        unsafe { &*gc.ptr.as_ref().value.get() }
+    }
+
+    pub fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
 }
@@ -1,8 +1,8 @@
-use std::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
-use std::fmt::{self, Debug};
+use core::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
+use core::fmt::{self, Debug};
 
 use crate::collect::Collect;
-use crate::context::{CollectionContext, MutationContext};
+use crate::context::{AllocError, CollectionContext, MutationContext};
 use crate::gc::Gc;
 
 /// A garbage collected pointer to a type T that may be safely mutated.  When a type that may hold
@@ -42,6 +42,17 @@ impl<'gc, T: 'gc + Collect> GcCell<'gc, T> {
         ))
     }
 
+    /// Like `GcCell::allocate`, but returns `AllocError` rather than panicking if the allocation
+    /// would exceed the `max_heap` set in the arena's `ArenaParameters`.
+    pub fn try_allocate(mc: MutationContext<'gc, '_>, t: T) -> Result<GcCell<'gc, T>, AllocError> {
+        Ok(GcCell(Gc::try_allocate(
+            mc,
+            GcRefCell {
+                cell: RefCell::new(t),
+            },
+        )?))
+    }
+
     pub fn ptr_eq(this: GcCell<'gc, T>, other: GcCell<'gc, T>) -> bool {
         this.as_ptr() == other.as_ptr()
     }
@@ -50,6 +61,26 @@ impl<'gc, T: 'gc + Collect> GcCell<'gc, T> {
         self.0.cell.as_ptr()
     }
 
+    /// Equivalent to `Gc::object_id` for the allocation holding this cell.
+    pub fn object_id(self) -> *const () {
+        Gc::object_id(self.0)
+    }
+
+    /// Equivalent to `Gc::erase` for the allocation holding this cell.  The erased pointer has the
+    /// same `Gc::object_id` as the cell.
+    pub fn erase(cell: GcCell<'gc, T>) -> Gc<'gc, dyn Collect + 'gc> {
+        Gc::erase(cell.0)
+    }
+
+    /// Equivalent to `Gc::reachable_from`, starting from the allocation holding this cell.
+    #[cfg(feature = "gc-debug")]
+    pub fn reachable_from(
+        mc: MutationContext<'gc, '_>,
+        cell: GcCell<'gc, T>,
+    ) -> std::collections::HashSet<*const ()> {
+        Gc::reachable_from(mc, cell.0)
+    }
+
     pub fn read<'a>(&'a self) -> Ref<'a, T> {
         self.0.cell.borrow()
     }
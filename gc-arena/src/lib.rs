@@ -1,18 +1,33 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 #[doc(hidden)]
 pub use gc_arena_derive::*;
 
+// Lets `make_arena!` name `alloc` types from crates which do not declare it themselves.
+#[doc(hidden)]
+pub extern crate alloc as __alloc;
+
+mod allocator;
 mod arena;
 mod collect;
 mod collect_impl;
 mod context;
+mod ephemeron;
 mod gc;
 mod gc_cell;
 mod static_collect;
+mod traced_box;
 mod types;
 
+pub use self::allocator::*;
 pub use self::arena::*;
 pub use self::collect::*;
 pub use self::context::*;
+pub use self::ephemeron::*;
 pub use self::gc::*;
 pub use self::gc_cell::*;
 pub use self::static_collect::*;
+pub use self::traced_box::*;
+pub use self::types::GcColor;
@@ -0,0 +1,61 @@
+use core::fmt::{self, Debug};
+use core::ops::{Deref, DerefMut};
+
+use crate::collect::Collect;
+use crate::context::CollectionContext;
+
+/// A wrapper that implements `Collect` for a type which does not, by calling a trace function given
+/// at construction.
+///
+/// This allows storing a type from another crate that holds `Gc` pointers without running into the
+/// orphan rule, for example `Gc::allocate(mc, TracedBox::new(foreign, trace_foreign))`.
+pub struct TracedBox<T> {
+    value: T,
+    trace: fn(&T, CollectionContext),
+}
+
+impl<T> TracedBox<T> {
+    /// Wraps `value`, which will be traced by calling `trace` on it.
+    ///
+    /// # Safety
+    ///
+    /// The same rules as implementing `Collect` apply: `trace` must call `Collect::trace` on every
+    /// `Gc` pointer held inside `value`, `value` must not access any held `Gc` pointers in its
+    /// `Drop` impl, and it must not adopt new `Gc` pointers through internal mutability without a
+    /// write barrier.  Since a `TracedBox` may be mutated through `DerefMut`, this must continue to
+    /// hold for every value it is mutated into.
+    pub unsafe fn new(value: T, trace: fn(&T, CollectionContext)) -> TracedBox<T> {
+        TracedBox { value, trace }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for TracedBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for TracedBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Debug> Debug for TracedBox<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("TracedBox").field(&self.value).finish()
+    }
+}
+
+unsafe impl<T> Collect for TracedBox<T> {
+    #[inline]
+    fn trace(&self, cc: CollectionContext) {
+        (self.trace)(&self.value, cc)
+    }
+}
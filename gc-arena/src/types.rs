@@ -1,22 +1,239 @@
-use std::cell::{Cell, UnsafeCell};
-use std::marker::PhantomData;
-use std::ptr::NonNull;
+use alloc::alloc::{handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::any::TypeId;
+use core::cell::{Cell, UnsafeCell};
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::{self, NonNull};
+#[cfg(feature = "gc-debug-backtrace")]
+use std::backtrace::Backtrace;
 
+use crate::allocator::GcAllocator;
 use crate::collect::Collect;
+use crate::context::CollectionContext;
 
+/// The tri-color marking state of an object.  Outside of a collection every object is white.
+/// During propagation, objects reached by the collector become gray until their children are
+/// traced, after which they are black.  No black object may point to a white one.  Whatever is
+/// still white once propagation finishes is freed during the sweep.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub(crate) enum GcColor {
+pub enum GcColor {
     White,
     Gray,
     Black,
 }
 
-pub(crate) struct GcBox<T: Collect + ?Sized> {
-    pub(crate) flags: GcFlags,
-    pub(crate) next: Cell<Option<NonNull<GcBox<Collect>>>>,
+// `GcBox` is `repr(C)` so that a pointer to a `GcBox` of any type is also a valid pointer to its
+// header, allowing the collector to manage every allocation through thin header pointers.
+#[repr(C)]
+pub(crate) struct GcBox<T: ?Sized> {
+    pub(crate) header: GcBoxHeader,
     pub(crate) value: UnsafeCell<T>,
 }
 
+pub(crate) struct GcBoxHeader {
+    pub(crate) flags: GcFlags,
+    pub(crate) next: Cell<Option<NonNull<GcBoxHeader>>>,
+    pub(crate) vtable: &'static GcBoxVtable,
+    // The id of the arena this object was allocated in, set when it is linked into the arena.
+    #[cfg(feature = "gc-debug")]
+    pub(crate) arena: Cell<usize>,
+    #[cfg(feature = "gc-debug-backtrace")]
+    pub(crate) backtrace: Backtrace,
+}
+
+impl GcBoxHeader {
+    fn new(vtable: &'static GcBoxVtable, needs_trace: bool) -> GcBoxHeader {
+        let flags = GcFlags::new();
+        flags.set_needs_trace(needs_trace);
+        GcBoxHeader {
+            flags,
+            next: Cell::new(None),
+            vtable,
+            #[cfg(feature = "gc-debug")]
+            arena: Cell::new(0),
+            #[cfg(feature = "gc-debug-backtrace")]
+            backtrace: Backtrace::force_capture(),
+        }
+    }
+
+    // The layout of the entire allocation holding this header.
+    pub(crate) unsafe fn layout(ptr: NonNull<GcBoxHeader>) -> Layout {
+        (ptr.as_ref().vtable.layout)(ptr)
+    }
+
+    // Calls `Collect::trace` on the value held in this allocation.
+    pub(crate) unsafe fn trace_value(ptr: NonNull<GcBoxHeader>, cc: CollectionContext) {
+        (ptr.as_ref().vtable.trace)(ptr, cc)
+    }
+
+    // Drops the value held in this allocation and frees it to the allocator it came from.
+    pub(crate) unsafe fn free(ptr: NonNull<GcBoxHeader>, allocator: &dyn GcAllocator) {
+        (ptr.as_ref().vtable.free)(ptr, allocator)
+    }
+
+    // The `TypeId` of the type held in this allocation, with every lifetime replaced by `'static`.
+    pub(crate) fn type_id(&self) -> TypeId {
+        (self.vtable.type_id)()
+    }
+}
+
+// Type erased operations on a `GcBox`, which replace what would otherwise be the vtable of a
+// `GcBox<dyn Collect>`.  A `dyn Collect` cannot be made from a dynamically sized slice, so the
+// collector keeps its own.
+pub(crate) struct GcBoxVtable {
+    layout: unsafe fn(NonNull<GcBoxHeader>) -> Layout,
+    trace: unsafe fn(NonNull<GcBoxHeader>, CollectionContext),
+    free: unsafe fn(NonNull<GcBoxHeader>, &dyn GcAllocator),
+    type_id: fn() -> TypeId,
+}
+
+// Returns an allocation to its allocator when dropped, so that it is freed even if dropping the
+// value it held panics.
+struct Dealloc<'a> {
+    allocator: &'a dyn GcAllocator,
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+impl<'a> Drop for Dealloc<'a> {
+    fn drop(&mut self) {
+        unsafe { self.allocator.dealloc_box(self.ptr, self.layout) }
+    }
+}
+
+// `TypeId::of` requires `T: 'static`, which most `Collect` types are not since they hold `'gc`
+// pointers.  Calling it through a transmuted trait object gives the `TypeId` of `T` with all of its
+// lifetimes erased instead.
+fn erased_type_id<T: ?Sized>() -> TypeId {
+    trait NonStaticAny {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static;
+    }
+
+    impl<T: ?Sized> NonStaticAny for PhantomData<T> {
+        fn type_id(&self) -> TypeId
+        where
+            Self: 'static,
+        {
+            TypeId::of::<T>()
+        }
+    }
+
+    let phantom = PhantomData::<T>;
+    let any: &dyn NonStaticAny = &phantom;
+    unsafe { mem::transmute::<&dyn NonStaticAny, &(dyn NonStaticAny + 'static)>(any) }.type_id()
+}
+
+impl<T: Collect> GcBox<T> {
+    const VTABLE: GcBoxVtable = GcBoxVtable {
+        layout: |_| Layout::new::<GcBox<T>>(),
+        trace: |ptr, cc| unsafe { (*ptr.cast::<GcBox<T>>().as_ref().value.get()).trace(cc) },
+        free: |ptr, allocator| unsafe {
+            let _dealloc = Dealloc {
+                allocator,
+                ptr: ptr.as_ptr() as *mut u8,
+                layout: Layout::new::<GcBox<T>>(),
+            };
+            ptr::drop_in_place(ptr.cast::<GcBox<T>>().as_ptr());
+        },
+        type_id: erased_type_id::<T>,
+    };
+
+    pub(crate) fn new(t: T, allocator: &dyn GcAllocator) -> NonNull<GcBox<T>> {
+        let layout = Layout::new::<GcBox<T>>();
+        unsafe {
+            let ptr = allocator.alloc_box(layout) as *mut GcBox<T>;
+            if ptr.is_null() {
+                handle_alloc_error(layout);
+            }
+            ptr::write(
+                ptr,
+                GcBox {
+                    header: GcBoxHeader::new(&Self::VTABLE, T::needs_trace()),
+                    value: UnsafeCell::new(t),
+                },
+            );
+            NonNull::new_unchecked(ptr)
+        }
+    }
+}
+
+// Slices store their length in a `usize` placed immediately before the `GcBox`, so that it can be
+// recovered from a thin header pointer.
+impl<T: Collect> GcBox<[T]> {
+    const VTABLE: GcBoxVtable = GcBoxVtable {
+        layout: |ptr| unsafe { Self::slice_layout(Self::slice_len(ptr)).0 },
+        trace: |ptr, cc| unsafe {
+            for t in &*Self::from_header(ptr).as_ref().value.get() {
+                t.trace(cc)
+            }
+        },
+        free: |ptr, allocator| unsafe {
+            let (layout, box_offset) = Self::slice_layout(Self::slice_len(ptr));
+            let _dealloc = Dealloc {
+                allocator,
+                ptr: (ptr.as_ptr() as *mut u8).sub(box_offset),
+                layout,
+            };
+            ptr::drop_in_place(Self::from_header(ptr).as_ptr());
+        },
+        type_id: erased_type_id::<[T]>,
+    };
+
+    // Returns the layout of the entire allocation for a slice of the given length, along with the
+    // offset of the `GcBox` within it.
+    pub(crate) fn slice_layout(len: usize) -> (Layout, usize) {
+        let gc_box = Layout::new::<GcBoxHeader>()
+            .extend(Layout::array::<T>(len).expect("slice allocation too large"))
+            .expect("slice allocation too large")
+            .0;
+        let (layout, box_offset) = Layout::new::<usize>()
+            .extend(gc_box)
+            .expect("slice allocation too large");
+        (layout.pad_to_align(), box_offset)
+    }
+
+    pub(crate) fn new_slice(values: Vec<T>, allocator: &dyn GcAllocator) -> NonNull<GcBox<[T]>> {
+        let mut values = values;
+        let len = values.len();
+        let (layout, box_offset) = Self::slice_layout(len);
+        unsafe {
+            let base = allocator.alloc_box(layout);
+            if base.is_null() {
+                handle_alloc_error(layout);
+            }
+            *(base.add(box_offset - mem::size_of::<usize>()) as *mut usize) = len;
+
+            let header = NonNull::new_unchecked(base.add(box_offset) as *mut GcBoxHeader);
+            ptr::write(
+                header.as_ptr(),
+                GcBoxHeader::new(&Self::VTABLE, T::needs_trace()),
+            );
+
+            let gc_box = Self::from_header(header);
+            ptr::copy_nonoverlapping(
+                values.as_ptr(),
+                ptr::addr_of_mut!((*gc_box.as_ptr()).value) as *mut T,
+                len,
+            );
+            values.set_len(0);
+
+            gc_box
+        }
+    }
+
+    unsafe fn slice_len(ptr: NonNull<GcBoxHeader>) -> usize {
+        *((ptr.as_ptr() as *const u8).sub(mem::size_of::<usize>()) as *const usize)
+    }
+
+    unsafe fn from_header(ptr: NonNull<GcBoxHeader>) -> NonNull<GcBox<[T]>> {
+        let slice = ptr::slice_from_raw_parts_mut(ptr.as_ptr() as *mut T, Self::slice_len(ptr));
+        NonNull::new_unchecked(slice as *mut GcBox<[T]>)
+    }
+}
+
 pub(crate) struct GcFlags(Cell<u8>);
 
 impl GcFlags {
@@ -0,0 +1,44 @@
+// Exercises the arena using only `core` and `alloc`, run with `cargo test --no-default-features`.
+#![cfg(not(feature = "std"))]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use gc_arena::{make_arena, ArenaParameters, Collect, Gc, GcCell};
+
+#[test]
+fn no_std_allocate_collect() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        cell: GcCell<'gc, Vec<Gc<'gc, i64>>>,
+        queue: Gc<'gc, VecDeque<Gc<'gc, i64>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        cell: GcCell::allocate(mc, Vec::new()),
+        queue: Gc::allocate(mc, (0..4).map(|i| Gc::allocate(mc, i)).collect()),
+    });
+
+    arena.mutate(|mc, root| {
+        let mut cell = root.cell.write(mc);
+        for i in 0..64 {
+            cell.push(Gc::allocate(mc, i));
+            Gc::allocate(mc, -i);
+        }
+    });
+    arena.collect_all();
+    arena.collect_all();
+
+    arena.mutate(|mc, root| {
+        let cell = root.cell.read();
+        assert_eq!(cell.len(), 64);
+        assert!(cell.iter().enumerate().all(|(i, v)| **v == i as i64));
+        assert_eq!(*root.queue[3], 3);
+        Gc::allocate(mc, 0);
+    });
+}
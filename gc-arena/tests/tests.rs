@@ -1,9 +1,15 @@
+#![cfg(feature = "std")]
+
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
 use rand::distributions::Distribution;
 
-use gc_arena::{make_arena, unsafe_empty_collect, ArenaParameters, Collect, Gc, GcCell};
+use gc_arena::{
+    make_arena, unsafe_empty_collect, AllocError, AnyGcObject, ArenaParameters, Collect,
+    CollectionOutcome, Ephemeron, Gc, GcAllocator, GcCell, GcColor, GcObserver, GcPhase, TracedBox,
+};
 
 #[test]
 fn simple_allocation() {
@@ -179,4 +185,1145 @@ fn derive_collect() {
     assert_eq!(Test4::needs_trace(), false);
     assert_eq!(Test5::needs_trace(), true);
     assert_eq!(Test6::needs_trace(), false);
+    assert!(!<Box<[i32]>>::needs_trace());
+    assert!(<Box<[Gc<'static, i32>]>>::needs_trace());
+}
+
+#[test]
+fn max_heap() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Vec<Gc<'gc, i64>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default().set_max_heap(Some(4096)), |mc| {
+        TestRoot(GcCell::allocate(mc, Vec::new()))
+    });
+
+    // Fill the heap with garbage until the ceiling is reached.
+    arena.mutate(|mc, _| while Gc::try_allocate(mc, 0i64).is_ok() {});
+    assert!(arena.total_allocated() <= 4096);
+    assert_eq!(
+        arena.mutate(|mc, _| Gc::try_allocate(mc, 0i64).map(|_| ())),
+        Err(AllocError)
+    );
+
+    // Once the garbage is collected, allocation succeeds again.
+    arena.collect_all();
+    arena.mutate(|mc, root| {
+        let p = Gc::try_allocate(mc, 1).unwrap();
+        root.0.write(mc).push(p);
+    });
+
+    // Fill the heap with live data, which collection cannot free.
+    arena.mutate(|mc, root| {
+        while let Ok(p) = Gc::try_allocate(mc, 2) {
+            root.0.write(mc).push(p);
+        }
+    });
+    arena.collect_all();
+    assert_eq!(
+        arena.mutate(|mc, _| Gc::try_allocate(mc, 3i64).map(|_| ())),
+        Err(AllocError)
+    );
+}
+
+#[test]
+fn with_limits() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Option<Gc<'gc, [u8]>>);
+    make_arena!(TestArena, TestRoot);
+
+    // The allocation made while constructing the root is already subject to the limits.
+    let mut arena = TestArena::with_limits(ArenaParameters::default(), 4096, 128, |mc| {
+        TestRoot(Gc::try_allocate_slice(mc, vec![0u8; 256]).ok())
+    });
+    arena.mutate(|_, root| assert!(root.0.is_none()));
+    assert_eq!(arena.total_allocated(), 0);
+
+    arena.mutate(|mc, _| {
+        assert!(Gc::try_allocate(mc, [0u8; 32]).is_ok());
+        assert_eq!(
+            Gc::try_allocate_slice(mc, vec![0u8; 256]).map(|_| ()),
+            Err(AllocError)
+        );
+        while Gc::try_allocate(mc, [0u8; 32]).is_ok() {}
+    });
+    assert!(arena.total_allocated() <= 4096);
+    assert_eq!(
+        arena.mutate(|mc, _| Gc::try_allocate(mc, 0u8).map(|_| ())),
+        Err(AllocError)
+    );
+}
+
+#[cfg(feature = "gc-debug-backtrace")]
+#[test]
+fn alloc_backtrace() {
+    #[inline(never)]
+    fn allocating_function<'gc>(mc: gc_arena::MutationContext<'gc, '_>) -> Gc<'gc, i32> {
+        Gc::allocate(mc, 7)
+    }
+
+    gc_arena::rootless_arena(|mc| {
+        let p = allocating_function(mc);
+        let backtrace = Gc::alloc_backtrace(&p).to_string();
+        assert!(backtrace.contains("allocating_function"));
+    });
+}
+
+#[cfg(feature = "gc-debug")]
+#[test]
+fn reachable_from() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct Node<'gc> {
+        edges: Vec<GcCell<'gc, Node<'gc>>>,
+        data: Option<Gc<'gc, i32>>,
+        ephemeron: Option<Ephemeron<'gc, i32, Gc<'gc, i32>>>,
+    }
+
+    gc_arena::rootless_arena(|mc| {
+        let node = |edges| {
+            GcCell::allocate(
+                mc,
+                Node {
+                    edges,
+                    data: None,
+                    ephemeron: None,
+                },
+            )
+        };
+        let reaches = |from, to| GcCell::reachable_from(mc, from).contains(&to);
+
+        let x = node(vec![]);
+        let w = node(vec![]);
+        let y = node(vec![node(vec![x])]);
+        let z = node(vec![w]);
+        x.write(mc).edges.push(y);
+
+        assert!(reaches(y, x.object_id()));
+        assert!(reaches(x, y.object_id()));
+        assert!(reaches(y, y.object_id()));
+        assert!(!reaches(z, x.object_id()));
+        assert!(!reaches(y, w.object_id()));
+        assert_eq!(GcCell::reachable_from(mc, z).len(), 2);
+
+        // Ephemeron values are only reachable once their keys are.
+        let key = Gc::allocate(mc, 1);
+        let value = Gc::allocate(mc, 2);
+        y.write(mc).ephemeron = Some(Ephemeron::new(mc, key, value));
+        assert!(!reaches(y, Gc::object_id(key)));
+        assert!(!reaches(y, Gc::object_id(value)));
+
+        x.write(mc).data = Some(key);
+        assert!(reaches(y, Gc::object_id(key)));
+        assert!(reaches(y, Gc::object_id(value)));
+        assert!(!reaches(z, Gc::object_id(value)));
+    });
+}
+
+#[test]
+fn observer() {
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct CountingObserver {
+        phases: RefCell<Vec<GcPhase>>,
+        freed: Cell<usize>,
+    }
+
+    impl GcObserver for CountingObserver {
+        fn on_phase_change(&self, from: GcPhase, to: GcPhase) {
+            let mut phases = self.phases.borrow_mut();
+            if phases.is_empty() {
+                phases.push(from);
+            }
+            assert_eq!(phases.last(), Some(&from));
+            phases.push(to);
+        }
+
+        fn on_object_freed(&self, size: usize) {
+            self.freed.set(self.freed.get() + size);
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, i32>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::allocate(mc, 1))
+    });
+    arena.collect_all();
+    assert_eq!(arena.phase(), GcPhase::Sleep);
+
+    let observer = Rc::new(CountingObserver::default());
+    arena.set_observer(Some(observer.clone()));
+
+    arena.mutate(|mc, _| {
+        Gc::allocate(mc, 2);
+    });
+    let allocated = arena.total_allocated();
+    arena.collect_all();
+
+    assert_eq!(
+        *observer.phases.borrow(),
+        vec![
+            GcPhase::Sleep,
+            GcPhase::Wake,
+            GcPhase::Propagate,
+            GcPhase::Sweep,
+            GcPhase::Sleep
+        ]
+    );
+    assert_eq!(observer.freed.get(), allocated - arena.total_allocated());
+    assert!(observer.freed.get() > 0);
+
+    arena.set_observer(None);
+    arena.collect_all();
+    assert_eq!(observer.phases.borrow().len(), 5);
+}
+
+#[test]
+fn slice_access() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, [Gc<'gc, i32>]>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::allocate_slice(
+            mc,
+            (0..8).map(|i| Gc::allocate(mc, i * 10)).collect(),
+        ))
+    });
+
+    arena.collect_all();
+
+    arena.mutate(|mc, root| {
+        assert_eq!(root.0.len(), 8);
+        assert!(!root.0.is_empty());
+        assert_eq!(*root.0[3], 30);
+        assert_eq!(
+            Gc::as_slice(root.0).iter().map(|i| **i).collect::<Vec<_>>(),
+            vec![0, 10, 20, 30, 40, 50, 60, 70]
+        );
+
+        let empty = Gc::<[i32]>::allocate_slice(mc, Vec::new());
+        assert!(empty.is_empty());
+    });
+}
+
+#[test]
+fn slice_collection() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Option<Gc<'gc, [Gc<'gc, RefCounter>]>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let rooted = RefCounter(Rc::new(()));
+    let unrooted = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, None))
+    });
+
+    arena.mutate(|mc, root| {
+        *root.0.write(mc) = Some(Gc::allocate_slice(
+            mc,
+            (0..16).map(|_| Gc::allocate(mc, rooted.clone())).collect(),
+        ));
+        Gc::allocate_slice(mc, vec![unrooted.clone(); 16]);
+    });
+    assert_eq!(Rc::strong_count(&rooted.0), 17);
+    assert_eq!(Rc::strong_count(&unrooted.0), 17);
+
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&rooted.0), 17);
+    assert_eq!(Rc::strong_count(&unrooted.0), 1);
+
+    arena.mutate(|mc, root| {
+        *root.0.write(mc) = None;
+    });
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&rooted.0), 1);
+}
+
+#[test]
+fn ephemerons() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct Value<'gc> {
+        key: Option<Gc<'gc, i32>>,
+        counter: RefCounter,
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        keys: GcCell<'gc, Vec<Gc<'gc, i32>>>,
+        ephemerons: GcCell<'gc, Vec<Ephemeron<'gc, i32, Gc<'gc, Value<'gc>>>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let counter = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        keys: GcCell::allocate(mc, Vec::new()),
+        ephemerons: GcCell::allocate(mc, Vec::new()),
+    });
+
+    arena.mutate(|mc, root| {
+        for i in 0..4 {
+            let key = Gc::allocate(mc, i);
+            // Odd values refer back to their own key, which must not keep the key alive.
+            let value = Gc::allocate(
+                mc,
+                Value {
+                    key: if i % 2 == 1 { Some(key) } else { None },
+                    counter: counter.clone(),
+                },
+            );
+            root.keys.write(mc).push(key);
+            root.ephemerons
+                .write(mc)
+                .push(Ephemeron::new(mc, key, value));
+        }
+    });
+
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&counter.0), 5);
+    arena.mutate(|_, root| {
+        for (i, ephemeron) in root.ephemerons.read().iter().enumerate() {
+            let (key, value) = ephemeron.get().unwrap();
+            assert_eq!(*key, i as i32);
+            assert_eq!(value.key.is_some(), i % 2 == 1);
+        }
+    });
+
+    // Only keep the keys 0 and 1 alive.
+    arena.mutate(|mc, root| {
+        root.keys.write(mc).truncate(2);
+    });
+    arena.collect_all();
+    arena.collect_all();
+
+    assert_eq!(Rc::strong_count(&counter.0), 3);
+    arena.mutate(|_, root| {
+        let ephemerons = root.ephemerons.read();
+        assert_eq!(*ephemerons[0].key().unwrap(), 0);
+        assert_eq!(*ephemerons[1].key().unwrap(), 1);
+        assert!(ephemerons[2].is_dead());
+        assert!(ephemerons[3].get().is_none());
+    });
+
+    // A key that is only reachable through the value of another live ephemeron stays alive.
+    arena.mutate(|mc, root| {
+        let inner_key = Gc::allocate(mc, 10);
+        let inner_value = Gc::allocate(
+            mc,
+            Value {
+                key: None,
+                counter: counter.clone(),
+            },
+        );
+        let outer_value = Gc::allocate(
+            mc,
+            Value {
+                key: Some(inner_key),
+                counter: counter.clone(),
+            },
+        );
+        let outer_key = root.keys.read()[0];
+        let mut ephemerons = root.ephemerons.write(mc);
+        ephemerons.push(Ephemeron::new(mc, inner_key, inner_value));
+        ephemerons.push(Ephemeron::new(mc, outer_key, outer_value));
+    });
+    arena.collect_all();
+    arena.collect_all();
+
+    assert_eq!(Rc::strong_count(&counter.0), 5);
+    arena.mutate(|_, root| {
+        assert_eq!(*root.ephemerons.read()[4].key().unwrap(), 10);
+    });
+
+    arena.mutate(|mc, root| {
+        root.keys.write(mc).clear();
+    });
+    arena.collect_all();
+    arena.collect_all();
+
+    assert_eq!(Rc::strong_count(&counter.0), 1);
+    arena.mutate(|_, root| {
+        assert!(root.ephemerons.read().iter().all(|e| e.is_dead()));
+    });
+}
+
+#[test]
+fn erased_ephemeron_keys() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        cell: GcCell<'gc, Option<GcCell<'gc, i32>>>,
+        ephemerons: GcCell<'gc, Vec<Ephemeron<'gc, dyn Collect + 'gc, i32>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let kept = GcCell::allocate(mc, 1);
+        let ephemerons = vec![
+            Ephemeron::new(mc, GcCell::erase(kept), 1),
+            Ephemeron::new(mc, Gc::erase(Gc::allocate(mc, 2u8)), 2),
+        ];
+        TestRoot {
+            cell: GcCell::allocate(mc, Some(kept)),
+            ephemerons: GcCell::allocate(mc, ephemerons),
+        }
+    });
+
+    arena.mutate(|_, root| {
+        let kept = root.cell.read().unwrap();
+        let key = root.ephemerons.read()[0].key().unwrap();
+        assert_eq!(Gc::object_id(key), GcCell::object_id(kept));
+    });
+
+    arena.collect_all();
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        let ephemerons = root.ephemerons.read();
+        assert_eq!(ephemerons[0].value(), Some(&1));
+        assert!(ephemerons[1].is_dead());
+    });
+
+    arena.mutate(|mc, root| {
+        *root.cell.write(mc) = None;
+    });
+    arena.collect_all();
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        assert!(root.ephemerons.read()[0].is_dead());
+    });
+}
+
+#[test]
+fn ptr_eq() {
+    gc_arena::rootless_arena(|mc| {
+        let a = Gc::allocate(mc, 1);
+        let b = Gc::allocate(mc, 1);
+        let c = a;
+        assert!(Gc::ptr_eq(a, c));
+        assert!(!Gc::ptr_eq(a, b));
+
+        let a = GcCell::allocate(mc, 1);
+        let b = GcCell::allocate(mc, 1);
+        let c = a;
+        assert!(GcCell::ptr_eq(a, c));
+        assert!(!GcCell::ptr_eq(a, b));
+    });
+}
+
+#[test]
+fn gc_cell_write_barrier() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        filler: Gc<'gc, Vec<Gc<'gc, Gc<'gc, i32>>>>,
+        cell: GcCell<'gc, Vec<Gc<'gc, (usize, RefCounter)>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let r = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default().set_min_sleep(0), |mc| TestRoot {
+        filler: Gc::allocate(
+            mc,
+            (0..2000)
+                .map(|i| Gc::allocate(mc, Gc::allocate(mc, i)))
+                .collect(),
+        ),
+        cell: GcCell::allocate(mc, Vec::new()),
+    });
+    arena.collect_all();
+
+    // Store new children in the cell while the collector is partway through propagation, by which
+    // point the cell itself has often already been turned black.
+    let mut propagating_mutations = 0;
+    let mut i = 0;
+    while propagating_mutations < 100 {
+        if arena.phase() == GcPhase::Propagate {
+            propagating_mutations += 1;
+        }
+
+        arena.mutate(|mc, root| {
+            root.cell.write(mc).push(Gc::allocate(mc, (i, r.clone())));
+            Gc::allocate(mc, 0);
+        });
+        i += 1;
+        arena.collect_debt();
+
+        assert_eq!(
+            Rc::strong_count(&r.0),
+            arena.mutate(|_, root| root.cell.read().len()) + 1
+        );
+    }
+
+    arena.collect_all();
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        let cell = root.cell.read();
+        assert_eq!(cell.len(), i);
+        for (j, c) in cell.iter().enumerate() {
+            assert_eq!(c.0, j);
+        }
+    });
+    assert_eq!(Rc::strong_count(&r.0), i + 1);
+}
+
+#[test]
+fn collect_and_verify_stable() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct Node<'gc> {
+        value: i32,
+        edges: Vec<GcCell<'gc, Node<'gc>>>,
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        nodes: Vec<GcCell<'gc, Node<'gc>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let nodes: Vec<_> = (0..64)
+            .map(|i| {
+                GcCell::allocate(
+                    mc,
+                    Node {
+                        value: i,
+                        edges: Vec::new(),
+                    },
+                )
+            })
+            .collect();
+        // Link the nodes into overlapping cycles, and hang some unrooted garbage off the side.
+        for (i, node) in nodes.iter().enumerate() {
+            let mut node = node.write(mc);
+            node.edges.push(nodes[(i + 1) % nodes.len()]);
+            node.edges.push(nodes[(i * 7) % nodes.len()]);
+        }
+        for i in 0..32 {
+            GcCell::allocate(
+                mc,
+                Node {
+                    value: -i,
+                    edges: vec![nodes[i as usize]],
+                },
+            );
+        }
+        TestRoot {
+            nodes: vec![nodes[0], nodes[13]],
+        }
+    });
+
+    arena.collect_and_verify_stable();
+    arena.mutate(|_, root| {
+        let mut node = root.nodes[1];
+        for _ in 0..64 {
+            let next = node.read().edges[0];
+            node = next;
+        }
+        assert_eq!(node.read().value, 13);
+    });
+    arena.collect_and_verify_stable();
+}
+
+#[test]
+#[should_panic(expected = "live set changed")]
+fn collect_and_verify_stable_catches_unstable_trace() {
+    use std::cell::Cell;
+
+    // Deliberately broken: stops tracing its child on the third collection, so the child is freed
+    // while still referenced.  The child is never accessed again, so this only upsets the live set.
+    struct Flaky<'gc> {
+        traces: Cell<u32>,
+        child: Gc<'gc, i32>,
+    }
+
+    unsafe impl<'gc> Collect for Flaky<'gc> {
+        fn trace(&self, cc: gc_arena::CollectionContext) {
+            let traces = self.traces.get();
+            self.traces.set(traces + 1);
+            if traces != 2 {
+                self.child.trace(cc);
+            }
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        flaky: Gc<'gc, Flaky<'gc>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        flaky: Gc::allocate(
+            mc,
+            Flaky {
+                traces: Cell::new(0),
+                child: Gc::allocate(mc, 4),
+            },
+        ),
+    });
+    arena.mutate(|_, root| root.flaky.traces.set(0));
+    arena.collect_and_verify_stable();
+}
+
+#[test]
+fn downcast() {
+    #[derive(Collect, Debug, PartialEq)]
+    #[collect(require_static)]
+    struct Foo(i32);
+    unsafe impl AnyGcObject for Foo {}
+
+    #[derive(Collect)]
+    #[collect(require_static)]
+    struct Bar;
+    unsafe impl AnyGcObject for Bar {}
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, dyn Collect + 'gc>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::erase(Gc::allocate(mc, Foo(7))))
+    });
+
+    arena.collect_all();
+    arena.collect_all();
+
+    arena.mutate(|mc, root| {
+        let foo = Gc::downcast::<Foo>(root.0).unwrap();
+        assert_eq!(*foo, Foo(7));
+        assert!(Gc::downcast::<Bar>(root.0).is_none());
+
+        let erased = Gc::erase(Gc::allocate(mc, Bar));
+        assert!(Gc::downcast::<Bar>(erased).is_some());
+        assert!(Gc::downcast::<Foo>(erased).is_none());
+    });
+}
+
+#[test]
+fn collection_outcome() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Vec<Gc<'gc, i64>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, Vec::new()))
+    });
+    arena.collect_all();
+
+    arena.mutate(|mc, root| {
+        root.0.write(mc).push(Gc::allocate(mc, -1));
+    });
+    let live = arena.total_allocated();
+    arena.mutate(|mc, _| {
+        for i in 0..50 {
+            Gc::allocate(mc, i);
+        }
+    });
+    let garbage = arena.total_allocated() - live;
+
+    let outcome = arena.collect_all_outcome();
+    assert_eq!(
+        outcome,
+        CollectionOutcome {
+            bytes_freed: garbage,
+            objects_freed: 50,
+            started_cycle: true,
+        }
+    );
+    assert_eq!(arena.collect_all_outcome().bytes_freed, 0);
+    assert_eq!(arena.total_allocated(), live);
+}
+
+#[test]
+fn colors() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, Vec<Gc<'gc, Vec<Gc<'gc, i32>>>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default().set_min_sleep(0), |mc| {
+        TestRoot(Gc::allocate(
+            mc,
+            (0..64)
+                .map(|i| Gc::allocate(mc, (0..8).map(|j| Gc::allocate(mc, i * j)).collect()))
+                .collect(),
+        ))
+    });
+    arena.collect_all();
+    assert_eq!(arena.phase(), GcPhase::Sleep);
+
+    let mut seen_gray = false;
+    let mut seen_propagate_black = false;
+    let mut seen_sweep = false;
+    while !seen_sweep || arena.phase() != GcPhase::Sleep {
+        let phase = arena.phase();
+        arena.mutate(|mc, root| {
+            for &child in root.0.iter() {
+                let color = Gc::color_of(child);
+                match phase {
+                    GcPhase::Sleep | GcPhase::Wake => assert_eq!(color, GcColor::White),
+                    GcPhase::Propagate => {
+                        seen_gray |= color == GcColor::Gray;
+                        if color == GcColor::Black {
+                            seen_propagate_black = true;
+                            // A black object may never point to a white one.
+                            for &grandchild in child.iter() {
+                                assert_ne!(Gc::color_of(grandchild), GcColor::White);
+                            }
+                        }
+                    }
+                    GcPhase::Sweep => {}
+                }
+            }
+
+            let fresh = Gc::allocate(mc, 0);
+            assert_eq!(Gc::color_of(fresh), GcColor::White);
+        });
+        seen_sweep |= arena.phase() == GcPhase::Sweep;
+        arena.collect_debt();
+    }
+
+    assert!(seen_gray && seen_propagate_black && seen_sweep);
+    arena.mutate(|_, root| {
+        assert!(root.0.iter().all(|&c| Gc::color_of(c) == GcColor::White));
+    });
+}
+
+#[test]
+fn step_work() {
+    #[derive(Clone)]
+    struct RefCounter(Rc<()>);
+    unsafe_empty_collect!(RefCounter);
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Vec<Gc<'gc, RefCounter>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let live = RefCounter(Rc::new(()));
+    let garbage = RefCounter(Rc::new(()));
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, Vec::new()))
+    });
+    arena.mutate(|mc, root| {
+        let mut v = root.0.write(mc);
+        for _ in 0..1000 {
+            v.push(Gc::allocate(mc, live.clone()));
+            Gc::allocate(mc, garbage.clone());
+        }
+    });
+
+    let mut steps = 1;
+    while !arena.step(256) {
+        steps += 1;
+        assert_ne!(arena.phase(), GcPhase::Sleep);
+    }
+    assert!(steps > 10);
+    assert_eq!(arena.phase(), GcPhase::Sleep);
+    assert_eq!(Rc::strong_count(&live.0), 1001);
+    assert_eq!(Rc::strong_count(&garbage.0), 1);
+
+    // Stepping while asleep starts a new cycle.
+    assert!(!arena.step(1));
+    assert_ne!(arena.phase(), GcPhase::Sleep);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "the gc heap was mutated during collection")]
+fn allocate_during_trace() {
+    use gc_arena::{CollectionContext, MutationContext};
+    use std::cell::Cell;
+
+    thread_local! {
+        static SMUGGLED: Cell<Option<MutationContext<'static, 'static>>> = const { Cell::new(None) };
+    }
+
+    struct Malicious;
+
+    unsafe impl Collect for Malicious {
+        fn trace(&self, _cc: CollectionContext) {
+            if let Some(mc) = SMUGGLED.with(|s| s.get()) {
+                Gc::allocate(mc, 0u8);
+            }
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, Malicious>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::allocate(mc, Malicious))
+    });
+    arena.mutate(|mc, _| {
+        let mc: MutationContext<'static, 'static> = unsafe { std::mem::transmute(mc) };
+        SMUGGLED.with(|s| s.set(Some(mc)));
+    });
+    arena.collect_all();
+}
+
+#[test]
+fn sleep_is_stable_across_cycles() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        live: Vec<Gc<'gc, i64>>,
+    }
+
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default().set_min_sleep(0), |mc| TestRoot {
+        live: (0..100).map(|i| Gc::allocate(mc, i)).collect(),
+    });
+
+    // With a live set that never changes, every cycle should sleep for the same number of bytes of
+    // new garbage before waking.
+    let mut sleeps = Vec::new();
+    for _ in 0..4 {
+        arena.collect_all();
+        assert_eq!(arena.phase(), GcPhase::Sleep);
+        let start = arena.total_allocated();
+        while arena.phase() == GcPhase::Sleep {
+            arena.mutate(|mc, _| {
+                Gc::allocate(mc, 0i64);
+            });
+        }
+        sleeps.push(arena.total_allocated() - start);
+    }
+    assert!(
+        sleeps.iter().all(|&sleep| sleep == sleeps[0]),
+        "sleep grew between cycles: {:?}",
+        sleeps
+    );
+}
+
+#[cfg(feature = "gc-debug")]
+#[test]
+fn phase_history() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, i32>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::allocate(mc, 1))
+    });
+    for _ in 0..3 {
+        arena.mutate(|mc, _| {
+            for i in 0..100 {
+                Gc::allocate(mc, i);
+            }
+        });
+        arena.collect_all();
+    }
+
+    let history = arena.phase_history();
+    let phases: Vec<GcPhase> = history.iter().map(|&(p, _)| p).collect();
+    assert_eq!(
+        &phases[..3],
+        &[GcPhase::Propagate, GcPhase::Sweep, GcPhase::Sleep]
+    );
+    for cycle in phases[3..].chunks(4) {
+        assert_eq!(
+            cycle,
+            &[
+                GcPhase::Wake,
+                GcPhase::Propagate,
+                GcPhase::Sweep,
+                GcPhase::Sleep
+            ]
+        );
+    }
+    assert_eq!(phases.len(), 11);
+
+    // Garbage is freed between entering the sweep phase and going back to sleep.
+    assert!(history[2].1 < history[1].1);
+    for cycle in history[3..].chunks(4) {
+        assert!(cycle[3].1 < cycle[2].1);
+    }
+}
+
+#[test]
+fn custom_allocator() {
+    use std::alloc::{Layout, System};
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct CountingAllocator {
+        allocated: Cell<usize>,
+        freed: Cell<usize>,
+    }
+
+    unsafe impl GcAllocator for CountingAllocator {
+        unsafe fn alloc_box(&self, layout: Layout) -> *mut u8 {
+            self.allocated.set(self.allocated.get() + 1);
+            std::alloc::GlobalAlloc::alloc(&System, layout)
+        }
+
+        unsafe fn dealloc_box(&self, ptr: *mut u8, layout: Layout) {
+            self.freed.set(self.freed.get() + 1);
+            std::alloc::GlobalAlloc::dealloc(&System, ptr, layout)
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Vec<Gc<'gc, [u32]>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let allocator = Rc::new(CountingAllocator::default());
+    let mut arena = TestArena::new(
+        ArenaParameters::default().set_allocator(allocator.clone()),
+        |mc| TestRoot(GcCell::allocate(mc, Vec::new())),
+    );
+    arena.mutate(|mc, root| {
+        for i in 0..10 {
+            root.0.write(mc).push(Gc::allocate_slice(mc, vec![i; 4]));
+            Gc::allocate(mc, i);
+        }
+    });
+    assert_eq!(allocator.allocated.get(), 21);
+
+    arena.collect_all();
+    assert_eq!(allocator.freed.get(), 10);
+    arena.mutate(|_, root| {
+        for (i, slice) in root.0.read().iter().enumerate() {
+            assert_eq!(&slice[..], &[i as u32; 4]);
+        }
+    });
+
+    drop(arena);
+    assert_eq!(allocator.freed.get(), 21);
+}
+
+#[test]
+fn traced_box() {
+    // Stands in for a type from another crate, which cannot implement `Collect` here.
+    struct Foreign<'gc> {
+        held: Gc<'gc, i32>,
+    }
+
+    fn trace_foreign(foreign: &Foreign, cc: gc_arena::CollectionContext) {
+        foreign.held.trace(cc);
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, TracedBox<Foreign<'gc>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        let foreign = Foreign {
+            held: Gc::allocate(mc, 42),
+        };
+        TestRoot(Gc::allocate(mc, unsafe {
+            TracedBox::new(foreign, trace_foreign)
+        }))
+    });
+
+    let live = arena.total_allocated();
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(arena.total_allocated(), live);
+
+    arena.mutate(|_, root| {
+        assert_eq!(*root.0.held, 42);
+    });
+}
+
+#[test]
+fn linked_allocations() {
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct Node<'gc> {
+        next: Option<Gc<'gc, Node<'gc>>>,
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(GcCell<'gc, Option<Gc<'gc, Node<'gc>>>>);
+    make_arena!(TestArena, TestRoot);
+
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(GcCell::allocate(mc, None))
+    });
+    arena.collect_all();
+    let empty = arena.total_allocated();
+
+    arena.mutate(|mc, root| {
+        let tail = Gc::allocate(mc, Node { next: None });
+        let head = Gc::allocate(mc, Node { next: Some(tail) });
+        *root.0.write(mc) = Some(head);
+    });
+    let linked = arena.total_allocated();
+    assert!(linked > empty);
+
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(arena.total_allocated(), linked);
+    arena.mutate(|_, root| {
+        let head = root.0.read().unwrap();
+        assert!(head.next.unwrap().next.is_none());
+    });
+
+    arena.mutate(|mc, root| {
+        *root.0.write(mc) = None;
+    });
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(arena.total_allocated(), empty);
+}
+
+#[cfg(all(feature = "gc-debug", debug_assertions))]
+#[test]
+#[should_panic(expected = "a `Gc` pointer from another arena")]
+fn foreign_write_barrier() {
+    use gc_arena::MutationContext;
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc>(Gc<'gc, i32>);
+    make_arena!(TestArena, TestRoot);
+
+    // Gives a pointer the branding of another arena, which is only possible with unsafe code.
+    unsafe fn rebrand<'a, 'b>(_: MutationContext<'a, '_>, ptr: Gc<'b, i32>) -> Gc<'a, i32> {
+        std::mem::transmute(ptr)
+    }
+
+    let mut a = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::allocate(mc, 1))
+    });
+    let mut b = TestArena::new(ArenaParameters::default(), |mc| {
+        TestRoot(Gc::allocate(mc, 2))
+    });
+    a.mutate(|mc_a, _| {
+        b.mutate(|_, root_b| {
+            let foreign = unsafe { rebrand(mc_a, root_b.0) };
+            Gc::write_barrier(mc_a, foreign);
+        })
+    });
+}
+
+#[test]
+fn panic_during_collection() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    thread_local! {
+        static PANIC_IN_TRACE: Cell<bool> = const { Cell::new(false) };
+        static PANIC_IN_DROP: Cell<bool> = const { Cell::new(false) };
+    }
+
+    struct Panicky {
+        _counter: Rc<()>,
+    }
+
+    unsafe impl Collect for Panicky {
+        fn needs_trace() -> bool {
+            true
+        }
+
+        fn trace(&self, _cc: gc_arena::CollectionContext) {
+            if PANIC_IN_TRACE.with(|p| p.get()) {
+                panic!("panic in trace");
+            }
+        }
+    }
+
+    impl Drop for Panicky {
+        fn drop(&mut self) {
+            if PANIC_IN_DROP.with(|p| p.replace(false)) {
+                panic!("panic in drop");
+            }
+        }
+    }
+
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct TestRoot<'gc> {
+        live: GcCell<'gc, Vec<Gc<'gc, Panicky>>>,
+    }
+    make_arena!(TestArena, TestRoot);
+
+    let counter = Rc::new(());
+    let mut arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        live: GcCell::allocate(mc, Vec::new()),
+    });
+    arena.mutate(|mc, root| {
+        for i in 0..8 {
+            let p = Gc::allocate(
+                mc,
+                Panicky {
+                    _counter: counter.clone(),
+                },
+            );
+            if i % 2 == 0 {
+                root.live.write(mc).push(p);
+            }
+        }
+    });
+
+    PANIC_IN_TRACE.with(|p| p.set(true));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| arena.collect_all()));
+    assert!(res.is_err());
+    PANIC_IN_TRACE.with(|p| p.set(false));
+
+    // The interrupted cycle is abandoned without freeing anything, and the next one collects only
+    // the garbage.
+    assert_eq!(arena.phase(), GcPhase::Sleep);
+    assert_eq!(Rc::strong_count(&counter), 9);
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&counter), 5);
+    arena.mutate(|mc, root| {
+        assert_eq!(root.live.read().len(), 4);
+        root.live.write(mc).truncate(2);
+    });
+
+    // A panic while dropping garbage during the sweep also leaves the arena usable.
+    PANIC_IN_DROP.with(|p| p.set(true));
+    let res = panic::catch_unwind(AssertUnwindSafe(|| arena.collect_all()));
+    assert!(res.is_err());
+    arena.collect_all();
+    arena.collect_all();
+    assert_eq!(Rc::strong_count(&counter), 3);
+
+    drop(arena);
+    assert_eq!(Rc::strong_count(&counter), 1);
 }
@@ -0,0 +1,39 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub enum AndNext<S, N> {
+    First(S, Option<N>),
+    Second(N),
+}
+
+impl<S, N> AndNext<S, N> {
+    pub fn new(s: S, next: N) -> AndNext<S, N> {
+        AndNext::First(s, Some(next))
+    }
+}
+
+impl<'gc, S, N, I, E, I2> Sequence<'gc> for AndNext<S, N>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    N: Sequence<'gc, Output = Result<I2, E>>,
+{
+    type Output = Result<I2, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        match self {
+            AndNext::First(s, next) => match s.step(mc) {
+                Some(Ok(_)) => {
+                    *self = AndNext::Second(next.take().expect("cannot step a finished sequence"));
+                    None
+                }
+                Some(Err(err)) => Some(Err(err)),
+                None => None,
+            },
+            AndNext::Second(next) => next.step(mc),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct Batch<S> {
+    s: S,
+    steps: usize,
+}
+
+impl<S> Batch<S> {
+    pub fn new(s: S, steps: usize) -> Batch<S> {
+        assert!(steps != 0, "cannot batch zero steps");
+        Batch { s, steps }
+    }
+}
+
+impl<'gc, S> Sequence<'gc> for Batch<S>
+where
+    S: Sequence<'gc>,
+{
+    type Output = S::Output;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<S::Output> {
+        for _ in 0..self.steps {
+            if let Some(res) = self.s.step(mc) {
+                return Some(res);
+            }
+        }
+        None
+    }
+
+    fn partial(&self) -> Option<S::Output> {
+        self.s.partial()
+    }
+}
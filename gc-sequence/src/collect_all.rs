@@ -0,0 +1,69 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+/// Creates a sequence which steps every sequence in `sequences` together, resulting in all of their
+/// results in the same order once they have all completed successfully.
+///
+/// If any of the sequences errors, the returned sequence completes with that error immediately and
+/// the rest are not stepped further.
+pub fn collect_all<'gc, S, I, E>(sequences: Vec<S>) -> CollectAll<S, I>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    I: Collect,
+{
+    CollectAll::new(sequences)
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct CollectAll<S, I> {
+    sequences: Vec<Option<S>>,
+    results: Vec<Option<I>>,
+}
+
+impl<S, I> CollectAll<S, I> {
+    pub fn new(sequences: Vec<S>) -> CollectAll<S, I> {
+        let mut results = Vec::new();
+        results.resize_with(sequences.len(), || None);
+        CollectAll {
+            sequences: sequences.into_iter().map(Some).collect(),
+            results,
+        }
+    }
+}
+
+impl<'gc, S, I, E> Sequence<'gc> for CollectAll<S, I>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    I: Collect,
+{
+    type Output = Result<Vec<I>, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        let mut finished = true;
+        for (sequence, result) in self.sequences.iter_mut().zip(&mut self.results) {
+            if let Some(s) = sequence {
+                match s.step(mc) {
+                    Some(Ok(res)) => {
+                        *sequence = None;
+                        *result = Some(res);
+                    }
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => finished = false,
+                }
+            }
+        }
+
+        if finished {
+            Some(Ok(self
+                .results
+                .drain(..)
+                .map(|res| res.expect("cannot step a finished sequence"))
+                .collect()))
+        } else {
+            None
+        }
+    }
+}
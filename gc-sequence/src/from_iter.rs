@@ -0,0 +1,60 @@
+use gc_arena::{Collect, MutationContext, StaticCollect};
+
+use crate::Sequence;
+
+/// Creates a sequence which takes one element from `iter` per step, and results in all of the
+/// elements once the iterator is exhausted.
+///
+/// The iterator is always looked ahead by one element, so an iterator of `n` elements completes on
+/// step `n` rather than needing an extra step to find its end.  An empty iterator completes on the
+/// first step.
+pub fn from_iter<I>(iter: I) -> FromIter<I, I::Item>
+where
+    I: 'static + Iterator,
+{
+    FromIter::new(iter)
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct FromIter<I, T> {
+    iter: StaticCollect<I>,
+    next: StaticCollect<Option<T>>,
+    items: StaticCollect<Vec<T>>,
+}
+
+impl<I, T> FromIter<I, T>
+where
+    I: Iterator<Item = T>,
+{
+    pub fn new(mut iter: I) -> FromIter<I, T> {
+        let next = iter.next();
+        FromIter {
+            iter: StaticCollect(iter),
+            next: StaticCollect(next),
+            items: StaticCollect(Vec::new()),
+        }
+    }
+}
+
+impl<'gc, I, T> Sequence<'gc> for FromIter<I, T>
+where
+    I: 'static + Iterator<Item = T>,
+    T: 'static,
+{
+    type Output = Vec<T>;
+
+    fn step(&mut self, _: MutationContext<'gc, '_>) -> Option<Vec<T>> {
+        if let Some(item) = self.next.0.take() {
+            self.items.0.push(item);
+            self.next.0 = self.iter.0.next();
+        }
+
+        if self.next.0.is_none() {
+            Some(std::mem::take(&mut self.items.0))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct Fuse<S>(Option<S>);
+
+impl<S> Fuse<S> {
+    pub fn new(s: S) -> Fuse<S> {
+        Fuse(Some(s))
+    }
+
+    /// Whether the inner sequence has completed.
+    pub fn is_finished(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+impl<'gc, S> Sequence<'gc> for Fuse<S>
+where
+    S: Sequence<'gc>,
+{
+    type Output = S::Output;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<S::Output> {
+        let res = self.0.as_mut()?.step(mc);
+        if res.is_some() {
+            self.0 = None;
+        }
+        res
+    }
+
+    fn partial(&self) -> Option<S::Output> {
+        self.0.as_ref()?.partial()
+    }
+}
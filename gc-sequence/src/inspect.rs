@@ -0,0 +1,59 @@
+use gc_arena::{Collect, MutationContext, StaticCollect};
+
+use crate::Sequence;
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct Inspect<S, F>(S, Option<StaticCollect<F>>);
+
+impl<S, F> Inspect<S, F> {
+    pub fn new(s: S, f: F) -> Inspect<S, F> {
+        Inspect(s, Some(StaticCollect(f)))
+    }
+}
+
+impl<'gc, S, F, I, E> Sequence<'gc> for Inspect<S, F>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    F: 'static + FnOnce(&I),
+{
+    type Output = Result<I, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        let res = self.0.step(mc)?;
+        let f = self.1.take().expect("cannot step a finished sequence");
+        if let Ok(res) = &res {
+            f.0(res);
+        }
+        Some(res)
+    }
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct InspectErr<S, F>(S, Option<StaticCollect<F>>);
+
+impl<S, F> InspectErr<S, F> {
+    pub fn new(s: S, f: F) -> InspectErr<S, F> {
+        InspectErr(s, Some(StaticCollect(f)))
+    }
+}
+
+impl<'gc, S, F, I, E> Sequence<'gc> for InspectErr<S, F>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    F: 'static + FnOnce(&E),
+{
+    type Output = Result<I, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        let res = self.0.step(mc)?;
+        let f = self.1.take().expect("cannot step a finished sequence");
+        if let Err(err) = &res {
+            f.0(err);
+        }
+        Some(res)
+    }
+}
@@ -0,0 +1,66 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct Join<A, B, IA, IB> {
+    a: Option<A>,
+    b: Option<B>,
+    a_res: Option<IA>,
+    b_res: Option<IB>,
+}
+
+impl<A, B, IA, IB> Join<A, B, IA, IB> {
+    pub fn new(a: A, b: B) -> Join<A, B, IA, IB> {
+        Join {
+            a: Some(a),
+            b: Some(b),
+            a_res: None,
+            b_res: None,
+        }
+    }
+}
+
+impl<'gc, A, B, IA, IB, E> Sequence<'gc> for Join<A, B, IA, IB>
+where
+    A: Sequence<'gc, Output = Result<IA, E>>,
+    B: Sequence<'gc, Output = Result<IB, E>>,
+    IA: Collect,
+    IB: Collect,
+{
+    type Output = Result<(IA, IB), E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        if let Some(a) = &mut self.a {
+            match a.step(mc) {
+                Some(Ok(res)) => {
+                    self.a = None;
+                    self.a_res = Some(res);
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => {}
+            }
+        }
+
+        if let Some(b) = &mut self.b {
+            match b.step(mc) {
+                Some(Ok(res)) => {
+                    self.b = None;
+                    self.b_res = Some(res);
+                }
+                Some(Err(err)) => return Some(Err(err)),
+                None => {}
+            }
+        }
+
+        if self.a.is_none() && self.b.is_none() {
+            let a = self.a_res.take().expect("cannot step a finished sequence");
+            let b = self.b_res.take().expect("cannot step a finished sequence");
+            Some(Ok((a, b)))
+        } else {
+            None
+        }
+    }
+}
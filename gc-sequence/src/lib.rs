@@ -1,18 +1,37 @@
+pub mod and_next;
 pub mod and_then;
+pub mod batch;
+pub mod collect_all;
 pub mod done;
 pub mod flatten;
 pub mod flatten_result;
+pub mod from_iter;
+pub mod fuse;
+pub mod inspect;
+pub mod join;
+pub mod loop_until;
 pub mod map;
 pub mod map_result;
+pub mod on_complete;
+pub mod select;
 mod sequencable_arena;
 mod sequence;
 mod sequence_ext;
 mod sequence_fn;
 mod sequence_result_ext;
+pub mod step_limit;
+pub mod take_for;
 pub mod then;
 
+pub use self::collect_all::collect_all;
 pub use self::done::{done, err, ok};
-pub use self::sequence::Sequence;
+pub use self::from_iter::from_iter;
+pub use self::loop_until::loop_until;
+pub use self::select::Either;
+pub use self::sequence::{run_sequence, BoxSequence, Sequence};
 pub use self::sequence_ext::SequenceExt;
-pub use self::sequence_fn::{from_fn, from_fn_with, SequenceFn, SequenceFnWith};
+pub use self::sequence_fn::{
+    from_fn, from_fn_with, from_step_fn, SequenceFn, SequenceFnWith, StepFn,
+};
 pub use self::sequence_result_ext::SequenceResultExt;
+pub use self::step_limit::StepLimitError;
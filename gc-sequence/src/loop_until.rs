@@ -0,0 +1,62 @@
+use gc_arena::{Collect, MutationContext, StaticCollect};
+
+use crate::Sequence;
+
+/// Creates a sequence which repeatedly runs a fresh sequence from `make`, until `done` returns true
+/// for its result, and then results in that result.
+///
+/// If `done` never returns true, the returned sequence never completes.
+pub fn loop_until<'gc, F, G, S>(make: F, done: G) -> LoopUntil<F, G, S>
+where
+    F: 'static + FnMut(MutationContext<'gc, '_>) -> S,
+    G: 'static + FnMut(&S::Output) -> bool,
+    S: Sequence<'gc>,
+{
+    LoopUntil::new(make, done)
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct LoopUntil<F, G, S> {
+    make: StaticCollect<F>,
+    done: StaticCollect<G>,
+    current: Option<S>,
+}
+
+impl<F, G, S> LoopUntil<F, G, S> {
+    pub fn new(make: F, done: G) -> LoopUntil<F, G, S> {
+        LoopUntil {
+            make: StaticCollect(make),
+            done: StaticCollect(done),
+            current: None,
+        }
+    }
+}
+
+impl<'gc, F, G, S> Sequence<'gc> for LoopUntil<F, G, S>
+where
+    F: 'static + FnMut(MutationContext<'gc, '_>) -> S,
+    G: 'static + FnMut(&S::Output) -> bool,
+    S: Sequence<'gc>,
+{
+    type Output = S::Output;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<S::Output> {
+        let make = &mut self.make.0;
+        let current = self.current.get_or_insert_with(|| make(mc));
+        match current.step(mc) {
+            Some(res) => {
+                if (self.done.0)(&res) {
+                    Some(res)
+                } else {
+                    // Each new iteration starts on the next step, so that a loop where every
+                    // iteration completes in a single step still gives collection a chance to run.
+                    self.current = None;
+                    None
+                }
+            }
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use gc_arena::{Collect, MutationContext, StaticCollect};
+
+use crate::Sequence;
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct OnComplete<S, F>(S, Option<StaticCollect<F>>);
+
+impl<S, F> OnComplete<S, F> {
+    pub fn new(s: S, f: F) -> OnComplete<S, F> {
+        OnComplete(s, Some(StaticCollect(f)))
+    }
+}
+
+impl<'gc, S, F, I, E> Sequence<'gc> for OnComplete<S, F>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    F: 'static + FnOnce(&Result<I, E>),
+{
+    type Output = Result<I, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        match self.0.step(mc) {
+            Some(res) => {
+                self.1.take().expect("cannot step a finished sequence").0(&res);
+                Some(res)
+            }
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+/// The result of a `Select`, which is the result of whichever sequence completed first.
+#[derive(Debug, Clone, PartialEq, Eq, Collect)]
+#[collect(empty_drop)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct Select<A, B>(A, B);
+
+impl<A, B> Select<A, B> {
+    pub fn new(a: A, b: B) -> Select<A, B> {
+        Select(a, b)
+    }
+}
+
+impl<'gc, A, B, IA, IB, E> Sequence<'gc> for Select<A, B>
+where
+    A: Sequence<'gc, Output = Result<IA, E>>,
+    B: Sequence<'gc, Output = Result<IB, E>>,
+{
+    type Output = Result<Either<IA, IB>, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        if let Some(res) = self.0.step(mc) {
+            return Some(res.map(Either::Left));
+        }
+        self.1.step(mc).map(|res| res.map(Either::Right))
+    }
+}
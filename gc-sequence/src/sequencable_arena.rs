@@ -62,7 +62,7 @@ macro_rules! make_sequencable_arena {
             use std::any::Any;
             use std::marker::PhantomData;
 
-            use gc_arena::{make_arena, ArenaParameters, Collect, GcCell, MutationContext};
+            use gc_arena::{make_arena, ArenaParameters, Collect, GcCell, GcPhase, MutationContext};
             use gc_sequence::{Sequence, SequenceExt};
 
             use super::$root;
@@ -151,6 +151,13 @@ macro_rules! make_sequencable_arena {
                     self.0.allocation_debt()
                 }
 
+                /// Returns the phase the garbage collector is currently in.
+                #[allow(unused)]
+                #[inline]
+                $innervis fn phase(&self) -> GcPhase {
+                    self.0.phase()
+                }
+
                 /// Runs the incremental garbage collector until the allocation debt is <= 0.0.
                 /// There is no minimum unit of work enforced here, so it may be faster to only call
                 /// this method when the allocation debt is above some threshold.
@@ -215,6 +222,12 @@ macro_rules! make_sequencable_arena {
                     self.0.allocation_debt()
                 }
 
+                #[allow(unused)]
+                #[inline]
+                $innervis fn phase(&self) -> GcPhase {
+                    self.0.phase()
+                }
+
                 #[allow(unused)]
                 #[inline]
                 $innervis fn collect_debt(&mut self) {
@@ -9,12 +9,40 @@ pub trait Sequence<'gc>: Collect {
     type Output;
 
     fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output>;
+
+    /// The intermediate result of this sequence so far, if it has one, for a caller which is not
+    /// going to wait for it to complete.  The default implementation returns `None`.
+    fn partial(&self) -> Option<Self::Output> {
+        None
+    }
 }
 
+/// Steps the given sequence until it completes, all within the current mutation, and returns its
+/// result.
+///
+/// Since this never returns to the arena in between steps, no garbage collection can take place
+/// while the sequence runs.  This is fine for short sequences, but long running ones should be run
+/// with a sequencable arena instead, which collects in between steps.
+pub fn run_sequence<'gc, S: Sequence<'gc>>(mc: MutationContext<'gc, '_>, mut s: S) -> S::Output {
+    loop {
+        if let Some(res) = s.step(mc) {
+            return res;
+        }
+    }
+}
+
+/// A boxed sequence with its concrete type erased, so that different sequences with the same output
+/// can be used interchangeably.
+pub type BoxSequence<'gc, O> = Box<dyn Sequence<'gc, Output = O> + 'gc>;
+
 impl<'gc, T: ?Sized + Sequence<'gc>> Sequence<'gc> for Box<T> {
     type Output = T::Output;
 
     fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
         T::step(&mut (*self), mc)
     }
+
+    fn partial(&self) -> Option<Self::Output> {
+        T::partial(self)
+    }
 }
@@ -1,10 +1,13 @@
 use gc_arena::{Collect, MutationContext};
 
 use crate::{
+    batch::Batch,
     flatten::Flatten,
+    fuse::Fuse,
     map::{Map, MapWith},
+    take_for::TakeFor,
     then::{Then, ThenWith},
-    Sequence,
+    BoxSequence, Sequence,
 };
 
 /// Extension trait for `Sequence` that provides useful combinator methods.
@@ -100,12 +103,44 @@ pub trait SequenceExt<'gc>: Sized + Sequence<'gc> {
         Flatten::new(self)
     }
 
+    /// Step this sequence up to `steps` times for every time the returned sequence is stepped,
+    /// stopping early if it completes.
+    ///
+    /// Garbage collection cannot take place in between the batched steps, so this trades
+    /// collection granularity for doing more work per call to `Sequence::step`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `steps` is zero.
+    fn batch(self, steps: usize) -> Batch<Self> {
+        Batch::new(self, steps)
+    }
+
+    /// Step this sequence at most `steps` times, after which the returned sequence completes with
+    /// `Progress::Partial` holding the result of `Sequence::partial`, if this sequence has not
+    /// completed by then.
+    ///
+    /// Most combinators do not forward `Sequence::partial`, so this should be called on the
+    /// sequence which actually exposes its intermediate state.
+    fn take_for(self, steps: usize) -> TakeFor<Self> {
+        TakeFor::new(self, steps)
+    }
+
+    /// Make this sequence safe to step after it completes.
+    ///
+    /// Most sequences panic if they are stepped again after producing their result.  A fused
+    /// sequence instead drops the inner sequence once it completes, and returns `None` from every
+    /// later step.
+    fn fuse(self) -> Fuse<Self> {
+        Fuse::new(self)
+    }
+
     /// Turn this sequence into a boxed sequence type.
     ///
     /// The return type is a `dyn Sequence` because where you would need to produce a boxed sequence
     /// you generally are doing this to purposefully forget what the particular sequence type is,
     /// and doing this here eases type inference.
-    fn boxed(self) -> Box<dyn Sequence<'gc, Output = Self::Output> + 'gc>
+    fn boxed(self) -> BoxSequence<'gc, Self::Output>
     where
         Self: 'gc,
     {
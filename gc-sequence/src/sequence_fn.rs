@@ -64,3 +64,37 @@ where
         Some(f(mc, c))
     }
 }
+
+/// Creates a sequence which calls the given function every time it is stepped, completing when the
+/// function returns `Some`.
+///
+/// This is the sequence equivalent of `std::iter::from_fn`, for small state machines where writing
+/// out a full `Sequence` impl would be overkill.
+pub fn from_step_fn<'gc, F, R>(f: F) -> StepFn<F>
+where
+    F: 'static + FnMut(MutationContext<'gc, '_>) -> Option<R>,
+{
+    StepFn::new(f)
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct StepFn<F>(StaticCollect<F>);
+
+impl<F> StepFn<F> {
+    pub fn new(f: F) -> StepFn<F> {
+        StepFn(StaticCollect(f))
+    }
+}
+
+impl<'gc, F, R> Sequence<'gc> for StepFn<F>
+where
+    F: 'static + FnMut(MutationContext<'gc, '_>) -> Option<R>,
+{
+    type Output = R;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        (self.0).0(mc)
+    }
+}
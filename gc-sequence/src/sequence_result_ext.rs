@@ -1,9 +1,15 @@
 use gc_arena::{Collect, MutationContext};
 
 use crate::{
+    and_next::AndNext,
     and_then::{AndThen, AndThenWith},
     flatten_result::FlattenOk,
+    inspect::{Inspect, InspectErr},
+    join::Join,
     map_result::{MapError, MapOk, MapOkWith},
+    on_complete::OnComplete,
+    select::Select,
+    step_limit::{StepLimit, StepLimitError},
     Sequence,
 };
 
@@ -85,6 +91,18 @@ pub trait SequenceResultExt<'gc, I, E>: Sized + Sequence<'gc, Output = Result<I,
         FlattenOk::new(AndThenWith::new(self, c, f))
     }
 
+    /// Run `next` after this sequence completes successfully, discarding this sequence's result.
+    ///
+    /// Unlike `SequenceResultExt::and_chain`, the next sequence is constructed up front rather than
+    /// from the result, which is useful for running setup steps in order.  If this sequence errors,
+    /// `next` is dropped without being stepped.
+    fn and_next<N, I2>(self, next: N) -> AndNext<Self, N>
+    where
+        N: Sequence<'gc, Output = Result<I2, E>>,
+    {
+        AndNext::new(self, next)
+    }
+
     /// Similar to `SequenceExt::flatten`, but this sequence must result in an `Ok(next_sequence)`.
     fn flatten_ok<I2>(self) -> FlattenOk<Self, I>
     where
@@ -92,6 +110,72 @@ pub trait SequenceResultExt<'gc, I, E>: Sized + Sequence<'gc, Output = Result<I,
     {
         FlattenOk::new(self)
     }
+
+    /// Step this sequence and `other` together, resulting in both of their results once both have
+    /// completed successfully.
+    ///
+    /// Each step of the returned sequence steps whichever of the two sequences have not yet
+    /// completed.  If either sequence errors, the join completes with that error immediately and
+    /// the other sequence is not stepped further.
+    fn join<B, I2>(self, other: B) -> Join<Self, B, I, I2>
+    where
+        I: Collect,
+        I2: Collect,
+        B: Sequence<'gc, Output = Result<I2, E>>,
+    {
+        Join::new(self, other)
+    }
+
+    /// Step this sequence and `other` together, resulting in the result of whichever completes
+    /// first.  The other sequence is dropped without being stepped further.
+    ///
+    /// This sequence is stepped first, so if both would complete in the same step, its result is
+    /// the one returned.  An error from either sequence completes the select with that error.
+    fn select<B, I2>(self, other: B) -> Select<Self, B>
+    where
+        B: Sequence<'gc, Output = Result<I2, E>>,
+    {
+        Select::new(self, other)
+    }
+
+    /// Step this sequence at most `max_steps` times.  If it has not completed by then, the last
+    /// allowed step produces a `StepLimitError` instead, and the inner sequence is not stepped again.
+    fn step_limit(self, max_steps: usize) -> StepLimit<Self>
+    where
+        E: From<StepLimitError>,
+    {
+        StepLimit::new(self, max_steps)
+    }
+
+    /// Call a function with a reference to the result of this sequence if it completes
+    /// successfully, and then pass the result through unchanged.
+    fn inspect<F>(self, f: F) -> Inspect<Self, F>
+    where
+        F: 'static + FnOnce(&I),
+    {
+        Inspect::new(self, f)
+    }
+
+    /// Call a function with a reference to the error of this sequence if it fails, and then pass
+    /// the error through unchanged.
+    fn inspect_err<F>(self, f: F) -> InspectErr<Self, F>
+    where
+        F: 'static + FnOnce(&E),
+    {
+        InspectErr::new(self, f)
+    }
+
+    /// Call a function with a reference to the result of this sequence once it completes, whether
+    /// it succeeded or failed, and then pass the result through unchanged.
+    ///
+    /// This is the sequence equivalent of a `finally` block.  If the sequence is dropped before it
+    /// completes, the function is never called.
+    fn on_complete<F>(self, f: F) -> OnComplete<Self, F>
+    where
+        F: 'static + FnOnce(&Result<I, E>),
+    {
+        OnComplete::new(self, f)
+    }
 }
 
 impl<'gc, T, I, E> SequenceResultExt<'gc, I, E> for T where T: Sequence<'gc, Output = Result<I, E>> {}
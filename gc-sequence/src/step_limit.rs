@@ -0,0 +1,60 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+/// The error produced by a `StepLimit` sequence whose inner sequence did not finish within its
+/// step budget.  Holds the number of steps that were allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Collect)]
+#[collect(require_static)]
+pub struct StepLimitError(pub usize);
+
+impl StdError for StepLimitError {}
+
+impl fmt::Display for StepLimitError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "sequence did not complete within {} steps", self.0)
+    }
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct StepLimit<S> {
+    s: S,
+    max_steps: usize,
+    steps: usize,
+}
+
+impl<S> StepLimit<S> {
+    pub fn new(s: S, max_steps: usize) -> StepLimit<S> {
+        StepLimit {
+            s,
+            max_steps,
+            steps: 0,
+        }
+    }
+}
+
+impl<'gc, S, I, E> Sequence<'gc> for StepLimit<S>
+where
+    S: Sequence<'gc, Output = Result<I, E>>,
+    E: From<StepLimitError>,
+{
+    type Output = Result<I, E>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        if self.steps == self.max_steps {
+            return Some(Err(StepLimitError(self.max_steps).into()));
+        }
+        self.steps += 1;
+        match self.s.step(mc) {
+            None if self.steps == self.max_steps => {
+                Some(Err(StepLimitError(self.max_steps).into()))
+            }
+            res => res,
+        }
+    }
+}
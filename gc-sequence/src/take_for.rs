@@ -0,0 +1,46 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::Sequence;
+
+/// The result of a `TakeFor` sequence.
+#[derive(Debug, Clone, PartialEq, Eq, Collect)]
+#[collect(empty_drop)]
+pub enum Progress<O> {
+    /// The sequence completed within its step budget.
+    Complete(O),
+    /// The step budget ran out first, and this is what `Sequence::partial` returned at that point.
+    Partial(Option<O>),
+}
+
+#[must_use = "sequences do nothing unless stepped"]
+#[derive(Debug, Collect)]
+#[collect(empty_drop)]
+pub struct TakeFor<S> {
+    s: S,
+    steps: usize,
+}
+
+impl<S> TakeFor<S> {
+    pub fn new(s: S, steps: usize) -> TakeFor<S> {
+        TakeFor { s, steps }
+    }
+}
+
+impl<'gc, S> Sequence<'gc> for TakeFor<S>
+where
+    S: Sequence<'gc>,
+{
+    type Output = Progress<S::Output>;
+
+    fn step(&mut self, mc: MutationContext<'gc, '_>) -> Option<Self::Output> {
+        if self.steps == 0 {
+            return Some(Progress::Partial(self.s.partial()));
+        }
+        self.steps -= 1;
+        match self.s.step(mc) {
+            Some(output) => Some(Progress::Complete(output)),
+            None if self.steps == 0 => Some(Progress::Partial(self.s.partial())),
+            None => None,
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use gc_arena::{ArenaParameters, Collect, Gc};
-use gc_sequence::{self as sequence, make_sequencable_arena, SequenceExt, SequenceResultExt};
+use gc_sequence::{
+    self as sequence, make_sequencable_arena, Either, SequenceExt, SequenceResultExt,
+};
 
 #[derive(Collect)]
 #[collect(empty_drop)]
@@ -43,3 +45,730 @@ fn test_sequencer() {
         }
     }
 }
+
+#[test]
+fn test_on_complete() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let calls = Rc::new(Cell::new(0));
+
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence({
+        let calls = calls.clone();
+        move |root| {
+            sequence::from_fn_with(root.test, |_, test| Ok::<_, ()>(*test))
+                .on_complete(move |res| {
+                    assert_eq!(*res, Ok(42));
+                    calls.set(calls.get() + 1);
+                })
+                .boxed()
+        }
+    });
+
+    let arena = loop {
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok(42));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+    assert_eq!(calls.get(), 1);
+
+    let mut sequence = arena.sequence({
+        let calls = calls.clone();
+        move |_| {
+            sequence::err::<(), _>("error")
+                .on_complete(move |res| {
+                    assert_eq!(*res, Err("error"));
+                    calls.set(calls.get() + 1);
+                })
+                .boxed()
+        }
+    });
+
+    let arena = loop {
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Err("error"));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+    assert_eq!(calls.get(), 2);
+
+    let sequence = arena.sequence({
+        let calls = calls.clone();
+        move |_| {
+            sequence::from_fn(|_| Ok::<(), ()>(()))
+                .and_chain(|_, _| Ok(sequence::ok(())))
+                .on_complete(move |_| calls.set(calls.get() + 1))
+                .boxed()
+        }
+    });
+    sequence.abort();
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn test_batch() {
+    #[derive(Collect)]
+    #[collect(require_static)]
+    struct Countdown(u32);
+
+    impl<'gc> sequence::Sequence<'gc> for Countdown {
+        type Output = ();
+
+        fn step(&mut self, _: gc_arena::MutationContext<'gc, '_>) -> Option<()> {
+            self.0 -= 1;
+            if self.0 == 0 {
+                Some(())
+            } else {
+                None
+            }
+        }
+    }
+
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let sequence = arena.sequence(|_| Countdown(5).batch(5).boxed());
+    let arena = match sequence.step() {
+        Ok((arena, ())) => arena,
+        Err(_) => panic!("batched sequence did not complete in one step"),
+    };
+
+    let sequence = arena.sequence(|_| Countdown(5).batch(3).boxed());
+    let sequence = match sequence.step() {
+        Ok(_) => panic!("batched sequence completed early"),
+        Err(sequence) => sequence,
+    };
+    assert!(sequence.step().is_ok());
+}
+
+#[test]
+fn test_map() {
+    #[derive(Collect)]
+    #[collect(require_static)]
+    struct Countdown(u32);
+
+    impl<'gc> sequence::Sequence<'gc> for Countdown {
+        type Output = Result<u32, &'static str>;
+
+        fn step(&mut self, _: gc_arena::MutationContext<'gc, '_>) -> Option<Self::Output> {
+            self.0 -= 1;
+            if self.0 == 0 {
+                Some(Ok(7))
+            } else {
+                None
+            }
+        }
+    }
+
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|_| Countdown(3).map(|res| res.map(|i| i * 6)).boxed());
+    let arena = loop {
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok(42));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+
+    let mut sequence = arena.sequence(|_| {
+        Countdown(3)
+            .and_then(|_, _| Err::<(), _>("error"))
+            .map_err(|err| err.len())
+            .boxed()
+    });
+    loop {
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, Err(5));
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+}
+
+#[test]
+fn test_and_chain() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|root| {
+        sequence::from_fn_with(root.test, |_, test| Ok::<_, &'static str>(*test))
+            .and_chain(|mc, first| {
+                let first = Gc::allocate(mc, first);
+                Ok(sequence::from_fn_with(first, |_, first| Ok(*first + 1)))
+            })
+            .boxed()
+    });
+    let arena = loop {
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok(43));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+
+    let mut sequence = arena.sequence(|_| {
+        sequence::err::<i32, _>("first")
+            .and_chain(|_, first| Ok(sequence::ok(first + 1)))
+            .boxed()
+    });
+    loop {
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, Err("first"));
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+}
+
+#[test]
+fn test_and_next() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let setup = Rc::new(Cell::new(false));
+
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence({
+        let setup = setup.clone();
+        move |root| {
+            let check = setup.clone();
+            sequence::from_fn(move |_| {
+                setup.set(true);
+                Ok(())
+            })
+            .and_next(sequence::from_fn_with(root.test, move |_, test| {
+                assert!(check.get(), "setup step did not run first");
+                Ok::<_, &'static str>(*test)
+            }))
+            .boxed()
+        }
+    });
+    let arena = loop {
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok(42));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+    assert!(setup.get());
+
+    let mut sequence = arena.sequence(|_| {
+        sequence::err::<(), _>("setup failed")
+            .and_next(sequence::from_fn(|_| -> Result<i32, _> {
+                panic!("next sequence stepped after an error")
+            }))
+            .boxed()
+    });
+    loop {
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, Err("setup failed"));
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+}
+
+#[test]
+fn test_flatten() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|root| {
+        sequence::from_fn_with(root.test, |_, test| {
+            Ok::<_, &'static str>(sequence::from_fn_with(test, |_, test| {
+                Ok::<_, &'static str>(*test * 2)
+            }))
+        })
+        .flatten_ok()
+        .map(|res| sequence::done(res.map(|i| i + 1)))
+        .flatten()
+        .boxed()
+    });
+    let mut steps = 0;
+    loop {
+        steps += 1;
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, Ok(85));
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+    assert_eq!(steps, 3);
+}
+
+#[test]
+fn test_join() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|root| {
+        let two_steps = sequence::from_fn_with(root.test, |_, test| Ok::<_, &'static str>(*test))
+            .and_then(|_, test| Ok(test + 1));
+        let one_step = sequence::from_fn(|_| Ok("done"));
+        two_steps.join(one_step).boxed()
+    });
+    let mut steps = 0;
+    let arena = loop {
+        steps += 1;
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok((43, "done")));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+    assert_eq!(steps, 2);
+
+    let sequence = arena.sequence(|_| {
+        sequence::ok::<i32, _>(1)
+            .and_then(|_, _| -> Result<i32, _> { panic!("joined sequence stepped after an error") })
+            .join(sequence::err::<(), _>("error"))
+            .boxed()
+    });
+    match sequence.step() {
+        Ok((_, output)) => assert_eq!(output, Err("error")),
+        Err(_) => panic!("join did not short-circuit on error"),
+    }
+}
+
+#[test]
+fn test_select() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|root| {
+        let one_step = sequence::from_fn_with(root.test, |_, test| Ok::<_, &'static str>(*test));
+        let two_steps = sequence::ok(()).and_then(|_, _| -> Result<(), _> {
+            panic!("losing sequence stepped after select completed")
+        });
+        one_step.select(two_steps).boxed()
+    });
+    let arena = loop {
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok(Either::Left(42)));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+
+    let sequence = arena.sequence(|_| {
+        sequence::from_fn(|_| Ok(1))
+            .and_then(|_, i| Ok(i + 1))
+            .select(sequence::err::<(), _>("error"))
+            .boxed()
+    });
+    match sequence.step() {
+        Ok((_, output)) => assert_eq!(output, Err("error")),
+        Err(_) => panic!("select did not complete with the error"),
+    }
+}
+
+#[test]
+fn test_from_step_fn() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut count = 0;
+    let mut sequence = arena.sequence(|_| {
+        sequence::from_step_fn(move |_| {
+            count += 1;
+            if count == 3 {
+                Some(Ok::<_, ()>(count))
+            } else {
+                None
+            }
+        })
+        .boxed()
+    });
+    let mut steps = 0;
+    loop {
+        steps += 1;
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, Ok(3));
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+    assert_eq!(steps, 3);
+}
+
+#[test]
+fn test_box_sequence() {
+    // Steps every sequence in the list until all of them have completed.
+    #[derive(Collect)]
+    #[collect(empty_drop)]
+    struct All<'gc>(Vec<sequence::BoxSequence<'gc, i32>>, Vec<i32>);
+
+    impl<'gc> sequence::Sequence<'gc> for All<'gc> {
+        type Output = Vec<i32>;
+
+        fn step(&mut self, mc: gc_arena::MutationContext<'gc, '_>) -> Option<Vec<i32>> {
+            let outputs = &mut self.1;
+            self.0.retain_mut(|s| match s.step(mc) {
+                Some(output) => {
+                    outputs.push(output);
+                    false
+                }
+                None => true,
+            });
+            if self.0.is_empty() {
+                Some(std::mem::take(outputs))
+            } else {
+                None
+            }
+        }
+    }
+
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|root| {
+        let sequences = vec![
+            sequence::done(1).then(|_, i| i + 1).boxed(),
+            sequence::from_fn_with(root.test, |_, test| *test).boxed(),
+        ];
+        All(sequences, Vec::new()).boxed()
+    });
+    loop {
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, vec![42, 2]);
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+}
+
+#[test]
+fn test_take_for() {
+    use sequence::take_for::Progress;
+
+    // Sums the numbers from 1 to `end`, one number per step.
+    #[derive(Collect)]
+    #[collect(require_static)]
+    struct Sum {
+        next: u32,
+        end: u32,
+        sum: u32,
+    }
+
+    impl<'gc> sequence::Sequence<'gc> for Sum {
+        type Output = u32;
+
+        fn step(&mut self, _: gc_arena::MutationContext<'gc, '_>) -> Option<u32> {
+            self.sum += self.next;
+            self.next += 1;
+            if self.next > self.end {
+                Some(self.sum)
+            } else {
+                None
+            }
+        }
+
+        fn partial(&self) -> Option<u32> {
+            Some(self.sum)
+        }
+    }
+
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let run = |arena: TestArena, end, steps| {
+        let mut sequence = arena.sequence(move |_| {
+            Sum {
+                next: 1,
+                end,
+                sum: 0,
+            }
+            .take_for(steps)
+            .boxed()
+        });
+        let mut taken = 1;
+        loop {
+            match sequence.step() {
+                Ok((arena, progress)) => return (arena, progress, taken),
+                Err(s) => sequence = s,
+            }
+            taken += 1;
+        }
+    };
+
+    let (arena, progress, taken) = run(arena, 10, 4);
+    assert_eq!(progress, Progress::Partial(Some(10)));
+    assert_eq!(taken, 4);
+    let (arena, progress, taken) = run(arena, 3, 4);
+    assert_eq!(progress, Progress::Complete(6));
+    assert_eq!(taken, 3);
+    let (_, progress, taken) = run(arena, 3, 0);
+    assert_eq!(progress, Progress::Partial(Some(0)));
+    assert_eq!(taken, 1);
+}
+
+#[test]
+fn test_loop_until() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|_| {
+        let mut attempts = 0;
+        sequence::loop_until(
+            move |_| {
+                attempts += 1;
+                sequence::done(attempts)
+            },
+            |&attempts| attempts == 3,
+        )
+        .boxed()
+    });
+    let mut steps = 0;
+    loop {
+        steps += 1;
+        match sequence.step() {
+            Ok((_, output)) => {
+                assert_eq!(output, 3);
+                break;
+            }
+            Err(s) => sequence = s,
+        }
+    }
+    assert_eq!(steps, 3);
+}
+
+#[test]
+fn test_optional_result() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let sequence = arena.sequence(|root| {
+        let value = Some(*root.test);
+        sequence::ok::<_, ()>(value).boxed()
+    });
+    let arena = match sequence.step() {
+        Ok((arena, output)) => {
+            assert_eq!(output, Ok(Some(42)));
+            arena
+        }
+        Err(_) => panic!("optional result took more than one step"),
+    };
+
+    let sequence = arena.sequence(|_| sequence::ok::<Option<i32>, ()>(None).boxed());
+    match sequence.step() {
+        Ok((_, output)) => assert_eq!(output, Ok(None)),
+        Err(_) => panic!("optional result took more than one step"),
+    }
+}
+
+#[test]
+fn test_collect_all() {
+    let arena = TestArena::new(ArenaParameters::default(), |mc| TestRoot {
+        test: Gc::allocate(mc, 42),
+    });
+
+    let mut sequence = arena.sequence(|_| {
+        let countdown = |steps: u32, value: i32| {
+            let mut remaining = steps;
+            sequence::from_step_fn(move |_| {
+                remaining -= 1;
+                if remaining == 0 {
+                    Some(Ok::<_, ()>(value))
+                } else {
+                    None
+                }
+            })
+        };
+        sequence::collect_all(vec![countdown(3, 1), countdown(1, 2), countdown(2, 3)]).boxed()
+    });
+    let mut steps = 0;
+    let arena = loop {
+        steps += 1;
+        match sequence.step() {
+            Ok((arena, output)) => {
+                assert_eq!(output, Ok(vec![1, 2, 3]));
+                break arena;
+            }
+            Err(s) => sequence = s,
+        }
+    };
+    assert_eq!(steps, 3);
+
+    let sequence = arena.sequence(|_| {
+        sequence::collect_all(vec![
+            sequence::ok(1),
+            sequence::err("error"),
+            sequence::ok(3),
+        ])
+        .boxed()
+    });
+    match sequence.step() {
+        Ok((_, output)) => assert_eq!(output, Err("error")),
+        Err(_) => panic!("collect_all did not short-circuit on error"),
+    }
+}
+
+#[test]
+fn test_fuse() {
+    use gc_arena::rootless_arena;
+    use sequence::Sequence;
+
+    rootless_arena(|mc| {
+        let mut sequence = sequence::ok::<_, ()>(42).and_then(|_, i| Ok(i + 1)).fuse();
+        assert_eq!(sequence.step(mc), None);
+        assert!(!sequence.is_finished());
+        assert_eq!(sequence.step(mc), Some(Ok(43)));
+        assert!(sequence.is_finished());
+        for _ in 0..3 {
+            assert_eq!(sequence.step(mc), None);
+        }
+    });
+}
+
+#[test]
+fn test_run_sequence() {
+    use gc_arena::rootless_arena;
+    use sequence::Sequence;
+
+    fn make<'gc>() -> impl Sequence<'gc, Output = Result<i32, ()>> {
+        sequence::ok(1)
+            .and_then(|_, i| Ok(i + 1))
+            .and_chain(|_, i| Ok(sequence::ok(i * 10)))
+    }
+
+    rootless_arena(|mc| {
+        let mut manual = make();
+        let mut steps = 1;
+        let expected = loop {
+            match manual.step(mc) {
+                Some(res) => break res,
+                None => steps += 1,
+            }
+        };
+        assert!(steps > 1);
+        assert_eq!(expected, Ok(20));
+        assert_eq!(sequence::run_sequence(mc, make()), expected);
+    });
+}
+
+#[test]
+fn test_inspect() {
+    use gc_arena::rootless_arena;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let oks = Rc::new(Cell::new(0));
+    let errs = Rc::new(Cell::new(0));
+
+    rootless_arena(|mc| {
+        let (ok_count, err_count) = (oks.clone(), errs.clone());
+        let ok = sequence::ok::<_, &'static str>(1)
+            .and_then(|_, i| Ok(i + 1))
+            .inspect(move |&i| {
+                assert_eq!(i, 2);
+                ok_count.set(ok_count.get() + 1);
+            })
+            .inspect_err(move |_| err_count.set(err_count.get() + 1));
+        assert_eq!(sequence::run_sequence(mc, ok), Ok(2));
+        assert_eq!((oks.get(), errs.get()), (1, 0));
+
+        let (ok_count, err_count) = (oks.clone(), errs.clone());
+        let err = sequence::err::<i32, _>("error")
+            .inspect(move |_| ok_count.set(ok_count.get() + 1))
+            .inspect_err(move |&err| {
+                assert_eq!(err, "error");
+                err_count.set(err_count.get() + 1);
+            });
+        assert_eq!(sequence::run_sequence(mc, err), Err("error"));
+        assert_eq!((oks.get(), errs.get()), (1, 1));
+    });
+}
+
+#[test]
+fn test_step_limit() {
+    use gc_arena::rootless_arena;
+    use sequence::{Sequence, StepLimitError};
+
+    rootless_arena(|mc| {
+        let mut steps = 0;
+        let mut forever =
+            sequence::from_step_fn(|_| None::<Result<(), StepLimitError>>).step_limit(10);
+        let res = loop {
+            steps += 1;
+            if let Some(res) = forever.step(mc) {
+                break res;
+            }
+        };
+        assert_eq!(res, Err(StepLimitError(10)));
+        assert_eq!(steps, 10);
+
+        let mut within = sequence::ok::<_, StepLimitError>(1).step_limit(1);
+        assert_eq!(within.step(mc), Some(Ok(1)));
+    });
+}
+
+#[test]
+fn test_from_iter() {
+    use gc_arena::rootless_arena;
+    use sequence::Sequence;
+
+    rootless_arena(|mc| {
+        let mut s = sequence::from_iter(0..100);
+        let mut steps = 0;
+        let items = loop {
+            steps += 1;
+            if let Some(items) = s.step(mc) {
+                break items;
+            }
+        };
+        assert_eq!(steps, 100);
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+
+        let mut empty = sequence::from_iter(std::iter::empty::<i32>());
+        assert_eq!(empty.step(mc), Some(Vec::new()));
+    });
+}
@@ -6,9 +6,7 @@ use clap::{crate_authors, crate_description, crate_name, crate_version, App, Arg
 use rustyline::Editor;
 
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
-use luster::{
-    compile, io, Closure, Error, Function, Lua, ParserError, StaticError, ThreadSequence,
-};
+use luster::{compile, io, Closure, Function, Lua, ThreadSequence};
 
 fn run_repl(lua: &mut Lua) {
     let mut editor = Editor::<()>::new();
@@ -30,9 +28,7 @@ fn run_repl(lua: &mut Lua) {
                     let result = compile(mc, root.interned_strings, line_clone.as_bytes());
                     let result = match result {
                         Ok(res) => Ok(res),
-                        err @ Err(Error::ParserError(ParserError::EndOfStream { expected: _ })) => {
-                            err
-                        }
+                        Err(err) if err.is_incomplete() => Err(err),
                         Err(_) => compile(
                             mc,
                             root.interned_strings,
@@ -62,12 +58,12 @@ fn run_repl(lua: &mut Lua) {
                 })
                 .boxed()
             }) {
-                err @ Err(StaticError::ParserError(ParserError::EndOfStream { expected: _ })) => {
+                Err(err) if err.is_incomplete() => {
                     match line.chars().last() {
                         Some(c) => {
                             if c == '\n' {
                                 editor.add_history_entry(line);
-                                eprintln!("error: {}", err.err().unwrap());
+                                eprintln!("error: {}", err);
                                 break;
                             }
                             prompt = ">> ";
@@ -1,10 +1,13 @@
+use std::cell::{Cell, RefCell};
 use std::fmt::{self, Debug};
 use std::hash::{Hash, Hasher};
 
-use gc_arena::{Collect, Gc, MutationContext, StaticCollect};
-use gc_sequence::{Sequence, SequenceExt};
+use gc_arena::{Collect, Gc, GcCell, MutationContext, StaticCollect};
+use gc_sequence::{BoxSequence, Sequence, SequenceExt};
 
-use crate::{Error, Function, Value};
+use rustc_hash::FxHashMap;
+
+use crate::{Error, Function, RuntimeError, String, Table, Value};
 
 // Safe, does not implement drop
 #[derive(Collect)]
@@ -21,7 +24,7 @@ pub enum CallbackResult<'gc> {
 
 pub enum CallbackReturn<'gc> {
     Immediate(Result<CallbackResult<'gc>, Error<'gc>>),
-    Sequence(Box<dyn Sequence<'gc, Output = Result<CallbackResult<'gc>, Error<'gc>>> + 'gc>),
+    Sequence(BoxSequence<'gc, Result<CallbackResult<'gc>, Error<'gc>>>),
 }
 
 pub trait ContinuationFn<'gc>: Collect {
@@ -139,14 +142,48 @@ impl<'gc> Continuation<'gc> {
 }
 
 pub trait CallbackFn<'gc>: Collect {
-    fn call(&self, res: Vec<Value<'gc>>) -> CallbackReturn<'gc>;
+    fn call(&self, mc: MutationContext<'gc, '_>, args: Vec<Value<'gc>>) -> CallbackReturn<'gc>;
+
+    /// A human readable name for this callback, used in its `Debug` output and error messages.
+    fn name(&self) -> Option<&str> {
+        None
+    }
 }
 
 #[derive(Clone, Copy, Collect)]
 #[collect(require_copy)]
-pub struct Callback<'gc>(pub Gc<'gc, Box<dyn CallbackFn<'gc> + 'gc>>);
+pub struct Callback<'gc>(pub Gc<'gc, CallbackBox<'gc>>);
+
+/// The allocation behind a `Callback`, holding the callback function along with its id.
+// Safe, does not implement drop
+#[derive(Collect)]
+#[collect(unsafe_drop)]
+pub struct CallbackBox<'gc> {
+    id: u64,
+    function: Box<dyn CallbackFn<'gc> + 'gc>,
+}
+
+thread_local! {
+    // Arenas cannot leave the thread they were created on, so a per-thread counter is enough to
+    // keep ids unique within an arena, and it makes the ids assigned by a single-threaded program
+    // the same on every run.
+    static NEXT_CALLBACK_ID: Cell<u64> = const { Cell::new(0) };
+}
 
 impl<'gc> Callback<'gc> {
+    /// Allocates a callback from any `CallbackFn` implementation, assigning it a fresh id.
+    pub fn from_fn(
+        mc: MutationContext<'gc, '_>,
+        function: Box<dyn CallbackFn<'gc> + 'gc>,
+    ) -> Callback<'gc> {
+        let id = NEXT_CALLBACK_ID.with(|next| {
+            let id = next.get();
+            next.set(id + 1);
+            id
+        });
+        Callback(Gc::allocate(mc, CallbackBox { id, function }))
+    }
+
     pub fn new<F>(mc: MutationContext<'gc, '_>, f: F) -> Callback<'gc>
     where
         F: 'static + Fn(Vec<Value<'gc>>) -> CallbackReturn<'gc>,
@@ -159,12 +196,104 @@ impl<'gc> Callback<'gc> {
         where
             F: 'static + Fn(Vec<Value<'gc>>) -> CallbackReturn<'gc>,
         {
-            fn call(&self, res: Vec<Value<'gc>>) -> CallbackReturn<'gc> {
+            fn call(
+                &self,
+                _: MutationContext<'gc, '_>,
+                res: Vec<Value<'gc>>,
+            ) -> CallbackReturn<'gc> {
                 self.0(res)
             }
         }
 
-        Callback(Gc::allocate(mc, Box::new(StaticCallbackFn(f))))
+        Callback::from_fn(mc, Box::new(StaticCallbackFn(f)))
+    }
+
+    /// Creates a callback with a name, which appears in its `Debug` output and is prefixed to the
+    /// message of any error it returns other than a `RuntimeError`.  Runtime errors are passed
+    /// through unchanged, since they carry a Lua value which may be inspected by `pcall`, and so
+    /// are errors which are not recoverable, which `pcall` must not catch.
+    pub fn new_named<F>(
+        mc: MutationContext<'gc, '_>,
+        name: impl Into<Box<str>>,
+        f: F,
+    ) -> Callback<'gc>
+    where
+        F: 'static + Fn(Vec<Value<'gc>>) -> CallbackReturn<'gc>,
+    {
+        #[derive(Collect)]
+        #[collect(require_static)]
+        struct NamedCallbackFn<F> {
+            name: Box<str>,
+            f: F,
+        }
+
+        impl<'gc, F> CallbackFn<'gc> for NamedCallbackFn<F>
+        where
+            F: 'static + Fn(Vec<Value<'gc>>) -> CallbackReturn<'gc>,
+        {
+            fn call(
+                &self,
+                mc: MutationContext<'gc, '_>,
+                args: Vec<Value<'gc>>,
+            ) -> CallbackReturn<'gc> {
+                match (self.f)(args) {
+                    CallbackReturn::Immediate(res) => CallbackReturn::Immediate(
+                        res.map_err(|err| name_error(mc, &self.name, err)),
+                    ),
+                    CallbackReturn::Sequence(seq) => {
+                        let name = self.name.clone();
+                        CallbackReturn::Sequence(
+                            seq.then(move |mc, res| res.map_err(|err| name_error(mc, &name, err)))
+                                .boxed(),
+                        )
+                    }
+                }
+            }
+
+            fn name(&self) -> Option<&str> {
+                Some(&self.name)
+            }
+        }
+
+        Callback::from_fn(
+            mc,
+            Box::new(NamedCallbackFn {
+                name: name.into(),
+                f,
+            }),
+        )
+    }
+
+    /// Creates a callback from an `FnMut`, so that it may keep mutable state between calls.
+    ///
+    /// The function is mutably borrowed for the duration of each call, so a re-entrant call of the
+    /// same callback will panic.
+    pub fn new_mut<F>(mc: MutationContext<'gc, '_>, f: F) -> Callback<'gc>
+    where
+        F: 'static + FnMut(Vec<Value<'gc>>) -> CallbackReturn<'gc>,
+    {
+        #[derive(Collect)]
+        #[collect(require_static)]
+        struct StaticCallbackFnMut<F>(RefCell<F>);
+
+        impl<'gc, F> CallbackFn<'gc> for StaticCallbackFnMut<F>
+        where
+            F: 'static + FnMut(Vec<Value<'gc>>) -> CallbackReturn<'gc>,
+        {
+            fn call(
+                &self,
+                _: MutationContext<'gc, '_>,
+                res: Vec<Value<'gc>>,
+            ) -> CallbackReturn<'gc> {
+                let mut f = self
+                    .0
+                    .try_borrow_mut()
+                    .expect("mutable callback called re-entrantly");
+                (*f)(res)
+            }
+        }
+
+        Callback::from_fn(mc, Box::new(StaticCallbackFnMut(RefCell::new(f))))
     }
 
     pub fn new_with<C, F>(mc: MutationContext<'gc, '_>, c: C, f: F) -> Callback<'gc>
@@ -181,15 +310,50 @@ impl<'gc> Callback<'gc> {
             C: 'gc + Collect,
             F: 'static + Fn(&C, Vec<Value<'gc>>) -> CallbackReturn<'gc>,
         {
-            fn call(&self, args: Vec<Value<'gc>>) -> CallbackReturn<'gc> {
+            fn call(
+                &self,
+                _: MutationContext<'gc, '_>,
+                args: Vec<Value<'gc>>,
+            ) -> CallbackReturn<'gc> {
                 (self.1).0(&self.0, args)
             }
         }
 
-        Callback(Gc::allocate(
-            mc,
-            Box::new(ContextCallbackFn(c, StaticCollect(f))),
-        ))
+        Callback::from_fn(mc, Box::new(ContextCallbackFn(c, StaticCollect(f))))
+    }
+
+    /// Creates a callback which is given the `MutationContext` on every call, so that it can
+    /// allocate the values it returns.
+    pub fn new_with_mc<F>(mc: MutationContext<'gc, '_>, f: F) -> Callback<'gc>
+    where
+        F: 'static
+            + for<'context> Fn(
+                MutationContext<'gc, 'context>,
+                Vec<Value<'gc>>,
+            ) -> Result<CallbackResult<'gc>, Error<'gc>>,
+    {
+        #[derive(Collect)]
+        #[collect(require_static)]
+        struct MutationCallbackFn<F>(F);
+
+        impl<'gc, F> CallbackFn<'gc> for MutationCallbackFn<F>
+        where
+            F: 'static
+                + for<'context> Fn(
+                    MutationContext<'gc, 'context>,
+                    Vec<Value<'gc>>,
+                ) -> Result<CallbackResult<'gc>, Error<'gc>>,
+        {
+            fn call(
+                &self,
+                mc: MutationContext<'gc, '_>,
+                args: Vec<Value<'gc>>,
+            ) -> CallbackReturn<'gc> {
+                CallbackReturn::Immediate(self.0(mc, args))
+            }
+        }
+
+        Callback::from_fn(mc, Box::new(MutationCallbackFn(f)))
     }
 
     pub fn new_immediate<F>(mc: MutationContext<'gc, '_>, f: F) -> Callback<'gc>
@@ -199,6 +363,13 @@ impl<'gc> Callback<'gc> {
         Callback::new(mc, move |res| CallbackReturn::Immediate(f(res)))
     }
 
+    pub fn new_immediate_mut<F>(mc: MutationContext<'gc, '_>, mut f: F) -> Callback<'gc>
+    where
+        F: 'static + FnMut(Vec<Value<'gc>>) -> Result<CallbackResult<'gc>, Error<'gc>>,
+    {
+        Callback::new_mut(mc, move |res| CallbackReturn::Immediate(f(res)))
+    }
+
     pub fn new_immediate_with<C, F>(mc: MutationContext<'gc, '_>, c: C, f: F) -> Callback<'gc>
     where
         C: 'gc + Collect,
@@ -230,22 +401,109 @@ impl<'gc> Callback<'gc> {
         })
     }
 
-    pub fn call(&self, args: Vec<Value<'gc>>) -> CallbackReturn<'gc> {
-        self.0.call(args)
+    pub fn call(&self, mc: MutationContext<'gc, '_>, args: Vec<Value<'gc>>) -> CallbackReturn<'gc> {
+        self.0.function.call(mc, args)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.0.function.name()
+    }
+
+    /// A number which uniquely identifies this callback, assigned in creation order.  Unlike the
+    /// callback's address, this is stable across runs, so it is used for equality and hashing.
+    pub fn id(&self) -> u64 {
+        self.0.id
+    }
+}
+
+/// Callbacks kept by name, so that a native function is allocated once and can then be handed out
+/// to every table that refers to it.
+///
+/// The standard library registers each of its functions here under its global name, such as
+/// `print` or `string.len`.
+#[derive(Debug, Clone, Copy, Collect)]
+#[collect(require_copy)]
+pub struct CallbackRegistry<'gc>(GcCell<'gc, FxHashMap<std::string::String, Callback<'gc>>>);
+
+impl<'gc> CallbackRegistry<'gc> {
+    pub fn new(mc: MutationContext<'gc, '_>) -> CallbackRegistry<'gc> {
+        CallbackRegistry(GcCell::allocate(mc, FxHashMap::default()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Callback<'gc>> {
+        self.0.read().get(name).copied()
+    }
+
+    /// Registers `callback` under `name`, returning the callback previously registered there.
+    pub fn register(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        name: impl Into<std::string::String>,
+        callback: Callback<'gc>,
+    ) -> Option<Callback<'gc>> {
+        self.0.write(mc).insert(name.into(), callback)
+    }
+
+    /// Registers every callback found in `globals`, and in the tables directly inside it, under
+    /// its dotted path.
+    pub(crate) fn register_globals(&self, mc: MutationContext<'gc, '_>, globals: Table<'gc>) {
+        let name = |key: Value<'gc>| match key {
+            Value::String(s) => {
+                Some(std::string::String::from_utf8_lossy(s.as_bytes()).into_owned())
+            }
+            _ => None,
+        };
+
+        for (key, value) in globals.0.read().entries() {
+            let key = match name(key) {
+                Some(key) => key,
+                None => continue,
+            };
+            match value {
+                Value::Function(Function::Callback(callback)) => {
+                    self.register(mc, key, callback);
+                }
+                Value::Table(library) => {
+                    for (field, value) in library.0.read().entries() {
+                        if let (Some(field), Value::Function(Function::Callback(callback))) =
+                            (name(field), value)
+                        {
+                            self.register(mc, format!("{}.{}", key, field), callback);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Fatal errors keep their variant, so that `pcall` still cannot catch them once they are named.
+fn name_error<'gc>(mc: MutationContext<'gc, '_>, name: &str, error: Error<'gc>) -> Error<'gc> {
+    match error {
+        Error::RuntimeError(error) => Error::RuntimeError(error),
+        error if !error.is_recoverable() => error,
+        error => RuntimeError(Value::String(String::new(
+            mc,
+            format!("{}: {}", name, error).as_bytes(),
+        )))
+        .into(),
     }
 }
 
 impl<'gc> Debug for Callback<'gc> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_tuple("Callback")
-            .field(&Gc::as_ptr(self.0))
-            .finish()
+        let mut debug = fmt.debug_tuple("Callback");
+        if let Some(name) = self.name() {
+            debug.field(&name);
+        }
+        debug.field(&self.id()).finish()
     }
 }
 
 impl<'gc> PartialEq for Callback<'gc> {
     fn eq(&self, other: &Callback<'gc>) -> bool {
-        Gc::ptr_eq(self.0, other.0)
+        self.id() == other.id()
     }
 }
 
@@ -253,6 +511,6 @@ impl<'gc> Eq for Callback<'gc> {}
 
 impl<'gc> Hash for Callback<'gc> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        Gc::as_ptr(self.0).hash(state)
+        self.id().hash(state)
     }
 }
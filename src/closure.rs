@@ -4,7 +4,7 @@ use std::hash::{Hash, Hasher};
 
 use gc_arena::{Collect, Gc, GcCell, MutationContext};
 
-use crate::{Constant, OpCode, RegisterIndex, Table, Thread, UpValueIndex, Value};
+use crate::{Constant, OpCode, RegisterIndex, String, Table, Thread, UpValueIndex, Value};
 
 #[derive(Debug, Collect, Clone, Copy, PartialEq, Eq)]
 #[collect(require_static)]
@@ -14,6 +14,17 @@ pub enum UpValueDescriptor {
     Outer(UpValueIndex),
 }
 
+/// Debug information for a named local variable, which lives in `register` while the instruction
+/// being executed is in the range `start_pc..end_pc`.
+#[derive(Debug, Collect, Clone, Copy)]
+#[collect(require_copy)]
+pub struct LocalVariable<'gc> {
+    pub name: String<'gc>,
+    pub register: RegisterIndex,
+    pub start_pc: usize,
+    pub end_pc: usize,
+}
+
 #[derive(Debug, Collect)]
 #[collect(empty_drop)]
 pub struct FunctionProto<'gc> {
@@ -24,6 +35,7 @@ pub struct FunctionProto<'gc> {
     pub opcodes: Vec<OpCode>,
     pub upvalues: Vec<UpValueDescriptor>,
     pub prototypes: Vec<Gc<'gc, FunctionProto<'gc>>>,
+    pub locals: Vec<LocalVariable<'gc>>,
 }
 
 #[derive(Debug, Collect, Copy, Clone)]
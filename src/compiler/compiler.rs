@@ -15,8 +15,8 @@ use crate::parser::{
     WhileStatement,
 };
 use crate::{
-    Constant, ConstantIndex16, ConstantIndex8, FunctionProto, OpCode, Opt254, PrototypeIndex,
-    RegisterIndex, String, UpValueDescriptor, UpValueIndex, VarCount,
+    Constant, ConstantIndex16, ConstantIndex8, FunctionProto, LocalVariable, OpCode, Opt254,
+    PrototypeIndex, RegisterIndex, String, UpValueDescriptor, UpValueIndex, VarCount,
 };
 
 use super::operators::{
@@ -34,6 +34,7 @@ pub enum CompilerError {
     FixedParameters,
     Functions,
     Constants,
+    Locals,
     OpCodes,
     DuplicateLabel,
     GotoInvalid,
@@ -51,6 +52,7 @@ impl fmt::Display for CompilerError {
             CompilerError::FixedParameters => write!(fmt, "too many fixed parameters"),
             CompilerError::Functions => write!(fmt, "too many inner functions"),
             CompilerError::Constants => write!(fmt, "too many constants"),
+            CompilerError::Locals => write!(fmt, "too many local variables"),
             CompilerError::OpCodes => write!(fmt, "too many opcodes"),
             CompilerError::DuplicateLabel => write!(fmt, "label defined multiple times"),
             CompilerError::GotoInvalid => write!(fmt, "goto target label not found"),
@@ -92,6 +94,7 @@ struct CompilerFunction<'gc> {
     has_varargs: bool,
     fixed_params: u8,
     locals: Vec<(String<'gc>, RegisterIndex)>,
+    local_variables: Vec<LocalVariable<'gc>>,
 
     blocks: Vec<BlockDescriptor>,
     unique_jump_id: u64,
@@ -225,7 +228,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         while let Some((_, last)) = self.current_function.locals.last() {
             if last.0 as u16 >= last_block.stack_bottom {
                 self.current_function.register_allocator.free(*last);
-                self.current_function.locals.pop();
+                self.current_function.pop_local();
             } else {
                 break;
             }
@@ -434,7 +437,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     .register_allocator
                     .push(1)
                     .ok_or(CompilerError::Registers)?;
-                self.current_function.locals.push((*name, loop_var));
+                self.current_function.add_local(*name, loop_var)?;
 
                 self.block_statements(body)?;
                 self.exit_block()?;
@@ -511,8 +514,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     .ok_or(CompilerError::Registers)?;
                 for i in 0..name_count {
                     self.current_function
-                        .locals
-                        .push((names[i as usize], RegisterIndex(names_reg.0 + i)));
+                        .add_local(names[i as usize], RegisterIndex(names_reg.0 + i))?;
                 }
 
                 self.jump(loop_label)?;
@@ -672,8 +674,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 .push(OpCode::LoadNil { dest, count });
             for i in 0..name_len {
                 self.current_function
-                    .locals
-                    .push((local_statement.names[i], RegisterIndex(dest.0 + i as u8)));
+                    .add_local(local_statement.names[i], RegisterIndex(dest.0 + i as u8))?;
             }
         } else {
             for i in 0..val_len {
@@ -688,16 +689,15 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     let dest = self.expr_push_count(expr, names_left)?;
 
                     for j in 0..names_left {
-                        self.current_function.locals.push((
+                        self.current_function.add_local(
                             local_statement.names[val_len - 1 + j as usize],
                             RegisterIndex(dest.0 + j),
-                        ));
+                        )?;
                     }
                 } else {
                     let reg = self.expr_discharge(expr, ExprDestination::PushNew)?;
                     self.current_function
-                        .locals
-                        .push((local_statement.names[i], reg));
+                        .add_local(local_statement.names[i], reg)?;
                 }
             }
         }
@@ -748,40 +748,88 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             expr: ExprDescriptor<'gc>,
         ) -> Result<(), CompilerError> {
             match target {
-                AssignmentTarget::Name(name) => match this.find_variable(*name)? {
-                    VariableDescriptor::Local(dest) => {
-                        this.expr_discharge(expr, ExprDestination::Register(dest))?;
-                    }
-                    VariableDescriptor::UpValue(dest) => {
-                        let (source, source_is_temp) = this.expr_any_register(expr)?;
-                        this.current_function
-                            .opcodes
-                            .push(OpCode::SetUpValue { source, dest });
-                        if source_is_temp {
-                            this.current_function.register_allocator.free(source);
-                        }
-                    }
-                    VariableDescriptor::Global(name) => {
-                        let env = this.get_environment()?;
-                        let key = ExprDescriptor::Constant(Constant::String(name));
-                        this.set_table(env, key, expr)?;
-                    }
-                },
-
+                AssignmentTarget::Name(name) => assign_name(this, *name, expr),
                 AssignmentTarget::Field(table, field) => {
                     let table = this.suffixed_expression(table)?;
-                    let key = match field {
-                        FieldSuffix::Named(name) => {
-                            ExprDescriptor::Constant(Constant::String(*name))
-                        }
-                        FieldSuffix::Indexed(idx) => this.expression(idx)?,
-                    };
-                    this.set_table(table, key, expr)?;
+                    let key = field_key(this, field)?;
+                    this.set_table(table, key, expr)
+                }
+            }
+        }
+
+        fn assign_name<'gc, 'a, 's>(
+            this: &'s mut Compiler<'gc, 'a>,
+            name: String<'gc>,
+            expr: ExprDescriptor<'gc>,
+        ) -> Result<(), CompilerError> {
+            match this.find_variable(name)? {
+                VariableDescriptor::Local(dest) => {
+                    this.expr_discharge(expr, ExprDestination::Register(dest))?;
+                }
+                VariableDescriptor::UpValue(dest) => {
+                    let (source, source_is_temp) = this.expr_any_register(expr)?;
+                    this.current_function
+                        .opcodes
+                        .push(OpCode::SetUpValue { source, dest });
+                    if source_is_temp {
+                        this.current_function.register_allocator.free(source);
+                    }
+                }
+                VariableDescriptor::Global(name) => {
+                    let env = this.get_environment()?;
+                    let key = ExprDescriptor::Constant(Constant::String(name));
+                    this.set_table(env, key, expr)?;
                 }
             }
             Ok(())
         }
 
+        fn field_key<'gc, 'a, 's>(
+            this: &'s mut Compiler<'gc, 'a>,
+            field: &FieldSuffix<String<'gc>>,
+        ) -> Result<ExprDescriptor<'gc>, CompilerError> {
+            Ok(match field {
+                FieldSuffix::Named(name) => ExprDescriptor::Constant(Constant::String(*name)),
+                FieldSuffix::Indexed(idx) => this.expression(idx)?,
+            })
+        }
+
+        fn local<'gc>(register: RegisterIndex) -> ExprDescriptor<'gc> {
+            ExprDescriptor::Variable(VariableDescriptor::Local(register))
+        }
+
+        if target_len == 1 && val_len == 1 {
+            let expr = self.expression(&assignment.values[0])?;
+            return assign(self, &assignment.targets[0], expr);
+        }
+
+        // With multiple targets or values, every table and key expression in the targets and then
+        // every value is evaluated into a fresh register before any assignment takes place, so that
+        // no assignment can affect the evaluation of another part of the statement.
+        let top = self.current_function.register_allocator.stack_top();
+
+        enum Target<'gc> {
+            Name(String<'gc>),
+            Field(RegisterIndex, ExprDescriptor<'gc>),
+        }
+
+        let mut targets = Vec::with_capacity(target_len);
+        for target in &assignment.targets {
+            targets.push(match target {
+                AssignmentTarget::Name(name) => Target::Name(*name),
+                AssignmentTarget::Field(table, field) => {
+                    let table = self.suffixed_expression(table)?;
+                    let table = self.expr_discharge(table, ExprDestination::PushNew)?;
+                    let key = match field_key(self, field)? {
+                        ExprDescriptor::Constant(constant) => ExprDescriptor::Constant(constant),
+                        key => local(self.expr_discharge(key, ExprDestination::PushNew)?),
+                    };
+                    Target::Field(table, key)
+                }
+            });
+        }
+
+        let mut values = Vec::with_capacity(target_len);
         for i in 0..val_len {
             let expr = self.expression(&assignment.values[i])?;
 
@@ -789,25 +837,26 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 let reg = self.expr_discharge(expr, ExprDestination::AllocateNew)?;
                 self.current_function.register_allocator.free(reg);
             } else if i == val_len - 1 {
-                let top = self.current_function.register_allocator.stack_top();
-
                 let targets_left =
                     cast(1 + target_len - val_len).ok_or(CompilerError::Registers)?;
                 let results = self.expr_push_count(expr, targets_left)?;
-
                 for j in 0..targets_left {
-                    let expr = ExprDescriptor::Variable(VariableDescriptor::Local(RegisterIndex(
-                        results.0 + j,
-                    )));
-                    assign(self, &assignment.targets[val_len - 1 + j as usize], expr)?;
+                    values.push(RegisterIndex(results.0 + j));
                 }
-
-                self.current_function.register_allocator.pop_to(top);
             } else {
-                assign(self, &assignment.targets[i], expr)?;
+                values.push(self.expr_discharge(expr, ExprDestination::PushNew)?);
             }
         }
 
+        for (target, value) in targets.into_iter().zip(values) {
+            match target {
+                Target::Name(name) => assign_name(self, name, local(value))?,
+                Target::Field(table, key) => self.set_table(local(table), key, local(value))?,
+            }
+        }
+
+        self.current_function.register_allocator.pop_to(top);
+
         Ok(())
     }
 
@@ -829,9 +878,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         self.current_function
             .opcodes
             .push(OpCode::Closure { proto, dest });
-        self.current_function
-            .locals
-            .push((local_function.name, dest));
+        self.current_function.add_local(local_function.name, dest)?;
 
         Ok(())
     }
@@ -2038,20 +2085,55 @@ impl<'gc> CompilerFunction<'gc> {
         function.has_varargs = has_varargs;
         function.fixed_params = fixed_params;
         for i in 0..fixed_params {
-            function
-                .locals
-                .push((parameters[i as usize], RegisterIndex(i)));
+            function.add_local(parameters[i as usize], RegisterIndex(i))?;
         }
         Ok(function)
     }
 
+    // Declares a new local variable in the current scope.  Like PUC-Rio Lua, this limits the number
+    // of locals which may be live at once well below the number of registers, so that there are
+    // always registers left over for temporaries.
+    fn add_local(
+        &mut self,
+        name: String<'gc>,
+        register: RegisterIndex,
+    ) -> Result<(), CompilerError> {
+        const MAX_LOCALS: usize = 200;
+
+        if self.locals.len() >= MAX_LOCALS {
+            return Err(CompilerError::Locals);
+        }
+        self.locals.push((name, register));
+        self.local_variables.push(LocalVariable {
+            name,
+            register,
+            start_pc: self.opcodes.len(),
+            end_pc: usize::MAX,
+        });
+        Ok(())
+    }
+
+    // Removes the innermost local variable from scope, returning its register.
+    fn pop_local(&mut self) -> Option<RegisterIndex> {
+        let (_, register) = self.locals.pop()?;
+        let end_pc = self.opcodes.len();
+        let variable = self
+            .local_variables
+            .iter_mut()
+            .rev()
+            .find(|v| v.end_pc == usize::MAX)
+            .expect("no open local variable debug info");
+        variable.end_pc = end_pc;
+        Some(register)
+    }
+
     fn finish(mut self, mc: MutationContext<'gc, '_>) -> Result<FunctionProto<'gc>, CompilerError> {
         self.opcodes.push(OpCode::Return {
             start: RegisterIndex(0),
             count: VarCount::constant(0),
         });
         assert!(self.locals.len() == self.fixed_params as usize);
-        for (_, r) in self.locals.drain(..) {
+        while let Some(r) = self.pop_local() {
             self.register_allocator.free(r);
         }
         assert_eq!(
@@ -2076,6 +2158,7 @@ impl<'gc> CompilerFunction<'gc> {
                 .into_iter()
                 .map(|f| Gc::allocate(mc, f))
                 .collect(),
+            locals: self.local_variables,
         })
     }
 }
@@ -402,63 +402,65 @@ pub fn comparison_binop_opcode(
                 }
             }
         },
+        // `a > b` and `a >= b` are evaluated as `b < a` and `b <= a` rather than by negating
+        // `a <= b` and `a < b`, which would give the wrong answer when either side is NaN.
         ComparisonBinOp::GreaterThan => match (left, right) {
             (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
-                OpCode::LessEqRR {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessRR {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
             (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
-                OpCode::LessEqRC {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessCR {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
             (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
-                OpCode::LessEqCR {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessRC {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
             (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
-                OpCode::LessEqCC {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessCC {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
         },
         ComparisonBinOp::GreaterEqual => match (left, right) {
             (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
-                OpCode::LessRR {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessEqRR {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
             (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
-                OpCode::LessRC {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessEqCR {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
             (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
-                OpCode::LessCR {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessEqRC {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
             (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
-                OpCode::LessCC {
-                    skip_if: !skip_if,
-                    left,
-                    right,
+                OpCode::LessEqCC {
+                    skip_if,
+                    left: right,
+                    right: left,
                 }
             }
         },
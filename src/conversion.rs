@@ -0,0 +1,263 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use gc_arena::{Collect, MutationContext};
+
+use crate::{Error, String, Table, Value};
+
+/// An error converting between a Lua value and a Rust type.
+#[derive(Debug, Clone, Copy, Collect)]
+#[collect(require_static)]
+pub struct ConversionError {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub message: Option<&'static str>,
+}
+
+impl StdError for ConversionError {}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "cannot convert {} to {}", self.from, self.to)?;
+        if let Some(message) = self.message {
+            write!(fmt, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+/// A Rust type which can be converted from a Lua value.
+pub trait FromLua<'gc>: Sized {
+    fn from_lua(value: Value<'gc>) -> Result<Self, Error<'gc>>;
+}
+
+/// A Rust type which can be converted into a Lua value.
+pub trait IntoLua<'gc> {
+    fn into_lua(self, mc: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>>;
+}
+
+/// Typed access to callback arguments.
+pub trait TypedArgs<'gc> {
+    /// Converts the argument at index `i`, treating a missing argument as nil.
+    fn get_typed<T: FromLua<'gc>>(&self, i: usize) -> Result<T, Error<'gc>>;
+}
+
+impl<'gc> TypedArgs<'gc> for [Value<'gc>] {
+    fn get_typed<T: FromLua<'gc>>(&self, i: usize) -> Result<T, Error<'gc>> {
+        T::from_lua(self.get(i).copied().unwrap_or(Value::Nil))
+    }
+}
+
+impl<'gc> FromLua<'gc> for Value<'gc> {
+    fn from_lua(value: Value<'gc>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(value)
+    }
+}
+
+impl<'gc> IntoLua<'gc> for Value<'gc> {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(self)
+    }
+}
+
+/// Converted from an integer, a float with an exact integer representation, or a string
+/// coercible to either.
+impl<'gc> FromLua<'gc> for i64 {
+    fn from_lua(value: Value<'gc>) -> Result<i64, Error<'gc>> {
+        value.to_integer().ok_or_else(|| {
+            ConversionError {
+                from: value.type_name(),
+                to: "i64",
+                message: None,
+            }
+            .into()
+        })
+    }
+}
+
+impl<'gc> IntoLua<'gc> for i64 {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::Integer(self))
+    }
+}
+
+/// Converted from any number, or a string coercible to one.
+impl<'gc> FromLua<'gc> for f64 {
+    fn from_lua(value: Value<'gc>) -> Result<f64, Error<'gc>> {
+        value.to_number().ok_or_else(|| {
+            ConversionError {
+                from: value.type_name(),
+                to: "f64",
+                message: None,
+            }
+            .into()
+        })
+    }
+}
+
+impl<'gc> IntoLua<'gc> for f64 {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::Number(self))
+    }
+}
+
+/// Converted by Lua truthiness, so only `nil` and `false` are false.
+impl<'gc> FromLua<'gc> for bool {
+    fn from_lua(value: Value<'gc>) -> Result<bool, Error<'gc>> {
+        Ok(value.to_bool())
+    }
+}
+
+impl<'gc> IntoLua<'gc> for bool {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::Boolean(self))
+    }
+}
+
+/// Converted only from a string; numbers are not coerced since that would need to allocate.
+impl<'gc> FromLua<'gc> for String<'gc> {
+    fn from_lua(value: Value<'gc>) -> Result<String<'gc>, Error<'gc>> {
+        match value {
+            Value::String(s) => Ok(s),
+            value => Err(ConversionError {
+                from: value.type_name(),
+                to: "string",
+                message: None,
+            }
+            .into()),
+        }
+    }
+}
+
+impl<'gc> IntoLua<'gc> for String<'gc> {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::String(self))
+    }
+}
+
+/// Converted from a UTF-8 string, or from a number in its `tostring` form.
+impl<'gc> FromLua<'gc> for std::string::String {
+    fn from_lua(value: Value<'gc>) -> Result<std::string::String, Error<'gc>> {
+        let error = |message| ConversionError {
+            from: value.type_name(),
+            to: "String",
+            message,
+        };
+        match value {
+            Value::String(s) => std::str::from_utf8(s.as_bytes())
+                .map(|s| s.to_owned())
+                .map_err(|_| error(Some("string is not valid UTF-8")).into()),
+            Value::Integer(_) | Value::Number(_) => Ok(format!("{}", value)),
+            _ => Err(error(None).into()),
+        }
+    }
+}
+
+impl<'gc> IntoLua<'gc> for std::string::String {
+    fn into_lua(self, mc: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::String(String::new(mc, self.as_bytes())))
+    }
+}
+
+impl<'gc> IntoLua<'gc> for &str {
+    fn into_lua(self, mc: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::String(String::new(mc, self.as_bytes())))
+    }
+}
+
+/// `nil` converts to `None`, anything else must convert to `T`.
+impl<'gc, T: FromLua<'gc>> FromLua<'gc> for Option<T> {
+    fn from_lua(value: Value<'gc>) -> Result<Option<T>, Error<'gc>> {
+        match value {
+            Value::Nil => Ok(None),
+            value => T::from_lua(value).map(Some),
+        }
+    }
+}
+
+impl<'gc, T: IntoLua<'gc>> IntoLua<'gc> for Option<T> {
+    fn into_lua(self, mc: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        match self {
+            Some(t) => t.into_lua(mc),
+            None => Ok(Value::Nil),
+        }
+    }
+}
+
+/// Converted from the sequence of a table, the values at keys `1..` up to the first nil.
+impl<'gc, T: FromLua<'gc>> FromLua<'gc> for Vec<T> {
+    fn from_lua(value: Value<'gc>) -> Result<Vec<T>, Error<'gc>> {
+        match value {
+            Value::Table(table) => table.to_array_vec().into_iter().map(T::from_lua).collect(),
+            value => Err(ConversionError {
+                from: value.type_name(),
+                to: "Vec",
+                message: None,
+            }
+            .into()),
+        }
+    }
+}
+
+/// Converted into a new table with the elements at keys `1..`.
+impl<'gc, T: IntoLua<'gc>> IntoLua<'gc> for Vec<T> {
+    fn into_lua(self, mc: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        let table = Table::new(mc);
+        for (i, t) in self.into_iter().enumerate() {
+            table.set(mc, Value::Integer(i as i64 + 1), t.into_lua(mc)?)?;
+        }
+        Ok(Value::Table(table))
+    }
+}
+
+/// Converted from a non-negative number of seconds, which may be fractional.
+impl<'gc> FromLua<'gc> for Duration {
+    fn from_lua(value: Value<'gc>) -> Result<Duration, Error<'gc>> {
+        let error = |message| ConversionError {
+            from: value.type_name(),
+            to: "Duration",
+            message,
+        };
+        let secs = value.to_number().ok_or_else(|| error(None))?;
+        if secs < 0.0 {
+            return Err(error(Some("duration is negative")).into());
+        }
+        Duration::try_from_secs_f64(secs).map_err(|_| error(Some("duration out of range")).into())
+    }
+}
+
+impl<'gc> IntoLua<'gc> for Duration {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::Number(self.as_secs_f64()))
+    }
+}
+
+/// Converted from a number of seconds since the Unix epoch, which may be fractional or negative.
+impl<'gc> FromLua<'gc> for SystemTime {
+    fn from_lua(value: Value<'gc>) -> Result<SystemTime, Error<'gc>> {
+        let error = |message| ConversionError {
+            from: value.type_name(),
+            to: "SystemTime",
+            message,
+        };
+        let secs = value.to_number().ok_or_else(|| error(None))?;
+        let out_of_range = || error(Some("time out of range"));
+        let offset = Duration::try_from_secs_f64(secs.abs()).map_err(|_| out_of_range())?;
+        if secs < 0.0 {
+            UNIX_EPOCH.checked_sub(offset)
+        } else {
+            UNIX_EPOCH.checked_add(offset)
+        }
+        .ok_or_else(|| out_of_range().into())
+    }
+}
+
+impl<'gc> IntoLua<'gc> for SystemTime {
+    fn into_lua(self, _: MutationContext<'gc, '_>) -> Result<Value<'gc>, Error<'gc>> {
+        Ok(Value::Number(match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        }))
+    }
+}
@@ -5,8 +5,8 @@ use std::{fmt, io};
 use gc_arena::{Collect, MutationContext, StaticCollect};
 
 use crate::{
-    BadThreadMode, BinaryOperatorError, ClosureError, CompilerError, InternedStringSet,
-    InvalidTableKey, ParserError, StringError, ThreadError, Value,
+    BadThreadMode, BinaryOperatorError, ClosureError, CompilerError, ConversionError,
+    InternedStringSet, InvalidTableKey, ParserError, StringError, Table, ThreadError, Value,
 };
 
 #[derive(Debug, Clone, Copy, Collect)]
@@ -56,6 +56,7 @@ pub enum Error<'gc> {
     ThreadError(ThreadError),
     BadThreadMode(BadThreadMode),
     TypeError(TypeError),
+    ConversionError(ConversionError),
     BinaryOperatorError(BinaryOperatorError),
     RuntimeError(RuntimeError<'gc>),
 }
@@ -74,6 +75,7 @@ impl<'gc> fmt::Display for Error<'gc> {
             Error::ThreadError(error) => write!(fmt, "thread error: {}", error),
             Error::BadThreadMode(error) => write!(fmt, "bad thread mode: {}", error),
             Error::TypeError(error) => write!(fmt, "type error: {}", error),
+            Error::ConversionError(error) => write!(fmt, "conversion error: {}", error),
             Error::BinaryOperatorError(error) => write!(fmt, "operator error: {}", error),
             Error::RuntimeError(error) => write!(fmt, "runtime error: {}", error),
         }
@@ -134,6 +136,12 @@ impl<'gc> From<TypeError> for Error<'gc> {
     }
 }
 
+impl<'gc> From<ConversionError> for Error<'gc> {
+    fn from(error: ConversionError) -> Error<'gc> {
+        Error::ConversionError(error)
+    }
+}
+
 impl<'gc> From<BinaryOperatorError> for Error<'gc> {
     fn from(error: BinaryOperatorError) -> Error<'gc> {
         Error::BinaryOperatorError(error)
@@ -147,6 +155,52 @@ impl<'gc> From<RuntimeError<'gc>> for Error<'gc> {
 }
 
 impl<'gc> Error<'gc> {
+    /// Builds a structured error object, a table of the form `{code = code, message = message}`,
+    /// which Lua code sees as the error value when it is caught by `pcall`.
+    pub fn table(
+        mc: MutationContext<'gc, '_>,
+        code: impl Into<Value<'gc>>,
+        message: impl Into<Value<'gc>>,
+    ) -> Error<'gc> {
+        let table = Table::new(mc);
+        table
+            .set(mc, crate::String::new_static(b"code"), code)
+            .unwrap();
+        table
+            .set(mc, crate::String::new_static(b"message"), message)
+            .unwrap();
+        RuntimeError(Value::Table(table)).into()
+    }
+
+    /// If this is a runtime error whose value is a table, such as one built by `Error::table`,
+    /// returns that table.
+    pub fn as_error_table(&self) -> Option<Table<'gc>> {
+        match self {
+            Error::RuntimeError(RuntimeError(Value::Table(table))) => Some(*table),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a parser error caused by incomplete input, see `ParserError::is_incomplete`.
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            Error::ParserError(error) => error.is_incomplete(),
+            _ => false,
+        }
+    }
+
+    /// Whether a protected call such as `pcall` may catch this error.
+    ///
+    /// Errors raised by Lua code or by native functions are recoverable.  Errors that mean the
+    /// bytecode or the host's use of a thread is inconsistent are fatal, and always propagate out
+    /// to the host.
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self,
+            Error::ThreadError(ThreadError::ExpectedVariable(_)) | Error::BadThreadMode(_)
+        )
+    }
+
     pub fn to_static(self) -> StaticError {
         match self {
             Error::IoError(error) => StaticError::IoError(error.0),
@@ -158,6 +212,7 @@ impl<'gc> Error<'gc> {
             Error::ThreadError(error) => StaticError::ThreadError(error),
             Error::BadThreadMode(error) => StaticError::BadThreadMode(error),
             Error::TypeError(error) => StaticError::TypeError(error),
+            Error::ConversionError(error) => StaticError::ConversionError(error),
             Error::BinaryOperatorError(error) => StaticError::BinaryOperatorError(error),
             Error::RuntimeError(error) => {
                 let mut buf = Vec::new();
@@ -194,12 +249,23 @@ pub enum StaticError {
     ThreadError(ThreadError),
     BadThreadMode(BadThreadMode),
     TypeError(TypeError),
+    ConversionError(ConversionError),
     BinaryOperatorError(BinaryOperatorError),
     RuntimeError(String),
 }
 
 impl StdError for StaticError {}
 
+impl StaticError {
+    /// Whether this is a parser error caused by incomplete input, see `ParserError::is_incomplete`.
+    pub fn is_incomplete(&self) -> bool {
+        match self {
+            StaticError::ParserError(error) => error.is_incomplete(),
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for StaticError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -212,6 +278,7 @@ impl fmt::Display for StaticError {
             StaticError::ThreadError(error) => write!(fmt, "thread error: {}", error),
             StaticError::BadThreadMode(error) => write!(fmt, "bad thread mode: {}", error),
             StaticError::TypeError(error) => write!(fmt, "type error: {}", error),
+            StaticError::ConversionError(error) => write!(fmt, "conversion error: {}", error),
             StaticError::BinaryOperatorError(error) => write!(fmt, "operator error: {}", error),
             StaticError::RuntimeError(error) => write!(fmt, "runtime error: {}", error),
         }
@@ -751,6 +751,10 @@ where
 pub fn read_integer(s: &[u8]) -> Option<i64> {
     let (is_neg, s) = read_neg(s);
 
+    if s.is_empty() {
+        return None;
+    }
+
     let mut i: i64 = 0;
     for &c in s {
         let d = from_digit(c)? as i64;
@@ -771,7 +775,7 @@ pub fn read_integer(s: &[u8]) -> Option<i64> {
 pub fn read_hex_integer(s: &[u8]) -> Option<i64> {
     let (is_neg, s) = read_neg(s);
 
-    if s[0] != b'0' || (s[1] != b'x' && s[1] != b'X') {
+    if s.len() < 3 || s[0] != b'0' || (s[1] != b'x' && s[1] != b'X') {
         return None;
     }
 
@@ -794,6 +798,15 @@ pub fn read_hex_integer(s: &[u8]) -> Option<i64> {
 
 pub fn read_float(s: &[u8]) -> Option<f64> {
     let s = str::from_utf8(s).ok()?;
+    // Rust's float parsing also accepts the non-numeric spellings of infinity and NaN, which Lua
+    // does not.
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    if ["inf", "infinity", "nan"]
+        .iter()
+        .any(|special| unsigned.eq_ignore_ascii_case(special))
+    {
+        return None;
+    }
     str::parse(s).ok()
 }
 
@@ -942,7 +955,9 @@ fn is_newline(c: u8) -> bool {
     c == b'\n' || c == b'\r'
 }
 
-fn is_space(c: u8) -> bool {
+// Whitespace as C's `isspace` defines it, which unlike `u8::is_ascii_whitespace` includes the
+// vertical tab.
+pub fn is_space(c: u8) -> bool {
     c == b' ' || c == b'\t' || c == VERTICAL_TAB || c == FORM_FEED || is_newline(c)
 }
 
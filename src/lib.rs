@@ -3,6 +3,7 @@ mod callback;
 mod closure;
 mod compiler;
 mod constant;
+mod conversion;
 mod error;
 pub mod io;
 mod lexer;
@@ -18,23 +19,27 @@ mod value;
 
 mod stdlib;
 
-pub use callback::{Callback, CallbackResult, CallbackReturn, Continuation};
+pub use callback::{
+    Callback, CallbackBox, CallbackRegistry, CallbackResult, CallbackReturn, Continuation,
+};
 pub use closure::{
-    Closure, ClosureError, ClosureState, FunctionProto, UpValue, UpValueDescriptor, UpValueState,
+    Closure, ClosureError, ClosureState, FunctionProto, LocalVariable, UpValue, UpValueDescriptor,
+    UpValueState,
 };
 pub use compiler::{compile, compile_chunk, CompilerError};
 pub use constant::Constant;
+pub use conversion::{ConversionError, FromLua, IntoLua, TypedArgs};
 pub use error::{Error, RuntimeError, StaticError, TypeError};
 pub use lexer::{Lexer, LexerError, Token};
-pub use lua::{Lua, Root};
+pub use lua::{CoroutineErrorHook, Lua, Root};
 pub use opcode::OpCode;
 pub use parser::{parse_chunk, ParserError};
-pub use string::{InternedStringSet, String, StringError};
-pub use table::{InvalidTableKey, Table, TableState};
+pub use string::{InternedStringSet, Rope, String, StringError};
+pub use table::{InvalidTableKey, Table, TableState, TypeMetatables, WeakMode, WeakTableSet};
 pub use thread::{
     BadThreadMode, BinaryOperatorError, Thread, ThreadError, ThreadMode, ThreadSequence,
 };
 pub use types::{
     ConstantIndex16, ConstantIndex8, Opt254, PrototypeIndex, RegisterIndex, UpValueIndex, VarCount,
 };
-pub use value::{Function, Value};
+pub use value::{DivZeroPolicy, Function, Value};
@@ -1,17 +1,39 @@
-use gc_arena::{ArenaParameters, Collect, MutationContext};
-use gc_sequence::{make_sequencable_arena, Sequence};
+use std::io::Read;
+
+use gc_arena::{ArenaParameters, Collect, GcCell, GcPhase, MutationContext};
+use gc_sequence::{make_sequencable_arena, BoxSequence};
 
 use crate::{
-    stdlib::{load_base, load_coroutine, load_math, load_string},
-    InternedStringSet, Table, Thread,
+    compile,
+    stdlib::{load_base, load_coroutine, load_debug, load_math, load_string},
+    CallbackRegistry, Closure, Error, InternedStringSet, InvalidTableKey, String, Table, Thread,
+    TypeMetatables, Value, WeakTableSet,
 };
 
+/// Called with each error that kills a coroutine, see `Root::set_on_uncaught_coroutine_error`.
+pub type CoroutineErrorHook = Box<dyn for<'gc> Fn(&Error<'gc>)>;
+
+#[derive(Collect, Default)]
+#[collect(require_static)]
+pub(crate) struct Hooks {
+    pub(crate) on_uncaught_coroutine_error: Option<CoroutineErrorHook>,
+}
+
 #[derive(Collect, Clone, Copy)]
 #[collect(require_copy)]
 pub struct Root<'gc> {
     pub main_thread: Thread<'gc>,
     pub globals: Table<'gc>,
     pub interned_strings: InternedStringSet<'gc>,
+    /// A table for native code to keep values in which are not visible to Lua.
+    pub registry: Table<'gc>,
+    /// The standard library functions, by name.
+    pub callbacks: CallbackRegistry<'gc>,
+    /// The metatables for values other than tables, which the main thread and its coroutines use.
+    pub metatables: TypeMetatables<'gc>,
+    /// The tables made weak by `setmetatable`, which are pruned along with the interned strings.
+    pub weak_tables: WeakTableSet<'gc>,
+    pub(crate) hooks: GcCell<'gc, Hooks>,
 }
 
 impl<'gc> Root<'gc> {
@@ -20,15 +42,92 @@ impl<'gc> Root<'gc> {
             main_thread: Thread::new(mc, false),
             globals: Table::new(mc),
             interned_strings: InternedStringSet::new(mc),
+            registry: Table::new(mc),
+            callbacks: CallbackRegistry::new(mc),
+            metatables: TypeMetatables::new(mc),
+            weak_tables: WeakTableSet::new(mc),
+            hooks: GcCell::allocate(mc, Hooks::default()),
         };
+        root.main_thread.set_metatables(mc, root.metatables);
 
         load_base(mc, root, root.globals);
         load_coroutine(mc, root, root.globals);
+        load_debug(mc, root, root.globals);
         load_math(mc, root, root.globals);
         load_string(mc, root, root.globals);
+        root.callbacks.register_globals(mc, root.globals);
 
         root
     }
+
+    // Drops the entries of the interned strings and weak tables which the collector has released.
+    fn prune(&self, mc: MutationContext<'gc, '_>) {
+        self.interned_strings.prune(mc);
+        self.weak_tables.prune(mc);
+    }
+
+    /// Returns the canonical string with the given contents, so that every string interned with the
+    /// same bytes shares one allocation.
+    ///
+    /// The interned strings are held weakly, so a string which is no longer used is still collected,
+    /// and interning its contents again afterwards allocates a new one.
+    pub fn intern(&self, mc: MutationContext<'gc, '_>, bytes: &[u8]) -> String<'gc> {
+        self.interned_strings.new_string(mc, bytes)
+    }
+
+    /// Compiles a chunk into a closure whose `_ENV` is `env`, or the globals table if `env` is
+    /// `None`.
+    ///
+    /// Running the same source with different environments keeps the global variables each one
+    /// reads and assigns separate.
+    pub fn load<R: Read>(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        source: R,
+        env: Option<Table<'gc>>,
+    ) -> Result<Closure<'gc>, Error<'gc>> {
+        let proto = compile(mc, self.interned_strings, source)?;
+        Ok(Closure::new(mc, proto, Some(env.unwrap_or(self.globals)))?)
+    }
+
+    /// Sets a function to be called with every error raised inside a coroutine which kills it.
+    ///
+    /// `coroutine.resume` returns such errors to its caller as `false, err`, so they are easy to lose
+    /// when the caller does not check the status.  The hook sees each of them as it is returned,
+    /// whether or not the caller goes on to handle it.
+    pub fn set_on_uncaught_coroutine_error(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        hook: Option<CoroutineErrorHook>,
+    ) {
+        self.hooks.write(mc).on_uncaught_coroutine_error = hook;
+    }
+
+    /// Returns the value stored in the registry under `key`, first storing the result of `init`
+    /// there if there is none.
+    ///
+    /// Native functions can use this to keep state that is built once per interpreter, keyed by
+    /// something unique to them, such as a static string or the callback itself.
+    pub fn cache_or_init<K, F>(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        key: K,
+        init: F,
+    ) -> Result<Value<'gc>, InvalidTableKey>
+    where
+        K: Into<Value<'gc>>,
+        F: FnOnce(MutationContext<'gc, '_>) -> Value<'gc>,
+    {
+        let key = key.into();
+        match self.registry.get(key) {
+            Value::Nil => {
+                let value = init(mc);
+                self.registry.set(mc, key, value)?;
+                Ok(value)
+            }
+            value => Ok(value),
+        }
+    }
 }
 
 make_sequencable_arena!(pub lua_arena, Root);
@@ -57,7 +156,11 @@ impl Lua {
         let arena = self.0.as_mut().unwrap();
         let r = arena.mutate(move |mc, root| f(mc, *root));
         if arena.allocation_debt() > COLLECTOR_GRANULARITY {
+            let phase = arena.phase();
             arena.collect_debt();
+            if finished_marking(phase, arena.phase()) {
+                arena.mutate(|mc, root| root.prune(mc));
+            }
         }
         r
     }
@@ -67,22 +170,35 @@ impl Lua {
     pub fn sequence<F, R>(&mut self, f: F) -> R
     where
         R: 'static,
-        F: for<'gc> FnOnce(Root<'gc>) -> Box<dyn Sequence<'gc, Output = R> + 'gc>,
+        F: for<'gc> FnOnce(Root<'gc>) -> BoxSequence<'gc, R>,
     {
         let mut sequencer = self.0.take().unwrap().sequence(move |root| f(*root));
+        let mut marked = false;
         loop {
             match sequencer.step() {
-                Ok((arena, output)) => {
+                Ok((mut arena, output)) => {
+                    if marked {
+                        arena.mutate(|mc, root| root.prune(mc));
+                    }
                     self.0 = Some(arena);
                     return output;
                 }
                 Err(s) => {
                     sequencer = s;
                     if sequencer.allocation_debt() > COLLECTOR_GRANULARITY {
+                        let phase = sequencer.phase();
                         sequencer.collect_debt();
+                        marked |= finished_marking(phase, sequencer.phase());
                     }
                 }
             }
         }
     }
 }
+
+// Whether the collector finished marking in between the two phases, at which point the weakly held
+// interned strings and weak table entries that were found unreachable have been released.
+fn finished_marking(before: GcPhase, after: GcPhase) -> bool {
+    matches!(before, GcPhase::Wake | GcPhase::Propagate)
+        && matches!(after, GcPhase::Sweep | GcPhase::Sleep)
+}
@@ -254,6 +254,20 @@ pub enum ParserError {
 
 impl StdError for ParserError {}
 
+impl ParserError {
+    /// Whether the source ended where more input could have completed it, such as inside a block
+    /// with no `end` or an unfinished long string, as opposed to containing an actual syntax error.
+    ///
+    /// A REPL can use this to decide whether to read another line rather than report the error.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(
+            self,
+            ParserError::EndOfStream { .. }
+                | ParserError::LexerError(LexerError::UnfinishedLongString)
+        )
+    }
+}
+
 impl fmt::Display for ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let write_expected = |f: &mut fmt::Formatter, expected: &Option<String>| {
@@ -314,10 +328,12 @@ where
 {
     fn parse_chunk(&mut self) -> Result<Chunk<S>, ParserError> {
         let block = self.parse_block()?;
-        if self.look_ahead(0)? != None {
-            Err(ParserError::EndOfStream { expected: None })
-        } else {
-            Ok(Chunk { block })
+        match self.look_ahead(0)? {
+            None => Ok(Chunk { block }),
+            Some(token) => Err(ParserError::Unexpected {
+                unexpected: format!("{:?}", token),
+                expected: Some("end of input".to_owned()),
+            }),
         }
     }
 
@@ -1045,7 +1061,7 @@ fn binary_priority(operator: BinaryOperator) -> (u8, u8) {
         BinaryOperator::ShiftLeft => (7, 7),
         BinaryOperator::ShiftRight => (7, 7),
         BinaryOperator::Concat => (9, 8),
-        BinaryOperator::NotEqual => (10, 10),
+        BinaryOperator::NotEqual => (3, 3),
         BinaryOperator::Equal => (3, 3),
         BinaryOperator::LessThan => (3, 3),
         BinaryOperator::LessEqual => (3, 3),
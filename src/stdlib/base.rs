@@ -4,7 +4,8 @@ use gc_arena::MutationContext;
 use gc_sequence as sequence;
 
 use crate::{
-    Callback, CallbackResult, Continuation, Root, RuntimeError, String, Table, TypeError, Value,
+    Callback, CallbackResult, Continuation, Error, Root, RuntimeError, String, Table, TypeError,
+    Value,
 };
 
 pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<'gc>) {
@@ -30,6 +31,20 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
         mc,
         String::new_static(b"error"),
         Callback::new_immediate(mc, |args| {
+            // Functions carry no source positions, so there is nothing for the level to add to
+            // the message, but it is still checked as Lua does.
+            match args.get(1).cloned().unwrap_or(Value::Nil) {
+                Value::Nil => {}
+                level => {
+                    if level.to_integer().is_none() {
+                        return Err(TypeError {
+                            expected: "integer",
+                            found: level.type_name(),
+                        }
+                        .into());
+                    }
+                }
+            }
             let err = args.get(0).cloned().unwrap_or(Value::Nil);
             Err(RuntimeError(err).into())
         }),
@@ -59,9 +74,10 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
         mc,
         String::new_static(b"pcall"),
         Callback::new_immediate_with(mc, root.interned_strings, |interned_strings, mut args| {
-            let function = match args.get(0).cloned().unwrap_or(Value::Nil) {
-                Value::Function(function) => function,
-                value => {
+            let value = args.get(0).cloned().unwrap_or(Value::Nil);
+            let function = match value.as_callable() {
+                Some(function) => function,
+                None => {
                     return Err(TypeError {
                         expected: "function",
                         found: value.type_name(),
@@ -70,7 +86,10 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
                 }
             };
 
-            args.remove(0);
+            // A callable table stays in the arguments, to be passed to its `__call` metamethod.
+            if let Value::Function(_) = value {
+                args.remove(0);
+            }
             Ok(CallbackResult::TailCall {
                 function,
                 args,
@@ -85,6 +104,7 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
                                         res.insert(0, Value::Boolean(true));
                                         res
                                     }
+                                    Err(err) if !err.is_recoverable() => return Err(err),
                                     Err(err) => vec![
                                         Value::Boolean(false),
                                         err.to_value(mc, interned_strings),
@@ -116,6 +136,55 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
     )
     .unwrap();
 
+    env.set(
+        mc,
+        String::new_static(b"rawequal"),
+        Callback::new_immediate(mc, |args| {
+            if args.len() < 2 {
+                return Err(RuntimeError(Value::String(String::new_static(
+                    b"Missing argument to rawequal",
+                )))
+                .into());
+            }
+            Ok(CallbackResult::Return(vec![Value::Boolean(
+                args[0].raw_eq(args[1]),
+            )]))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"tostring"),
+        Callback::new_sequence(mc, |args| {
+            Ok(sequence::from_fn_with(args, |mc, args| {
+                match args.first() {
+                    Some(&v) => match v.tostring_metamethod() {
+                        Some(function) => Ok(CallbackResult::TailCall {
+                            function,
+                            args: vec![v],
+                            continuation: Continuation::new_sequence(|res| {
+                                Ok(sequence::from_fn_with(res, |mc, res| {
+                                    Ok(CallbackResult::Return(vec![Value::String(
+                                        tostring_result(mc, res)?,
+                                    )]))
+                                }))
+                            }),
+                        }),
+                        None => Ok(CallbackResult::Return(vec![Value::String(
+                            v.to_display_string(mc),
+                        )])),
+                    },
+                    None => Err(RuntimeError(Value::String(String::new_static(
+                        b"Missing argument to tostring",
+                    )))
+                    .into()),
+                }
+            }))
+        }),
+    )
+    .unwrap();
+
     env.set(
         mc,
         String::new_static(b"select"),
@@ -135,4 +204,132 @@ pub fn load_base<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<
         }),
     )
     .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"getmetatable"),
+        Callback::new_immediate_with(mc, root.metatables, |metatables, args| {
+            let metatable = match args.first() {
+                Some(v) => v.metatable(*metatables),
+                None => {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"Missing argument to getmetatable",
+                    )))
+                    .into());
+                }
+            };
+            Ok(CallbackResult::Return(vec![match metatable {
+                Some(mt) => match mt.get(String::new_static(b"__metatable")) {
+                    Value::Nil => Value::Table(mt),
+                    protected => protected,
+                },
+                None => Value::Nil,
+            }]))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"setmetatable"),
+        Callback::new_sequence_with(mc, root.weak_tables, |&weak_tables, args| {
+            Ok(sequence::from_fn_with(
+                (weak_tables, args),
+                |mc, (weak_tables, args)| {
+                    let bad_argument = || {
+                        RuntimeError(Value::String(String::new_static(
+                            b"Bad argument to setmetatable",
+                        )))
+                        .into()
+                    };
+                    let table = match args.first() {
+                        Some(&Value::Table(t)) => t,
+                        _ => return Err(bad_argument()),
+                    };
+                    let metatable = match args.get(1) {
+                        Some(&Value::Table(mt)) => Some(mt),
+                        Some(Value::Nil) => None,
+                        _ => return Err(bad_argument()),
+                    };
+                    if let Some(mt) = table.metatable() {
+                        if mt.get(String::new_static(b"__metatable")) != Value::Nil {
+                            return Err(RuntimeError(Value::String(String::new_static(
+                                b"cannot change a protected metatable",
+                            )))
+                            .into());
+                        }
+                    }
+                    table.set_metatable(mc, metatable);
+                    if table.weak_mode().is_some() {
+                        weak_tables.insert(mc, table);
+                    }
+                    Ok(CallbackResult::Return(vec![Value::Table(table)]))
+                },
+            ))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawget"),
+        Callback::new_immediate(mc, |args| match args.first() {
+            Some(&Value::Table(t)) => Ok(CallbackResult::Return(vec![
+                t.raw_get(args.get(1).cloned().unwrap_or(Value::Nil))
+            ])),
+            _ => Err(
+                RuntimeError(Value::String(String::new_static(b"Bad argument to rawget"))).into(),
+            ),
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawset"),
+        Callback::new_sequence(mc, |args| {
+            Ok(sequence::from_fn_with(args, |mc, args| {
+                match args.first() {
+                    Some(&Value::Table(t)) if args.len() >= 3 => {
+                        t.raw_set(mc, args[1], args[2])?;
+                        Ok(CallbackResult::Return(vec![Value::Table(t)]))
+                    }
+                    _ => Err(RuntimeError(Value::String(String::new_static(
+                        b"Bad argument to rawset",
+                    )))
+                    .into()),
+                }
+            }))
+        }),
+    )
+    .unwrap();
+
+    env.set(
+        mc,
+        String::new_static(b"rawlen"),
+        Callback::new_immediate(mc, |args| match args.first() {
+            Some(&Value::Table(t)) => Ok(CallbackResult::Return(vec![Value::Integer(t.raw_len())])),
+            Some(&Value::String(s)) => Ok(CallbackResult::Return(vec![Value::Integer(s.len())])),
+            _ => Err(
+                RuntimeError(Value::String(String::new_static(b"Bad argument to rawlen"))).into(),
+            ),
+        }),
+    )
+    .unwrap();
+}
+
+// Checks the results of a `__tostring` metamethod, which must be a string, or a number to be
+// converted to one.
+pub(super) fn tostring_result<'gc>(
+    mc: MutationContext<'gc, '_>,
+    res: Result<Vec<Value<'gc>>, Error<'gc>>,
+) -> Result<String<'gc>, Error<'gc>> {
+    match res?.first() {
+        Some(&Value::String(s)) => Ok(s),
+        Some(&v @ (Value::Integer(_) | Value::Number(_))) => Ok(v.to_display_string(mc)),
+        _ => Err(RuntimeError(Value::String(String::new_static(
+            b"'__tostring' must return a string",
+        )))
+        .into()),
+    }
 }
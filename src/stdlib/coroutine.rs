@@ -1,4 +1,4 @@
-use gc_arena::MutationContext;
+use gc_arena::{GcCell, MutationContext};
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
 
 use crate::{
@@ -9,28 +9,44 @@ use crate::{
 pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: Table<'gc>) {
     let coroutine = Table::new(mc);
 
+    // Callbacks are not told which thread they are running on, so the coroutine library keeps its
+    // own stack of the coroutines currently being resumed, innermost last.  While it is empty,
+    // the main thread is running.
+    let active: GcCell<'gc, Vec<Thread<'gc>>> = GcCell::allocate(mc, Vec::new());
+
     coroutine
         .set(
             mc,
             String::new_static(b"create"),
-            Callback::new_sequence(mc, |args| {
-                let function = match args.get(0).cloned().unwrap_or(Value::Nil) {
-                    Value::Function(function) => function,
-                    value => {
-                        return Err(TypeError {
-                            expected: "function",
-                            found: value.type_name(),
+            Callback::new_sequence_with(
+                mc,
+                (root.main_thread, active),
+                |&(main_thread, active), args| {
+                    let function = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                        Value::Function(function) => function,
+                        value => {
+                            return Err(TypeError {
+                                expected: "function",
+                                found: value.type_name(),
+                            }
+                            .into());
                         }
-                        .into());
-                    }
-                };
+                    };
 
-                Ok(sequence::from_fn_with(function, |mc, function| {
-                    let thread = Thread::new(mc, true);
-                    thread.start_suspended(mc, function).unwrap();
-                    Ok(CallbackResult::Return(vec![Value::Thread(thread)]))
-                }))
-            }),
+                    Ok(sequence::from_fn_with(
+                        (main_thread, active, function),
+                        |mc, (main_thread, active, function)| {
+                            let current = active.read().last().copied().unwrap_or(main_thread);
+                            let thread = Thread::new(mc, true);
+                            thread.set_integer_div_zero(mc, current.integer_div_zero());
+                            thread.set_max_meta_depth(mc, current.max_meta_depth());
+                            thread.set_metatables(mc, current.metatables());
+                            thread.start_suspended(mc, function).unwrap();
+                            Ok(CallbackResult::Return(vec![Value::Thread(thread)]))
+                        },
+                    ))
+                },
+            ),
         )
         .unwrap();
 
@@ -38,33 +54,52 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
         .set(
             mc,
             String::new_static(b"resume"),
-            Callback::new_sequence_with(mc, root.interned_strings, |interned_strings, mut args| {
-                let thread = match args.get(0).cloned().unwrap_or(Value::Nil) {
-                    Value::Thread(closure) => closure,
-                    value => {
-                        return Err(TypeError {
-                            expected: "thread",
-                            found: value.type_name(),
+            Callback::new_sequence_with(
+                mc,
+                (root.interned_strings, active, root.hooks),
+                |&(interned_strings, active, hooks), mut args| {
+                    let thread = match args.get(0).cloned().unwrap_or(Value::Nil) {
+                        Value::Thread(closure) => closure,
+                        value => {
+                            return Err(TypeError {
+                                expected: "thread",
+                                found: value.type_name(),
+                            }
+                            .into());
                         }
-                        .into());
-                    }
-                };
+                    };
 
-                args.remove(0);
-                Ok(
-                    sequence::from_fn_with((thread, args), |mc, (thread, args)| {
-                        if let Ok(()) = thread.resume(mc, &args) {
-                            Ok(ThreadSequence(thread))
-                        } else {
-                            Err(RuntimeError(Value::String(String::new_static(
-                                b"cannot resume thread",
-                            )))
-                            .into())
-                        }
-                    })
+                    args.remove(0);
+                    Ok(sequence::from_fn_with(
+                        (thread, args, active, hooks),
+                        |mc, (thread, args, active, hooks)| {
+                            if let Ok(()) = thread.resume(mc, &args) {
+                                active.write(mc).push(thread);
+                                Ok(ThreadSequence(thread).then_with(
+                                    (active, hooks),
+                                    |mc, (active, hooks), res| {
+                                        active.write(mc).pop();
+                                        if let Err(err) = &res {
+                                            if let Some(hook) =
+                                                &hooks.read().on_uncaught_coroutine_error
+                                            {
+                                                hook(err);
+                                            }
+                                        }
+                                        res
+                                    },
+                                ))
+                            } else {
+                                Err(RuntimeError(Value::String(String::new_static(
+                                    b"cannot resume thread",
+                                )))
+                                .into())
+                            }
+                        },
+                    )
                     .flatten_ok()
                     .then_with(
-                        *interned_strings,
+                        interned_strings,
                         |mc, interned_strings, res| {
                             Ok(CallbackResult::Return(match res {
                                 Ok(mut res) => {
@@ -76,9 +111,9 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
                                 }
                             }))
                         },
-                    ),
-                )
-            }),
+                    ))
+                },
+            ),
         )
         .unwrap();
 
@@ -119,6 +154,32 @@ pub fn load_coroutine<'gc>(mc: MutationContext<'gc, '_>, root: Root<'gc>, env: T
         )
         .unwrap();
 
+    coroutine
+        .set(
+            mc,
+            String::new_static(b"running"),
+            Callback::new_immediate_with(mc, (root.main_thread, active), |&(main, active), _| {
+                Ok(CallbackResult::Return(match active.read().last() {
+                    Some(&thread) => vec![Value::Thread(thread), Value::Boolean(false)],
+                    None => vec![Value::Thread(main), Value::Boolean(true)],
+                }))
+            }),
+        )
+        .unwrap();
+
+    // Every coroutine is created able to yield, so only the main thread is not yieldable.
+    coroutine
+        .set(
+            mc,
+            String::new_static(b"isyieldable"),
+            Callback::new_immediate_with(mc, active, |active, _| {
+                Ok(CallbackResult::Return(vec![Value::Boolean(
+                    !active.read().is_empty(),
+                )]))
+            }),
+        )
+        .unwrap();
+
     env.set(mc, String::new_static(b"coroutine"), coroutine)
         .unwrap();
 }
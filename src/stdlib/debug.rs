@@ -0,0 +1,66 @@
+use gc_arena::MutationContext;
+use gc_sequence as sequence;
+
+use crate::{Callback, CallbackResult, Error, Root, RuntimeError, String, Table, Thread, Value};
+
+pub fn load_debug<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc>) {
+    let debug = Table::new(mc);
+
+    debug
+        .set(
+            mc,
+            String::new_static(b"getlocal"),
+            Callback::new_sequence(mc, |args| {
+                Ok(sequence::from_fn_with(args, |_, args| {
+                    let (thread, level, n) = local_args(&args, b"Bad argument to getlocal")?;
+                    Ok(CallbackResult::Return(match thread.get_local(level, n) {
+                        Some((name, value)) => vec![Value::String(name), value],
+                        None => vec![Value::Nil],
+                    }))
+                }))
+            }),
+        )
+        .unwrap();
+
+    debug
+        .set(
+            mc,
+            String::new_static(b"setlocal"),
+            Callback::new_sequence(mc, |args| {
+                Ok(sequence::from_fn_with(args, |mc, args| {
+                    let (thread, level, n) = local_args(&args, b"Bad argument to setlocal")?;
+                    let value = args.get(3).cloned().unwrap_or(Value::Nil);
+                    Ok(CallbackResult::Return(
+                        match thread.set_local(mc, level, n, value) {
+                            Some(name) => vec![Value::String(name)],
+                            None => vec![Value::Nil],
+                        },
+                    ))
+                }))
+            }),
+        )
+        .unwrap();
+
+    env.set(mc, String::new_static(b"debug"), debug).unwrap();
+}
+
+// Callbacks are not told which thread they are running on, so unlike PUC-Rio Lua the thread
+// argument is required.  It can be obtained with `coroutine.running()`.
+fn local_args<'gc>(
+    args: &[Value<'gc>],
+    error: &'static [u8],
+) -> Result<(Thread<'gc>, usize, usize), Error<'gc>> {
+    let bad_argument = || Error::from(RuntimeError(Value::String(String::new_static(error))));
+    let thread = match args.first() {
+        Some(Value::Thread(thread)) => *thread,
+        _ => return Err(bad_argument()),
+    };
+    let index = |i: usize| {
+        args.get(i)
+            .and_then(|v| v.to_integer())
+            .filter(|&i| i > 0)
+            .map(|i| i as usize)
+            .ok_or_else(bad_argument)
+    };
+    Ok((thread, index(1)?, index(2)?))
+}
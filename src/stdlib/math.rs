@@ -1,15 +1,16 @@
-use gc_arena::MutationContext;
+use gc_arena::{GcCell, MutationContext, StaticCollect};
+use gc_sequence as sequence;
 
-use crate::{Callback, CallbackResult, Root, RuntimeError, String, Table, Value};
+use crate::{Callback, CallbackResult, Error, Root, RuntimeError, String, Table, Value};
 
-use rand::{FromEntropy, Rng, SeedableRng};
+use rand::{distributions::Uniform, FromEntropy, Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256StarStar;
-use std::{cell::RefCell, ops::DerefMut, rc::Rc};
 
 pub fn load_math<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc>) {
     let math = Table::new(mc);
-    let seeded_rng: Rc<RefCell<Xoshiro256StarStar>> =
-        Rc::new(RefCell::new(Xoshiro256StarStar::from_entropy()));
+    // The generator is owned by this Lua state rather than by the thread, so states seeded the
+    // same way with `math.randomseed` produce the same sequence.
+    let rng = GcCell::allocate(mc, StaticCollect(Xoshiro256StarStar::from_entropy()));
 
     math.set(
         mc,
@@ -395,57 +396,64 @@ pub fn load_math<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc
     )
     .unwrap();
 
-    // TODO: Random and Randomseed
-    let random_rng = seeded_rng.clone();
     math.set(
         mc,
         String::new_static(b"random"),
-        Callback::new_immediate(mc, move |args| {
-            let rng = &random_rng;
-            match (
-                args.get(0).cloned().unwrap_or(Value::Nil),
-                args.get(1).cloned().unwrap_or(Value::Nil),
-            ) {
-                (Value::Nil, Value::Nil) => Ok(CallbackResult::Return(vec![Value::Number(
-                    rng.borrow_mut().gen::<f64>(),
-                )])),
-                (a, b) => {
-                    if let (Some(first), Value::Nil) = (a.to_integer(), b) {
-                        Ok(CallbackResult::Return(vec![Value::Integer(
-                            rng.borrow_mut().gen_range(1, first + 1),
-                        )]))
-                    } else if let (Some(first), Some(second)) = (a.to_integer(), b.to_integer()) {
-                        Ok(CallbackResult::Return(vec![Value::Integer(
-                            rng.borrow_mut().gen_range(first, second + 1),
-                        )]))
-                    } else {
-                        Err(RuntimeError(Value::String(String::new_static(
-                            b"Bad argument to random",
-                        )))
-                        .into())
+        Callback::new_sequence_with(mc, rng, |&rng, args| {
+            Ok(sequence::from_fn_with((rng, args), |mc, (rng, args)| {
+                let bad_argument = || {
+                    Error::from(RuntimeError(Value::String(String::new_static(
+                        b"Bad argument to random",
+                    ))))
+                };
+                let range = match (
+                    args.get(0).cloned().unwrap_or(Value::Nil),
+                    args.get(1).cloned().unwrap_or(Value::Nil),
+                ) {
+                    (Value::Nil, Value::Nil) => {
+                        return Ok(CallbackResult::Return(vec![Value::Number(
+                            rng.write(mc).0.gen::<f64>(),
+                        )]));
                     }
+                    (a, Value::Nil) => (1, a.to_integer().ok_or_else(bad_argument)?),
+                    (a, b) => (
+                        a.to_integer().ok_or_else(bad_argument)?,
+                        b.to_integer().ok_or_else(bad_argument)?,
+                    ),
+                };
+                if range.0 > range.1 {
+                    return Err(RuntimeError(Value::String(String::new_static(
+                        b"Bad argument to random, interval is empty",
+                    )))
+                    .into());
                 }
-            }
+                Ok(CallbackResult::Return(vec![Value::Integer(
+                    rng.write(mc)
+                        .0
+                        .sample(Uniform::new_inclusive(range.0, range.1)),
+                )]))
+            }))
         }),
     )
     .unwrap();
 
-    let randomseed_rng = seeded_rng.clone();
     math.set(
         mc,
         String::new_static(b"randomseed"),
-        Callback::new_immediate(mc, move |args| {
-            let rng = &randomseed_rng;
-            match args.get(0).cloned().unwrap_or(Value::Nil).to_number() {
-                Some(f) => {
-                    *(rng.borrow_mut().deref_mut()) = Xoshiro256StarStar::seed_from_u64(f as u64);
-                    Ok(CallbackResult::Return(vec![]))
-                }
-                _ => Err(RuntimeError(Value::String(String::new_static(
-                    b"Bad argument to randomseed",
-                )))
-                .into()),
-            }
+        Callback::new_sequence_with(mc, rng, |&rng, args| {
+            Ok(sequence::from_fn_with(
+                (rng, args),
+                |mc, (rng, args)| match args.get(0).cloned().unwrap_or(Value::Nil).to_number() {
+                    Some(f) => {
+                        rng.write(mc).0 = Xoshiro256StarStar::seed_from_u64(f as u64);
+                        Ok(CallbackResult::Return(vec![]))
+                    }
+                    _ => Err(RuntimeError(Value::String(String::new_static(
+                        b"Bad argument to randomseed",
+                    )))
+                    .into()),
+                },
+            ))
         }),
     )
     .unwrap();
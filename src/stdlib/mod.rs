@@ -1,9 +1,12 @@
 mod base;
 mod coroutine;
+mod debug;
 mod math;
+mod pattern;
 mod string;
 
 pub use base::load_base;
 pub use coroutine::load_coroutine;
+pub use debug::load_debug;
 pub use math::load_math;
 pub use string::load_string;
@@ -0,0 +1,381 @@
+//! A matcher for Lua patterns, the small regular expression language used by `string.find` and
+//! `string.match`.  This follows the backtracking matcher in PUC-Rio Lua's `lstrlib.c`, and like
+//! it operates on bytes, so character classes only ever match ASCII characters.
+
+const MAX_CAPTURES: usize = 32;
+const MAX_RECURSION: usize = 200;
+const SPECIALS: &[u8] = b"^$*+?.([%-";
+
+/// A capture of a successful match, as positions in the subject.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Capture {
+    /// The byte range `start..end` of a parenthesized capture.
+    Range(usize, usize),
+    /// The position captured by an empty `()` capture.
+    Position(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub captures: Vec<Capture>,
+}
+
+/// An error in the pattern itself, such as an unbalanced bracket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PatternError(pub &'static str);
+
+/// Returns whether `pattern` contains no special characters, so that it can only match itself.
+pub fn is_plain(pattern: &[u8]) -> bool {
+    !pattern.iter().any(|b| SPECIALS.contains(b))
+}
+
+/// Finds the first match of `pattern` in `src` that starts at or after the byte offset `init`.
+pub fn find(src: &[u8], pattern: &[u8], init: usize) -> Result<Option<Match>, PatternError> {
+    let anchor = pattern.first() == Some(&b'^');
+    let mut state = MatchState {
+        src,
+        pattern,
+        depth: 0,
+        captures: Vec::new(),
+    };
+
+    let mut start = init;
+    loop {
+        state.captures.clear();
+        if let Some(end) = state.do_match(start, if anchor { 1 } else { 0 })? {
+            let captures = state
+                .captures
+                .iter()
+                .map(|&(start, len)| match len {
+                    CaptureLen::Len(len) => Ok(Capture::Range(start, start + len)),
+                    CaptureLen::Position => Ok(Capture::Position(start)),
+                    CaptureLen::Unfinished => Err(PatternError("unfinished capture")),
+                })
+                .collect::<Result<_, _>>()?;
+            return Ok(Some(Match {
+                start,
+                end,
+                captures,
+            }));
+        }
+        start += 1;
+        if anchor || start > src.len() {
+            return Ok(None);
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum CaptureLen {
+    Unfinished,
+    Position,
+    Len(usize),
+}
+
+struct MatchState<'a> {
+    src: &'a [u8],
+    pattern: &'a [u8],
+    depth: usize,
+    captures: Vec<(usize, CaptureLen)>,
+}
+
+impl<'a> MatchState<'a> {
+    // Matches the pattern from `p` against the subject from `s`, returning the end of the match.
+    fn do_match(&mut self, s: usize, p: usize) -> Result<Option<usize>, PatternError> {
+        if self.depth == MAX_RECURSION {
+            return Err(PatternError("pattern too complex"));
+        }
+        self.depth += 1;
+        let res = self.match_here(s, p);
+        self.depth -= 1;
+        res
+    }
+
+    fn match_here(&mut self, mut s: usize, mut p: usize) -> Result<Option<usize>, PatternError> {
+        let (src, pattern) = (self.src, self.pattern);
+        loop {
+            if p == pattern.len() {
+                return Ok(Some(s));
+            }
+
+            match (pattern[p], pattern.get(p + 1).copied()) {
+                (b'(', Some(b')')) => return self.start_capture(s, p + 2, CaptureLen::Position),
+                (b'(', _) => return self.start_capture(s, p + 1, CaptureLen::Unfinished),
+                (b')', _) => return self.end_capture(s, p + 1),
+                (b'$', None) => return Ok(if s == src.len() { Some(s) } else { None }),
+                (b'%', Some(b'b')) => match self.match_balance(s, p + 2)? {
+                    Some(end) => {
+                        s = end;
+                        p += 4;
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+                (b'%', Some(b'f')) => {
+                    p += 2;
+                    if pattern.get(p) != Some(&b'[') {
+                        return Err(PatternError("missing '[' after '%f' in pattern"));
+                    }
+                    let ep = self.class_end(p)?;
+                    let previous = if s == 0 { 0 } else { src[s - 1] };
+                    let current = src.get(s).copied().unwrap_or(0);
+                    if !self.match_bracket_class(previous, p, ep - 1)
+                        && self.match_bracket_class(current, p, ep - 1)
+                    {
+                        p = ep;
+                        continue;
+                    }
+                    return Ok(None);
+                }
+                (b'%', Some(l)) if l.is_ascii_digit() => match self.match_capture(s, l)? {
+                    Some(end) => {
+                        s = end;
+                        p += 2;
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+                _ => {}
+            }
+
+            let ep = self.class_end(p)?;
+            let matches = s < src.len() && self.single_match(src[s], p, ep);
+            match pattern.get(ep) {
+                Some(b'?') => {
+                    if matches {
+                        if let Some(end) = self.do_match(s + 1, ep + 1)? {
+                            return Ok(Some(end));
+                        }
+                    }
+                    p = ep + 1;
+                }
+                Some(b'+') => {
+                    return if matches {
+                        self.max_expand(s + 1, p, ep)
+                    } else {
+                        Ok(None)
+                    };
+                }
+                Some(b'*') => return self.max_expand(s, p, ep),
+                Some(b'-') => return self.min_expand(s, p, ep),
+                _ => {
+                    if !matches {
+                        return Ok(None);
+                    }
+                    s += 1;
+                    p = ep;
+                }
+            }
+        }
+    }
+
+    // Returns the end of the single character class starting at `p`.
+    fn class_end(&self, mut p: usize) -> Result<usize, PatternError> {
+        let pattern = self.pattern;
+        let c = pattern[p];
+        p += 1;
+        match c {
+            b'%' => {
+                if p == pattern.len() {
+                    return Err(PatternError("malformed pattern (ends with '%')"));
+                }
+                Ok(p + 1)
+            }
+            b'[' => {
+                if pattern.get(p) == Some(&b'^') {
+                    p += 1;
+                }
+                // The first character of a set is never its end, so `[]]` matches a ']'.
+                loop {
+                    if p >= pattern.len() {
+                        return Err(PatternError("malformed pattern (missing ']')"));
+                    }
+                    let c = pattern[p];
+                    p += 1;
+                    if c == b'%' && p < pattern.len() {
+                        p += 1;
+                    }
+                    if pattern.get(p) == Some(&b']') {
+                        return Ok(p + 1);
+                    }
+                }
+            }
+            _ => Ok(p),
+        }
+    }
+
+    fn single_match(&self, c: u8, p: usize, ep: usize) -> bool {
+        match self.pattern[p] {
+            b'.' => true,
+            b'%' => match_class(c, self.pattern[p + 1]),
+            b'[' => self.match_bracket_class(c, p, ep - 1),
+            pc => pc == c,
+        }
+    }
+
+    // Matches `c` against the set from the '[' at `p` to the ']' at `end`.
+    fn match_bracket_class(&self, c: u8, mut p: usize, end: usize) -> bool {
+        let pattern = self.pattern;
+        let mut found = true;
+        p += 1;
+        if pattern[p] == b'^' {
+            found = false;
+            p += 1;
+        }
+        while p < end {
+            if pattern[p] == b'%' {
+                p += 1;
+                if match_class(c, pattern[p]) {
+                    return found;
+                }
+                p += 1;
+            } else if pattern[p + 1] == b'-' && p + 2 < end {
+                if pattern[p] <= c && c <= pattern[p + 2] {
+                    return found;
+                }
+                p += 3;
+            } else {
+                if pattern[p] == c {
+                    return found;
+                }
+                p += 1;
+            }
+        }
+        !found
+    }
+
+    // Matches the class at `p` as many times as possible, then backs off until the rest of the
+    // pattern after `ep` matches.
+    fn max_expand(&mut self, s: usize, p: usize, ep: usize) -> Result<Option<usize>, PatternError> {
+        let mut count = 0;
+        while s + count < self.src.len() && self.single_match(self.src[s + count], p, ep) {
+            count += 1;
+        }
+        loop {
+            if let Some(end) = self.do_match(s + count, ep + 1)? {
+                return Ok(Some(end));
+            }
+            if count == 0 {
+                return Ok(None);
+            }
+            count -= 1;
+        }
+    }
+
+    // Matches the class at `p` as few times as possible for the rest of the pattern to match.
+    fn min_expand(
+        &mut self,
+        mut s: usize,
+        p: usize,
+        ep: usize,
+    ) -> Result<Option<usize>, PatternError> {
+        loop {
+            if let Some(end) = self.do_match(s, ep + 1)? {
+                return Ok(Some(end));
+            }
+            if s < self.src.len() && self.single_match(self.src[s], p, ep) {
+                s += 1;
+            } else {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn start_capture(
+        &mut self,
+        s: usize,
+        p: usize,
+        len: CaptureLen,
+    ) -> Result<Option<usize>, PatternError> {
+        if self.captures.len() == MAX_CAPTURES {
+            return Err(PatternError("too many captures"));
+        }
+        self.captures.push((s, len));
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.captures.pop();
+        }
+        Ok(res)
+    }
+
+    fn end_capture(&mut self, s: usize, p: usize) -> Result<Option<usize>, PatternError> {
+        let i = self
+            .captures
+            .iter()
+            .rposition(|(_, len)| matches!(len, CaptureLen::Unfinished))
+            .ok_or(PatternError("invalid pattern capture"))?;
+        self.captures[i].1 = CaptureLen::Len(s - self.captures[i].0);
+        let res = self.do_match(s, p)?;
+        if res.is_none() {
+            self.captures[i].1 = CaptureLen::Unfinished;
+        }
+        Ok(res)
+    }
+
+    // Matches a `%bxy` balanced run, with the 'x' at `p`.
+    fn match_balance(&self, s: usize, p: usize) -> Result<Option<usize>, PatternError> {
+        let (src, pattern) = (self.src, self.pattern);
+        if p + 1 >= pattern.len() {
+            return Err(PatternError(
+                "malformed pattern (missing arguments to '%b')",
+            ));
+        }
+        let (open, close) = (pattern[p], pattern[p + 1]);
+        if src.get(s) != Some(&open) {
+            return Ok(None);
+        }
+        let mut depth = 1;
+        for (i, &c) in src.iter().enumerate().skip(s + 1) {
+            if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(Some(i + 1));
+                }
+            } else if c == open {
+                depth += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    // Matches a `%1`-style back reference to the text of an earlier capture.
+    fn match_capture(&self, s: usize, l: u8) -> Result<Option<usize>, PatternError> {
+        let (start, len) = match l
+            .checked_sub(b'1')
+            .and_then(|i| self.captures.get(i as usize))
+        {
+            Some(&(start, CaptureLen::Len(len))) => (start, len),
+            _ => return Err(PatternError("invalid capture index")),
+        };
+        let captured = &self.src[start..start + len];
+        Ok(if self.src[s..].starts_with(captured) {
+            Some(s + len)
+        } else {
+            None
+        })
+    }
+}
+
+fn match_class(c: u8, class: u8) -> bool {
+    let matches = match class.to_ascii_lowercase() {
+        b'a' => c.is_ascii_alphabetic(),
+        b'c' => c.is_ascii_control(),
+        b'd' => c.is_ascii_digit(),
+        b'g' => c.is_ascii_graphic(),
+        b'l' => c.is_ascii_lowercase(),
+        b'p' => c.is_ascii_punctuation(),
+        // Unlike `is_ascii_whitespace`, C's `isspace` includes the vertical tab.
+        b's' => c.is_ascii_whitespace() || c == b'\x0b',
+        b'u' => c.is_ascii_uppercase(),
+        b'w' => c.is_ascii_alphanumeric(),
+        b'x' => c.is_ascii_hexdigit(),
+        _ => return class == c,
+    };
+    if class.is_ascii_uppercase() {
+        !matches
+    } else {
+        matches
+    }
+}
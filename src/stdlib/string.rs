@@ -1,7 +1,15 @@
 use gc_arena::MutationContext;
 use gc_sequence as sequence;
 
-use crate::{Callback, CallbackResult, Root, RuntimeError, String, Table, Value};
+use super::base::tostring_result;
+use super::pattern::{self, Capture, Match, PatternError};
+use crate::{
+    Callback, CallbackResult, Continuation, Error, Root, RuntimeError, String, Table, Value,
+};
+
+// The longest string `string.rep` will build, so that a large count is a Lua error rather than an
+// allocation failure.
+const MAX_STRING_LEN: usize = i32::MAX as usize;
 
 pub fn load_string<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'gc>) {
     let string = Table::new(mc);
@@ -12,17 +20,390 @@ pub fn load_string<'gc>(mc: MutationContext<'gc, '_>, _: Root<'gc>, env: Table<'
             String::new_static(b"len"),
             Callback::new_sequence(mc, |args| {
                 Ok(sequence::from_fn_with(args, |mc, args| {
-                    match args.get(0).cloned().unwrap_or(Value::Nil).to_string(mc) {
-                        Some(s) => Ok(CallbackResult::Return(vec![Value::Integer(s.len())])),
-                        None => Err(RuntimeError(Value::String(String::new_static(
-                            b"Bad argument to len",
+                    let s = string_arg(mc, &args, b"Bad argument to len")?;
+                    Ok(CallbackResult::Return(vec![Value::Integer(s.len())]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"sub"),
+            Callback::new_sequence(mc, |args| {
+                Ok(sequence::from_fn_with(args, |mc, args| {
+                    let s = string_arg(mc, &args, b"Bad argument to sub")?;
+                    let i = integer_arg(&args, 1, 1, b"Bad argument to sub")?;
+                    let j = integer_arg(&args, 2, -1, b"Bad argument to sub")?;
+
+                    let sub = match byte_range(s.len(), i, j) {
+                        Some((start, end)) => String::new(mc, &s.as_bytes()[start..end]),
+                        None => String::new_static(b""),
+                    };
+                    Ok(CallbackResult::Return(vec![Value::String(sub)]))
+                }))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"format"),
+            Callback::new_sequence(mc, |args| {
+                Ok(sequence::from_fn_with(args, |mc, args| {
+                    let format = string_arg(mc, &args, b"Bad argument to format")?;
+                    format_from(mc, format, 0, args, 1, Vec::new())
+                }))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"upper"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let s = string_arg(mc, &args, b"Bad argument to upper")?;
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc,
+                    &s.as_bytes().to_ascii_uppercase(),
+                ))]))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"lower"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let s = string_arg(mc, &args, b"Bad argument to lower")?;
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc,
+                    &s.as_bytes().to_ascii_lowercase(),
+                ))]))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"rep"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let s = string_arg(mc, &args, b"Bad argument to rep")?;
+                let n = match args.get(1).and_then(|n| n.to_integer()) {
+                    Some(n) => n,
+                    None => {
+                        return Err(RuntimeError(Value::String(String::new_static(
+                            b"Bad argument to rep",
                         )))
-                        .into()),
+                        .into())
+                    }
+                };
+                let sep = match args.get(2).cloned() {
+                    None | Some(Value::Nil) => String::new_static(b""),
+                    Some(_) => string_arg(mc, &args[2..], b"Bad argument to rep")?,
+                };
+                if n <= 0 {
+                    return Ok(CallbackResult::Return(vec![Value::String(
+                        String::new_static(b""),
+                    )]));
+                }
+
+                let (s, sep) = (s.as_bytes(), sep.as_bytes());
+                let total = (s.len() + sep.len())
+                    .checked_mul(n as usize)
+                    .filter(|&total| total <= MAX_STRING_LEN)
+                    .ok_or_else(|| {
+                        Error::from(RuntimeError(Value::String(String::new_static(
+                            b"resulting string too large",
+                        ))))
+                    })?;
+                let mut out = Vec::with_capacity(total);
+                for i in 0..n {
+                    if i != 0 {
+                        out.extend_from_slice(sep);
+                    }
+                    out.extend_from_slice(s);
+                }
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc, &out,
+                ))]))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"byte"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let s = string_arg(mc, &args, b"Bad argument to byte")?;
+                let i = integer_arg(&args, 1, 1, b"Bad argument to byte")?;
+                let j = integer_arg(&args, 2, i, b"Bad argument to byte")?;
+                let bytes = match byte_range(s.len(), i, j) {
+                    Some((start, end)) => s.as_bytes()[start..end]
+                        .iter()
+                        .map(|&b| Value::Integer(b as i64))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                Ok(CallbackResult::Return(bytes))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"char"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let bytes = args
+                    .iter()
+                    .map(|arg| match arg.to_integer() {
+                        Some(b @ 0..=255) => Ok(b as u8),
+                        _ => Err(Error::from(RuntimeError(Value::String(
+                            String::new_static(b"Bad argument to char"),
+                        )))),
+                    })
+                    .collect::<Result<Vec<u8>, _>>()?;
+                Ok(CallbackResult::Return(vec![Value::String(String::new(
+                    mc, &bytes,
+                ))]))
+            }),
+        )
+        .unwrap();
+
+    string
+        .set(
+            mc,
+            String::new_static(b"find"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let s = string_arg(mc, &args, b"Bad argument to find")?;
+                let pat = string_arg(mc, &args[1.min(args.len())..], b"Bad argument to find")?;
+                let init = match start_position(
+                    s.len(),
+                    integer_arg(&args, 2, 1, b"Bad argument to find")?,
+                ) {
+                    Some(init) => init,
+                    None => return Ok(CallbackResult::Return(vec![Value::Nil])),
+                };
+                let (src, pat) = (s.as_bytes(), pat.as_bytes());
+
+                let plain = args.get(3).is_some_and(|plain| plain.to_bool());
+                let found = if plain || pattern::is_plain(pat) {
+                    find_plain(&src[init..], pat).map(|start| Match {
+                        start: init + start,
+                        end: init + start + pat.len(),
+                        captures: Vec::new(),
+                    })
+                } else {
+                    pattern::find(src, pat, init).map_err(pattern_error)?
+                };
+
+                Ok(CallbackResult::Return(match found {
+                    Some(m) => {
+                        let mut ret = vec![
+                            Value::Integer(m.start as i64 + 1),
+                            Value::Integer(m.end as i64),
+                        ];
+                        ret.extend(m.captures.iter().map(|&c| capture_value(mc, src, c)));
+                        ret
                     }
+                    None => vec![Value::Nil],
                 }))
             }),
         )
         .unwrap();
 
+    string
+        .set(
+            mc,
+            String::new_static(b"match"),
+            Callback::new_with_mc(mc, |mc, args| {
+                let s = string_arg(mc, &args, b"Bad argument to match")?;
+                let pat = string_arg(mc, &args[1.min(args.len())..], b"Bad argument to match")?;
+                let init = match start_position(
+                    s.len(),
+                    integer_arg(&args, 2, 1, b"Bad argument to match")?,
+                ) {
+                    Some(init) => init,
+                    None => return Ok(CallbackResult::Return(vec![Value::Nil])),
+                };
+                let src = s.as_bytes();
+
+                Ok(CallbackResult::Return(
+                    match pattern::find(src, pat.as_bytes(), init).map_err(pattern_error)? {
+                        Some(m) if m.captures.is_empty() => {
+                            vec![Value::String(String::new(mc, &src[m.start..m.end]))]
+                        }
+                        Some(m) => m
+                            .captures
+                            .iter()
+                            .map(|&c| capture_value(mc, src, c))
+                            .collect(),
+                        None => vec![Value::Nil],
+                    },
+                ))
+            }),
+        )
+        .unwrap();
+
     env.set(mc, String::new_static(b"string"), string).unwrap();
 }
+
+// Every string function takes its first argument through here, so numbers are coerced to their
+// string form the same way for each of them, as they are in PUC-Rio Lua.
+fn string_arg<'gc>(
+    mc: MutationContext<'gc, '_>,
+    args: &[Value<'gc>],
+    error: &'static [u8],
+) -> Result<String<'gc>, Error<'gc>> {
+    args.get(0)
+        .cloned()
+        .unwrap_or(Value::Nil)
+        .to_string(mc)
+        .ok_or_else(|| RuntimeError(Value::String(String::new_static(error))).into())
+}
+
+// Formats `format` from byte `pos` onwards, appending to `out` and taking arguments from
+// `args[next_arg..]`.  A `%s` argument with a `__tostring` metamethod is formatted by tail calling
+// the metamethod, and the rest of the format string is formatted in its continuation.
+fn format_from<'gc>(
+    mc: MutationContext<'gc, '_>,
+    format: String<'gc>,
+    mut pos: usize,
+    args: Vec<Value<'gc>>,
+    mut next_arg: usize,
+    mut out: Vec<u8>,
+) -> Result<CallbackResult<'gc>, Error<'gc>> {
+    let bad_argument = || {
+        Error::from(RuntimeError(Value::String(String::new_static(
+            b"Bad argument to format",
+        ))))
+    };
+
+    let bytes = format.as_bytes();
+    while let Some(&b) = bytes.get(pos) {
+        pos += 1;
+        if b != b'%' {
+            out.push(b);
+            continue;
+        }
+        let conversion = bytes.get(pos).copied();
+        pos += 1;
+        match conversion {
+            Some(b'%') => out.push(b'%'),
+            Some(b's') => {
+                let arg = *args.get(next_arg).ok_or_else(bad_argument)?;
+                next_arg += 1;
+                if let Some(function) = arg.tostring_metamethod() {
+                    return Ok(CallbackResult::TailCall {
+                        function,
+                        args: vec![arg],
+                        continuation: Continuation::new_sequence_with(
+                            (format, args),
+                            move |(format, args), res| {
+                                Ok(sequence::from_fn_with(
+                                    (format, args, res),
+                                    move |mc, (format, args, res)| {
+                                        out.extend(tostring_result(mc, res)?.as_bytes());
+                                        format_from(mc, format, pos, args, next_arg, out)
+                                    },
+                                ))
+                            },
+                        ),
+                    });
+                }
+                out.extend(arg.to_display_string(mc).as_bytes());
+            }
+            Some(b'd') => {
+                let arg = args
+                    .get(next_arg)
+                    .and_then(|arg| arg.to_integer())
+                    .ok_or_else(bad_argument)?;
+                next_arg += 1;
+                out.extend(arg.to_string().as_bytes());
+            }
+            _ => {
+                return Err(RuntimeError(Value::String(String::new_static(
+                    b"Invalid conversion in format string",
+                )))
+                .into());
+            }
+        }
+    }
+
+    Ok(CallbackResult::Return(vec![Value::String(String::new(
+        mc, &out,
+    ))]))
+}
+
+fn integer_arg<'gc>(
+    args: &[Value<'gc>],
+    i: usize,
+    default: i64,
+    error: &'static [u8],
+) -> Result<i64, Error<'gc>> {
+    match args.get(i).cloned() {
+        None | Some(Value::Nil) => Ok(default),
+        Some(v) => v
+            .to_integer()
+            .ok_or_else(|| RuntimeError(Value::String(String::new_static(error))).into()),
+    }
+}
+
+// Converts the 1-based inclusive positions `i` and `j` into a byte range of a string of length
+// `len`, with negative positions counting back from the end, or `None` if the range is empty.
+fn byte_range(len: i64, i: i64, j: i64) -> Option<(usize, usize)> {
+    let start = if i < 0 {
+        (len + i + 1).max(1)
+    } else {
+        i.max(1)
+    };
+    let end = if j < 0 { len + j + 1 } else { j.min(len) };
+    if start > end {
+        None
+    } else {
+        Some((start as usize - 1, end as usize))
+    }
+}
+
+// Converts a 1-based start position, which may be negative to count from the end, into a byte
+// offset, or `None` if it is past the end of the string so nothing can match.
+fn start_position(len: i64, init: i64) -> Option<usize> {
+    let init = if init > 0 {
+        init
+    } else if init == 0 || init < -len {
+        1
+    } else {
+        len + init + 1
+    };
+    if init > len + 1 {
+        None
+    } else {
+        Some(init as usize - 1)
+    }
+}
+
+fn find_plain(src: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    src.windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn capture_value<'gc>(mc: MutationContext<'gc, '_>, src: &[u8], capture: Capture) -> Value<'gc> {
+    match capture {
+        Capture::Range(start, end) => Value::String(String::new(mc, &src[start..end])),
+        Capture::Position(position) => Value::Integer(position as i64 + 1),
+    }
+}
+
+fn pattern_error<'gc>(error: PatternError) -> Error<'gc> {
+    RuntimeError(Value::String(String::new_static(error.0.as_bytes()))).into()
+}
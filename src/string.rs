@@ -1,14 +1,14 @@
 use std::borrow::Borrow;
+use std::cell::{OnceCell, RefCell};
 use std::error::Error as StdError;
 use std::fmt::{self, Debug};
 use std::hash::{Hash, Hasher};
-use std::io::Write;
 use std::ops::Deref;
 use std::str;
 
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHasher};
 
-use gc_arena::{Collect, Gc, GcCell, MutationContext};
+use gc_arena::{Collect, CollectionContext, Ephemeron, Gc, GcCell, MutationContext};
 
 use crate::Value;
 
@@ -35,8 +35,54 @@ impl fmt::Display for StringError {
 pub enum String<'gc> {
     Short8(u8, Gc<'gc, [u8; 8]>),
     Short32(u8, Gc<'gc, [u8; 32]>),
-    Long(Gc<'gc, Box<[u8]>>),
+    Long(Gc<'gc, [u8]>),
     Static(&'static [u8]),
+    Rope(Gc<'gc, Rope<'gc>>),
+}
+
+/// The result of a long concatenation, which holds on to the concatenated strings rather than
+/// copying them.  The bytes are only joined together the first time they are actually needed, and
+/// are cached from then on, so building a string with repeated `..` does not copy the whole string
+/// at every step.
+pub struct Rope<'gc> {
+    len: usize,
+    // Emptied once the rope is flattened, so that the parts may be collected.
+    parts: RefCell<Vec<String<'gc>>>,
+    flat: OnceCell<Box<[u8]>>,
+}
+
+unsafe impl<'gc> Collect for Rope<'gc> {
+    fn trace(&self, cc: CollectionContext) {
+        self.parts.borrow().trace(cc);
+    }
+}
+
+impl<'gc> Rope<'gc> {
+    /// Returns the bytes of this rope, flattening it if it has not been already.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.flat.get_or_init(|| {
+            let mut bytes = Vec::with_capacity(self.len);
+            // Ropes built by repeated concatenation are very deep, so they are flattened with an
+            // explicit stack rather than by recursion.
+            let mut pending: Vec<String<'gc>> = self.parts.borrow().iter().rev().copied().collect();
+            while let Some(part) = pending.pop() {
+                match part {
+                    String::Rope(rope) => match rope.flat.get() {
+                        Some(flat) => bytes.extend_from_slice(flat),
+                        None => pending.extend(rope.parts.borrow().iter().rev().copied()),
+                    },
+                    part => bytes.extend_from_slice(part.as_bytes()),
+                }
+            }
+            self.parts.borrow_mut().clear();
+            bytes.into_boxed_slice()
+        })
+    }
+
+    /// Whether the bytes of this rope have been joined together yet.
+    pub fn is_flattened(&self) -> bool {
+        self.flat.get().is_some()
+    }
 }
 
 impl<'gc> Debug for String<'gc> {
@@ -46,6 +92,7 @@ impl<'gc> Debug for String<'gc> {
             String::Short32(_, _) => fmt.write_str("Short32")?,
             String::Long(_) => fmt.write_str("Long")?,
             String::Static(_) => fmt.write_str("Static")?,
+            String::Rope(_) => fmt.write_str("Rope")?,
         }
         fmt.write_str("(")?;
         if let Ok(s) = str::from_utf8(self.as_bytes()) {
@@ -70,7 +117,7 @@ impl<'gc> String<'gc> {
             b[..len].copy_from_slice(s);
             String::Short32(len as u8, Gc::allocate(mc, b))
         } else {
-            String::Long(Gc::allocate(mc, s.to_vec().into_boxed_slice()))
+            String::Long(Gc::allocate_slice(mc, s.to_vec()))
         }
     }
 
@@ -78,18 +125,34 @@ impl<'gc> String<'gc> {
         String::Static(s)
     }
 
+    /// Concatenates the given values into a new string.
+    ///
+    /// Results longer than a few hundred bytes are built as a `Rope`, which is only flattened once
+    /// its bytes are needed.
     pub fn concat(
         mc: MutationContext<'gc, '_>,
         values: &[Value<'gc>],
     ) -> Result<String<'gc>, StringError> {
-        let mut bytes = Vec::new();
+        const ROPE_THRESHOLD: usize = 256;
+
+        enum Part<'gc> {
+            String(String<'gc>),
+            Bytes(Vec<u8>),
+        }
+
+        let mut parts = Vec::with_capacity(values.len());
+        let mut len = 0;
         for value in values {
+            let mut bytes = Vec::new();
             match value {
-                Value::Nil => write!(&mut bytes, "nil").unwrap(),
-                Value::Boolean(b) => write!(&mut bytes, "{}", b).unwrap(),
-                Value::Integer(i) => write!(&mut bytes, "{}", i).unwrap(),
-                Value::Number(n) => write!(&mut bytes, "{}", n).unwrap(),
-                Value::String(s) => bytes.extend(s.as_bytes()),
+                Value::Nil | Value::Boolean(_) | Value::Integer(_) | Value::Number(_) => {
+                    value.display(&mut bytes).unwrap()
+                }
+                Value::String(s) => {
+                    len += s.byte_len();
+                    parts.push(Part::String(*s));
+                    continue;
+                }
                 Value::Table(_) => return Err(StringError::Concat { bad_type: "table" }),
                 Value::Function(_) => {
                     return Err(StringError::Concat {
@@ -100,8 +163,36 @@ impl<'gc> String<'gc> {
                     return Err(StringError::Concat { bad_type: "thread" });
                 }
             }
+            len += bytes.len();
+            parts.push(Part::Bytes(bytes));
+        }
+
+        if len > ROPE_THRESHOLD {
+            let parts = parts
+                .into_iter()
+                .map(|part| match part {
+                    Part::String(s) => s,
+                    Part::Bytes(b) => String::new(mc, &b),
+                })
+                .collect();
+            Ok(String::Rope(Gc::allocate(
+                mc,
+                Rope {
+                    len,
+                    parts: RefCell::new(parts),
+                    flat: OnceCell::new(),
+                },
+            )))
+        } else {
+            let mut bytes = Vec::with_capacity(len);
+            for part in &parts {
+                match part {
+                    Part::String(s) => bytes.extend_from_slice(s.as_bytes()),
+                    Part::Bytes(b) => bytes.extend_from_slice(b),
+                }
+            }
+            Ok(String::new(mc, &bytes))
         }
-        Ok(String::new(mc, &bytes))
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -110,9 +201,27 @@ impl<'gc> String<'gc> {
             String::Short32(l, b) => &b[0..*l as usize],
             String::Long(b) => b,
             String::Static(b) => b,
+            String::Rope(r) => r.as_bytes(),
+        }
+    }
+
+    // The length in bytes, which unlike `as_bytes` does not flatten ropes.
+    fn byte_len(&self) -> usize {
+        match self {
+            String::Short8(l, _) | String::Short32(l, _) => *l as usize,
+            String::Long(b) => b.len(),
+            String::Static(b) => b.len(),
+            String::Rope(r) => r.len,
         }
     }
 
+    /// Returns true if both strings share the same underlying storage, rather than merely holding
+    /// equal bytes.
+    pub fn ptr_eq(a: String<'gc>, b: String<'gc>) -> bool {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        a.as_ptr() == b.as_ptr() && a.len() == b.len()
+    }
+
     pub fn len(&self) -> i64 {
         fn as_i64(len: usize) -> i64 {
             if len <= std::i64::MAX as usize {
@@ -122,11 +231,7 @@ impl<'gc> String<'gc> {
             }
         }
 
-        match self {
-            String::Short8(l, _) | String::Short32(l, _) => *l as i64,
-            String::Long(b) => as_i64(b.len()),
-            String::Static(b) => as_i64(b.len()),
-        }
+        as_i64(self.byte_len())
     }
 }
 
@@ -155,7 +260,10 @@ where
     T: AsRef<[u8]>,
 {
     fn eq(&self, other: &T) -> bool {
-        self.as_bytes() == other.as_ref()
+        // Interned strings with the same contents share one allocation, so comparing them does not
+        // need to look at their bytes.
+        let (a, b) = (self.as_bytes(), other.as_ref());
+        (a.as_ptr() == b.as_ptr() && a.len() == b.len()) || a == b
     }
 }
 
@@ -167,22 +275,132 @@ impl<'gc> Hash for String<'gc> {
     }
 }
 
+/// The set of strings produced by the parser, so that identical strings share one allocation.
+///
+/// Strings are only held weakly, so an interned string that is no longer used anywhere else is
+/// still collected.  The entries left behind by collected strings are pruned whenever the set grows
+/// to twice the size it had after the last prune, and `Lua` also prunes the set each time the
+/// collector finishes marking, so the set does not grow without bound.
 #[derive(Collect, Clone, Copy)]
 #[collect(require_copy)]
-pub struct InternedStringSet<'gc>(GcCell<'gc, FxHashSet<String<'gc>>>);
+pub struct InternedStringSet<'gc>(GcCell<'gc, InternedStrings<'gc>>);
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct InternedStrings<'gc> {
+    // Entries are grouped by the hash of their bytes rather than keyed by the bytes themselves,
+    // because the bytes of a collected string can no longer be read.
+    buckets: FxHashMap<u64, Vec<WeakString<'gc>>>,
+    len: usize,
+    prune_at: usize,
+}
+
+#[derive(Collect, Clone, Copy)]
+#[collect(require_copy)]
+enum WeakString<'gc> {
+    Short8(u8, Ephemeron<'gc, [u8; 8], ()>),
+    Short32(u8, Ephemeron<'gc, [u8; 32], ()>),
+    Long(Ephemeron<'gc, [u8], ()>),
+}
+
+impl<'gc> WeakString<'gc> {
+    fn new(mc: MutationContext<'gc, '_>, s: String<'gc>) -> WeakString<'gc> {
+        match s {
+            String::Short8(len, b) => WeakString::Short8(len, Ephemeron::new(mc, b, ())),
+            String::Short32(len, b) => WeakString::Short32(len, Ephemeron::new(mc, b, ())),
+            String::Long(b) => WeakString::Long(Ephemeron::new(mc, b, ())),
+            String::Static(_) | String::Rope(_) => {
+                unreachable!("only allocated strings are interned")
+            }
+        }
+    }
+
+    fn upgrade(self) -> Option<String<'gc>> {
+        match self {
+            WeakString::Short8(len, e) => e.key().map(|b| String::Short8(len, b)),
+            WeakString::Short32(len, e) => e.key().map(|b| String::Short32(len, b)),
+            WeakString::Long(e) => e.key().map(String::Long),
+        }
+    }
+
+    fn is_dead(self) -> bool {
+        match self {
+            WeakString::Short8(_, e) => e.is_dead(),
+            WeakString::Short32(_, e) => e.is_dead(),
+            WeakString::Long(e) => e.is_dead(),
+        }
+    }
+}
+
+const MIN_PRUNE_AT: usize = 64;
 
 impl<'gc> InternedStringSet<'gc> {
     pub fn new(mc: MutationContext<'gc, '_>) -> InternedStringSet<'gc> {
-        InternedStringSet(GcCell::allocate(mc, FxHashSet::default()))
+        InternedStringSet(GcCell::allocate(
+            mc,
+            InternedStrings {
+                buckets: FxHashMap::default(),
+                len: 0,
+                prune_at: MIN_PRUNE_AT,
+            },
+        ))
     }
 
     pub fn new_string(&self, mc: MutationContext<'gc, '_>, s: &[u8]) -> String<'gc> {
-        if let Some(found) = self.0.read().get(s) {
-            return *found;
+        let mut hasher = FxHasher::default();
+        s.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if let Some(bucket) = self.0.read().buckets.get(&hash) {
+            if let Some(found) = bucket
+                .iter()
+                .filter_map(|weak| weak.upgrade())
+                .find(|found| found.as_bytes() == s)
+            {
+                return found;
+            }
         }
 
         let s = String::new(mc, s);
-        self.0.write(mc).insert(s);
+        let mut interned = self.0.write(mc);
+        interned
+            .buckets
+            .entry(hash)
+            .or_default()
+            .push(WeakString::new(mc, s));
+        interned.len += 1;
+        if interned.len >= interned.prune_at {
+            interned.prune();
+        }
         s
     }
+
+    /// Removes the entries of strings which have been collected.
+    ///
+    /// This happens on its own as the set grows, but it is best done just after the collector
+    /// finishes marking, when every string that was found unreachable has been released.
+    pub fn prune(&self, mc: MutationContext<'gc, '_>) {
+        self.0.write(mc).prune();
+    }
+
+    /// The number of entries in the set, including those whose strings have been collected but
+    /// which have not been pruned yet.
+    pub fn len(&self) -> usize {
+        self.0.read().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'gc> InternedStrings<'gc> {
+    fn prune(&mut self) {
+        self.buckets.retain(|_, bucket| {
+            bucket.retain(|weak| !weak.is_dead());
+            !bucket.is_empty()
+        });
+        self.len = self.buckets.values().map(|bucket| bucket.len()).sum();
+        self.prune_at = (self.len * 2).max(MIN_PRUNE_AT);
+    }
 }
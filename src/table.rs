@@ -5,9 +5,9 @@ use std::{fmt, i64, mem};
 use num_traits::cast;
 use rustc_hash::FxHashMap;
 
-use gc_arena::{Collect, GcCell, MutationContext};
+use gc_arena::{Collect, CollectionContext, Ephemeron, Gc, GcCell, MutationContext};
 
-use crate::Value;
+use crate::{Function, String, Value};
 
 #[derive(Debug, Copy, Clone, Collect)]
 #[collect(require_copy)]
@@ -50,33 +50,167 @@ impl<'gc> Table<'gc> {
         Table(GcCell::allocate(mc, TableState::default()))
     }
 
+    /// Reads the value at `key`.  Like every `Table` method, this never consults the metatable;
+    /// only the VM dispatches `__index` and `__newindex`.
     pub fn get<K: Into<Value<'gc>>>(&self, key: K) -> Value<'gc> {
         self.0.read().get(key.into())
     }
 
+    /// Sets the value at `key`, returning the previous value.  Assigning nil removes the entry.
     pub fn set<K: Into<Value<'gc>>, V: Into<Value<'gc>>>(
         &self,
         mc: MutationContext<'gc, '_>,
         key: K,
         value: V,
     ) -> Result<Value<'gc>, InvalidTableKey> {
-        self.0.write(mc).set(key.into(), value.into())
+        self.0.write(mc).set(mc, key.into(), value.into())
     }
 
+    /// The same as `get`, named after Lua's `rawget` for code that wants to make it clear that
+    /// metamethods are bypassed.
+    pub fn raw_get<K: Into<Value<'gc>>>(&self, key: K) -> Value<'gc> {
+        self.get(key)
+    }
+
+    /// The same as `set`, named after Lua's `rawset`.
+    pub fn raw_set<K: Into<Value<'gc>>, V: Into<Value<'gc>>>(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        key: K,
+        value: V,
+    ) -> Result<Value<'gc>, InvalidTableKey> {
+        self.set(mc, key, value)
+    }
+
+    /// The same as `length`, named after Lua's `rawlen`.
+    pub fn raw_len(&self) -> i64 {
+        self.length()
+    }
+
+    /// Creates a copy of this table, copying every nested table reachable through its keys and
+    /// values rather than sharing them.  Other values, such as strings, functions and threads, are
+    /// shared.
+    ///
+    /// Each table is copied once, so a table referenced several times, or a cycle, maps to a single
+    /// copy.  Metatables are copied the same way if `clone_metatables` is set, and shared otherwise.
+    pub fn deep_clone(&self, mc: MutationContext<'gc, '_>, clone_metatables: bool) -> Table<'gc> {
+        let mut clones = FxHashMap::default();
+        let mut pending = Vec::new();
+
+        let mut clone_of = |table: Table<'gc>, pending: &mut Vec<(Table<'gc>, Table<'gc>)>| {
+            *clones.entry(table).or_insert_with(|| {
+                let clone = Table::new(mc);
+                pending.push((table, clone));
+                clone
+            })
+        };
+
+        let root = clone_of(*self, &mut pending);
+        while let Some((original, clone)) = pending.pop() {
+            let (entries, metatable) = {
+                let state = original.0.read();
+                (state.entries().collect::<Vec<_>>(), state.metatable)
+            };
+            // The copied metatable may not be filled in yet, so the mode is read from the original.
+            let mode = metatable.and_then(WeakMode::from_metatable);
+
+            let mut clone_value = |value, pending: &mut Vec<_>| match value {
+                Value::Table(t) => Value::Table(clone_of(t, pending)),
+                v => v,
+            };
+
+            let mut state = clone.0.write(mc);
+            for (key, value) in entries {
+                let key = clone_value(key, &mut pending);
+                let value = clone_value(value, &mut pending);
+                state
+                    .set(mc, key, value)
+                    .expect("keys copied from a table are valid");
+            }
+            let metatable = match metatable {
+                Some(mt) if clone_metatables => Some(clone_of(mt, &mut pending)),
+                mt => mt,
+            };
+            state.set_metatable(mc, metatable, mode);
+        }
+        root
+    }
+
+    pub fn metatable(&self) -> Option<Table<'gc>> {
+        self.0.read().metatable
+    }
+
+    /// Sets the metatable of this table, returning the previous one.
+    ///
+    /// If the metatable has a `__mode` field, the table becomes weak, see `WeakMode`.  The mode is
+    /// only read here, so changing `__mode` afterwards has no effect on tables already using the
+    /// metatable.
+    pub fn set_metatable(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        metatable: Option<Table<'gc>>,
+    ) -> Option<Table<'gc>> {
+        let mode = metatable.and_then(WeakMode::from_metatable);
+        self.0.write(mc).set_metatable(mc, metatable, mode)
+    }
+
+    /// Which parts of this table's entries are held weakly, if any.
+    pub fn weak_mode(&self) -> Option<WeakMode> {
+        self.0.read().weak.as_ref().map(|weak| weak.mode)
+    }
+
+    /// Removes the entries of a weak table whose weakly held keys or values have been collected.
+    ///
+    /// Such entries are never visible, but they are only dropped when the table grows or is pruned.
+    pub fn prune(&self, mc: MutationContext<'gc, '_>) {
+        if let Some(weak) = &mut self.0.write(mc).weak {
+            weak.prune();
+        }
+    }
+
+    /// Returns the entry following `key`, or the first entry for `None`, see `TableState::next`.
+    pub fn next_entry(&self, key: Option<Value<'gc>>) -> Option<(Value<'gc>, Value<'gc>)> {
+        self.0.read().next(key.unwrap_or(Value::Nil))
+    }
+
+    /// The value of the length operator `#` on this table, see `TableState::length`.
+    ///
+    /// For a table with holes, any of its borders may be returned, as in PUC-Rio Lua.
     pub fn length(&self) -> i64 {
         self.0.read().length()
     }
+
+    /// Returns the values at keys `1..` in order, stopping at the first nil, the same as `ipairs`.
+    pub fn to_array_vec(&self) -> Vec<Value<'gc>> {
+        let state = self.0.read();
+        let mut values = Vec::new();
+        for i in 1.. {
+            match state.get(Value::Integer(i)) {
+                Value::Nil => break,
+                value => values.push(value),
+            }
+        }
+        values
+    }
 }
 
 #[derive(Debug, Collect, Default)]
 #[collect(empty_drop)]
 pub struct TableState<'gc> {
     array: Vec<Value<'gc>>,
-    map: FxHashMap<TableKey<'gc>, Value<'gc>>,
+    map: OrderedMap<TableKey<'gc>, Value<'gc>>,
+    metatable: Option<Table<'gc>>,
+    // Present if the metatable selected a weak mode, in which case every entry is kept here and the
+    // array and map parts are empty.
+    weak: Option<WeakEntries<'gc>>,
 }
 
 impl<'gc> TableState<'gc> {
     pub fn get(&self, key: Value<'gc>) -> Value<'gc> {
+        if let Some(weak) = &self.weak {
+            return weak.get(key);
+        }
+
         if let Some(index) = to_array_index(key) {
             if index < self.array.len() {
                 return self.array[index];
@@ -90,11 +224,18 @@ impl<'gc> TableState<'gc> {
         }
     }
 
+    /// Sets the value at `key`, returning the previous value.  Entries of weak tables are held
+    /// through ephemerons, which are allocated with `mc`.
     pub fn set(
         &mut self,
+        mc: MutationContext<'gc, '_>,
         key: Value<'gc>,
         value: Value<'gc>,
     ) -> Result<Value<'gc>, InvalidTableKey> {
+        if let Some(weak) = &mut self.weak {
+            return weak.set(mc, key, value);
+        }
+
         let index_key = to_array_index(key);
         if let Some(index) = index_key {
             if index < self.array.len() {
@@ -103,14 +244,21 @@ impl<'gc> TableState<'gc> {
         }
 
         let hash_key = TableKey::new(key)?;
-        if value == Value::Nil {
-            Ok(self.map.remove(&hash_key).unwrap_or(Value::Nil))
+        if let Some(existing) = self.map.get_mut(&hash_key) {
+            // Removed entries are left in the map with a nil value rather than being taken out, so
+            // that `next` can still find the position of a key cleared during a traversal.  They
+            // are only dropped when the map next needs to grow.
+            Ok(mem::replace(existing, value))
+        } else if value == Value::Nil {
+            Ok(Value::Nil)
         } else if self.map.len() < self.map.capacity() {
             Ok(self.map.insert(hash_key, value).unwrap_or(Value::Nil))
         } else {
             // If a new element does not fit in either the array or map part of the table, we need
-            // to grow.  First, we find the total count of array candidate elements across the array
-            // part, the map part, and the newly inserted key.
+            // to grow.  First, we drop any removed entries, then we find the total count of array
+            // candidate elements across the array part, the map part, and the newly inserted key.
+
+            self.map.retain(|_, v| *v != Value::Nil);
 
             const USIZE_BITS: usize = mem::size_of::<usize>() * 8;
 
@@ -196,6 +344,97 @@ impl<'gc> TableState<'gc> {
         }
     }
 
+    /// Every non-nil entry in the table, with the array part first, in no meaningful order.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (Value<'gc>, Value<'gc>)> + '_ {
+        self.array
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v != Value::Nil)
+            .map(|(i, v)| (Value::Integer(i as i64 + 1), *v))
+            .chain(
+                self.map
+                    .iter()
+                    .filter(|(_, v)| **v != Value::Nil)
+                    .map(|(k, v)| (k.0, *v)),
+            )
+            .chain(
+                self.weak
+                    .iter()
+                    .flat_map(|weak| weak.entries.values().filter_map(WeakEntry::get)),
+            )
+    }
+
+    // Sets the metatable along with the weak mode it selects, moving the entries between the
+    // normal and weak parts of the table if the mode changes.
+    pub(crate) fn set_metatable(
+        &mut self,
+        mc: MutationContext<'gc, '_>,
+        metatable: Option<Table<'gc>>,
+        mode: Option<WeakMode>,
+    ) -> Option<Table<'gc>> {
+        if mode != self.weak.as_ref().map(|weak| weak.mode) {
+            let entries = self.entries().collect::<Vec<_>>();
+            self.array.clear();
+            self.map.clear();
+            self.weak = mode.map(WeakEntries::new);
+            for (key, value) in entries {
+                self.set(mc, key, value)
+                    .expect("keys copied from a table are valid");
+            }
+        }
+        mem::replace(&mut self.metatable, metatable)
+    }
+
+    /// Returns the entry after `key` in the table's traversal order, or the first entry if `key` is
+    /// nil, as Lua's `next` does.  Returns `None` after the last entry, or if `key` is not in the
+    /// table.
+    ///
+    /// The array part is traversed in order, then the hash part.  The order is the same for as long
+    /// as no new keys are added, and assigning nil to the keys already visited is allowed during a
+    /// traversal.  Each step takes constant time, apart from skipping over cleared entries.
+    pub fn next(&self, key: Value<'gc>) -> Option<(Value<'gc>, Value<'gc>)> {
+        if let Some(weak) = &self.weak {
+            return weak.next(key);
+        }
+
+        let (array_start, map_start) = match key {
+            Value::Nil => (0, 0),
+            key => match to_array_index(key) {
+                Some(index) if index < self.array.len() => (index + 1, 0),
+                _ => {
+                    let key = TableKey::new(key).ok()?;
+                    (self.array.len(), self.map.position(&key)? + 1)
+                }
+            },
+        };
+
+        self.array
+            .iter()
+            .enumerate()
+            .skip(array_start)
+            .find(|(_, v)| **v != Value::Nil)
+            .map(|(i, v)| (Value::Integer(i as i64 + 1), *v))
+            .or_else(|| {
+                self.map
+                    .iter_from(map_start)
+                    .find(|(_, v)| **v != Value::Nil)
+                    .map(|(k, v)| (k.0, *v))
+            })
+    }
+
+    /// The size of the array part, including any trailing nil slots.
+    pub fn array_len(&self) -> usize {
+        self.array.len()
+    }
+
+    /// The number of non-nil entries in the hash part.  Every entry of a weak table is counted here.
+    pub fn map_len(&self) -> usize {
+        match &self.weak {
+            Some(weak) => weak.entries.values().filter_map(WeakEntry::get).count(),
+            None => self.map.values().filter(|v| **v != Value::Nil).count(),
+        }
+    }
+
     /// Returns a 'border' for this table.
     ///
     /// A 'border' for a table is any i >= 0 where:
@@ -218,21 +457,11 @@ impl<'gc> TableState<'gc> {
             min
         }
 
-        let array_len: i64 = cast(self.array.len()).unwrap();
-
-        if !self.array.is_empty() && self.array[array_len as usize - 1] == Value::Nil {
-            // If the array part ends in a Nil, there must be a border inside it
-            binary_search(0, array_len, |i| self.array[i as usize - 1] == Value::Nil)
-        } else if self.map.is_empty() {
-            // If there is no border in the arraay but the map part is empty, then the array length
-            // is a border
-            array_len
-        } else {
-            // Otherwise, we must check the map part for a border.  We need to find some nil value
-            // in the map part as the max for a binary search.
-            let min = array_len;
-            let mut max = array_len.checked_add(1).unwrap();
-            while self.map.contains_key(&TableKey(Value::Integer(max))) {
+        // Searches for a border above `min`, which must be 0 or have a non-nil entry.  We need to
+        // find some nil value as the max for a binary search.
+        fn unbounded_search<F: Fn(i64) -> bool>(min: i64, is_nil: F) -> i64 {
+            let mut max = min.checked_add(1).unwrap();
+            while !is_nil(max) {
                 if max == i64::MAX {
                     // If we can't find a nil entry by doubling, then the table is pathalogical.  We
                     // return the favor with a pathalogical answer: i64::MAX + 1 can't exist in the
@@ -247,16 +476,335 @@ impl<'gc> TableState<'gc> {
             }
 
             // We have found a max where table[max] == nil, so we can now binary search
-            binary_search(min, max, |i| {
-                !self.map.contains_key(&TableKey(Value::Integer(i)))
+            binary_search(min, max, is_nil)
+        }
+
+        if let Some(weak) = &self.weak {
+            return unbounded_search(0, |i| weak.get(Value::Integer(i)) == Value::Nil);
+        }
+
+        let array_len: i64 = cast(self.array.len()).unwrap();
+
+        if !self.array.is_empty() && self.array[array_len as usize - 1] == Value::Nil {
+            // If the array part ends in a Nil, there must be a border inside it
+            binary_search(0, array_len, |i| self.array[i as usize - 1] == Value::Nil)
+        } else if self.map.is_empty() {
+            // If there is no border in the arraay but the map part is empty, then the array length
+            // is a border
+            array_len
+        } else {
+            // Otherwise, we must check the map part for a border.
+            unbounded_search(array_len, |i| {
+                self.map
+                    .get(&TableKey(Value::Integer(i)))
+                    .is_none_or(|v| *v == Value::Nil)
             })
         }
     }
 }
 
+/// The metatables shared by every value of a type other than table, such as the string metatable,
+/// keyed by the type's name as given by `Value::type_name`.
+#[derive(Debug, Clone, Copy, Collect)]
+#[collect(require_copy)]
+pub struct TypeMetatables<'gc>(GcCell<'gc, FxHashMap<&'static str, Table<'gc>>>);
+
+impl<'gc> TypeMetatables<'gc> {
+    pub fn new(mc: MutationContext<'gc, '_>) -> TypeMetatables<'gc> {
+        TypeMetatables(GcCell::allocate(mc, FxHashMap::default()))
+    }
+
+    pub fn get(&self, type_name: &str) -> Option<Table<'gc>> {
+        self.0.read().get(type_name).copied()
+    }
+
+    /// Sets the metatable for the given type, returning the previous one.
+    ///
+    /// Tables each have their own metatable, so the entry for "table" is never consulted.
+    pub fn set(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        type_name: &'static str,
+        metatable: Option<Table<'gc>>,
+    ) -> Option<Table<'gc>> {
+        let mut metatables = self.0.write(mc);
+        match metatable {
+            Some(metatable) => metatables.insert(type_name, metatable),
+            None => metatables.remove(type_name),
+        }
+    }
+}
+
+/// Which parts of a table's entries are held weakly, selected by a metatable whose `__mode` field
+/// is a string containing `k`, `v`, or both.
+///
+/// As in PUC-Rio Lua, only tables, functions and threads are ever held weakly.  An entry is removed
+/// once any of its weakly held parts is collected, and a weak key keeps its value alive only for as
+/// long as the key is reachable from elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Collect)]
+#[collect(require_static)]
+pub enum WeakMode {
+    Keys,
+    Values,
+    KeysAndValues,
+}
+
+impl WeakMode {
+    /// Reads the mode selected by the `__mode` field of `metatable`, if any.
+    pub fn from_metatable<'gc>(metatable: Table<'gc>) -> Option<WeakMode> {
+        match metatable.get(String::new_static(b"__mode")) {
+            Value::String(mode) => {
+                let mode = mode.as_bytes();
+                match (mode.contains(&b'k'), mode.contains(&b'v')) {
+                    (true, true) => Some(WeakMode::KeysAndValues),
+                    (true, false) => Some(WeakMode::Keys),
+                    (false, true) => Some(WeakMode::Values),
+                    (false, false) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn weak_keys(self) -> bool {
+        matches!(self, WeakMode::Keys | WeakMode::KeysAndValues)
+    }
+
+    pub fn weak_values(self) -> bool {
+        matches!(self, WeakMode::Values | WeakMode::KeysAndValues)
+    }
+}
+
+/// The weak tables known to `Lua`, which prunes each of them every time the collector finishes
+/// marking.  `setmetatable` adds every table it makes weak, and the tables themselves are only held
+/// weakly.
+#[derive(Clone, Copy, Collect)]
+#[collect(require_copy)]
+pub struct WeakTableSet<'gc>(GcCell<'gc, Vec<Ephemeron<'gc, dyn Collect + 'gc, Table<'gc>>>>);
+
+impl<'gc> WeakTableSet<'gc> {
+    pub fn new(mc: MutationContext<'gc, '_>) -> WeakTableSet<'gc> {
+        WeakTableSet(GcCell::allocate(mc, Vec::new()))
+    }
+
+    /// Adds a table to the set, if it is not already there.
+    pub fn insert(&self, mc: MutationContext<'gc, '_>, table: Table<'gc>) {
+        if !self.0.read().iter().any(|e| e.value() == Some(&table)) {
+            let entry = Ephemeron::new(mc, GcCell::erase(table.0), table);
+            self.0.write(mc).push(entry);
+        }
+    }
+
+    /// Prunes every table in the set, and forgets the tables which have been collected.
+    pub fn prune(&self, mc: MutationContext<'gc, '_>) {
+        let mut tables = self.0.write(mc);
+        tables.retain(|e| !e.is_dead());
+        for entry in tables.iter() {
+            if let Some(table) = entry.value() {
+                table.prune(mc);
+            }
+        }
+    }
+
+    /// The number of tables in the set, including those which have been collected but which have
+    /// not been pruned yet.
+    pub fn len(&self) -> usize {
+        self.0.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// The entries of a weak table.  Weakly held keys are identified by the address of their object
+// rather than by their value, so that looking up a key never touches a collected object.
+struct WeakEntries<'gc> {
+    mode: WeakMode,
+    entries: OrderedMap<WeakKey<'gc>, WeakEntry<'gc>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum WeakKey<'gc> {
+    Strong(TableKey<'gc>),
+    Object(*const ()),
+}
+
+// A weakly held part of an entry is not traced directly, only through an ephemeron on its object,
+// and the entry is dead once either of its ephemerons is.  The ephemeron on a weak key also holds
+// the value, unless the value is weak as well, so that the value is only kept alive by the key.
+struct WeakEntry<'gc> {
+    key: Value<'gc>,
+    value: Value<'gc>,
+    weak_key: Option<Ephemeron<'gc, dyn Collect + 'gc, Value<'gc>>>,
+    weak_value: Option<Ephemeron<'gc, dyn Collect + 'gc, ()>>,
+}
+
+unsafe impl<'gc> Collect for WeakEntries<'gc> {
+    fn trace(&self, cc: CollectionContext) {
+        // Strong keys are traced through their entries, which hold a copy of them.
+        for entry in self.entries.values() {
+            entry.trace(cc);
+        }
+    }
+}
+
+unsafe impl<'gc> Collect for WeakEntry<'gc> {
+    fn trace(&self, cc: CollectionContext) {
+        match &self.weak_key {
+            Some(weak_key) => weak_key.trace(cc),
+            None => self.key.trace(cc),
+        }
+        match &self.weak_value {
+            Some(weak_value) => weak_value.trace(cc),
+            None if self.weak_key.is_none() => self.value.trace(cc),
+            None => {}
+        }
+    }
+}
+
+impl<'gc> fmt::Debug for WeakEntries<'gc> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WeakEntries")
+            .field("mode", &self.mode)
+            .field(
+                "entries",
+                &self
+                    .entries
+                    .values()
+                    .filter_map(WeakEntry::get)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl<'gc> WeakEntries<'gc> {
+    fn new(mode: WeakMode) -> WeakEntries<'gc> {
+        WeakEntries {
+            mode,
+            entries: OrderedMap::default(),
+        }
+    }
+
+    fn key(&self, key: Value<'gc>) -> Result<WeakKey<'gc>, InvalidTableKey> {
+        match weak_object(key) {
+            Some(object) if self.mode.weak_keys() => Ok(WeakKey::Object(Gc::object_id(object))),
+            _ => Ok(WeakKey::Strong(TableKey::new(key)?)),
+        }
+    }
+
+    fn get(&self, key: Value<'gc>) -> Value<'gc> {
+        self.key(key)
+            .ok()
+            .and_then(|key| self.entries.get(&key))
+            .and_then(WeakEntry::get)
+            .map(|(_, value)| value)
+            .unwrap_or(Value::Nil)
+    }
+
+    fn set(
+        &mut self,
+        mc: MutationContext<'gc, '_>,
+        key: Value<'gc>,
+        value: Value<'gc>,
+    ) -> Result<Value<'gc>, InvalidTableKey> {
+        let weak_key = self.key(key)?;
+        // Keys are stored normalized, so that a float key with an integer value reads back as an
+        // integer, as in the rest of the table.
+        let key = match &weak_key {
+            WeakKey::Strong(key) => key.0,
+            WeakKey::Object(_) => key,
+        };
+
+        let previous = self
+            .entries
+            .get(&weak_key)
+            .and_then(WeakEntry::get)
+            .map(|(_, value)| value);
+        if previous.is_none() && value == Value::Nil {
+            return Ok(Value::Nil);
+        }
+        if previous.is_none() && self.entries.len() == self.entries.capacity() {
+            // As in the map part of a normal table, removed and dead entries are only dropped when
+            // the map would otherwise need to grow.
+            self.prune();
+        }
+
+        // Cleared entries are kept with a nil value, so that `next` can still find their position
+        // during a traversal.
+        let weak_value = match weak_object(value) {
+            Some(object) if self.mode.weak_values() => Some(Ephemeron::new(mc, object, ())),
+            _ => None,
+        };
+        let weak_key_ephemeron = match weak_key {
+            WeakKey::Object(_) => weak_object(key).map(|object| {
+                let held = if weak_value.is_some() {
+                    Value::Nil
+                } else {
+                    value
+                };
+                Ephemeron::new(mc, object, held)
+            }),
+            WeakKey::Strong(_) => None,
+        };
+        self.entries.insert(
+            weak_key,
+            WeakEntry {
+                key,
+                value,
+                weak_key: weak_key_ephemeron,
+                weak_value,
+            },
+        );
+        Ok(previous.unwrap_or(Value::Nil))
+    }
+
+    fn next(&self, key: Value<'gc>) -> Option<(Value<'gc>, Value<'gc>)> {
+        let start = match key {
+            Value::Nil => 0,
+            key => {
+                let key = self.key(key).ok()?;
+                self.entries.position(&key)? + 1
+            }
+        };
+        self.entries
+            .iter_from(start)
+            .find_map(|(_, entry)| entry.get())
+    }
+
+    fn prune(&mut self) {
+        self.entries.retain(|_, entry| entry.get().is_some());
+    }
+}
+
+impl<'gc> WeakEntry<'gc> {
+    // The key and value of this entry, unless it has been cleared or either has been collected.
+    fn get(&self) -> Option<(Value<'gc>, Value<'gc>)> {
+        let dead = self.weak_key.is_some_and(|e| e.is_dead())
+            || self.weak_value.is_some_and(|e| e.is_dead());
+        if dead || self.value == Value::Nil {
+            None
+        } else {
+            Some((self.key, self.value))
+        }
+    }
+}
+
+// The object a value refers to, for the kinds of values that weak tables hold weakly.
+fn weak_object<'gc>(value: Value<'gc>) -> Option<Gc<'gc, dyn Collect + 'gc>> {
+    match value {
+        Value::Table(table) => Some(GcCell::erase(table.0)),
+        Value::Function(Function::Closure(closure)) => Some(Gc::erase(closure.0)),
+        Value::Function(Function::Callback(callback)) => Some(Gc::erase(callback.0)),
+        Value::Thread(thread) => Some(GcCell::erase(thread.0)),
+        _ => None,
+    }
+}
+
 // Value which implements Hash and Eq, and cannot contain Nil or NaN values.
-#[derive(Debug, Collect, PartialEq)]
-#[collect(empty_drop)]
+#[derive(Debug, Clone, Copy, Collect, PartialEq)]
+#[collect(require_copy)]
 struct TableKey<'gc>(Value<'gc>);
 
 impl<'gc> Eq for TableKey<'gc> {}
@@ -388,3 +936,119 @@ fn highest_bit(mut i: usize) -> usize {
 
     hb + LOG_2[i] as usize
 }
+
+// A hash map which keeps its entries in a vector in insertion order, so that the position of a key
+// is found without a scan and a traversal can resume from any position.  Entries are only removed
+// by `retain`, which keeps the remaining entries in order.
+struct OrderedMap<K, V> {
+    positions: FxHashMap<K, usize>,
+    entries: Vec<(K, V)>,
+}
+
+impl<K, V> Default for OrderedMap<K, V> {
+    fn default() -> OrderedMap<K, V> {
+        OrderedMap {
+            positions: FxHashMap::default(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+unsafe impl<K: Collect, V: Collect> Collect for OrderedMap<K, V> {
+    fn needs_trace() -> bool {
+        K::needs_trace() || V::needs_trace()
+    }
+
+    fn trace(&self, cc: CollectionContext) {
+        // Every key in `positions` is a copy of one held in `entries`.
+        for (key, value) in &self.entries {
+            key.trace(cc);
+            value.trace(cc);
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for OrderedMap<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_map()
+            .entries(self.entries.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+impl<K: Copy + Eq + Hash, V> OrderedMap<K, V> {
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn capacity(&self) -> usize {
+        self.entries.capacity().min(self.positions.capacity())
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+        self.positions.reserve(additional);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.positions.clear();
+    }
+
+    fn position(&self, key: &K) -> Option<usize> {
+        self.positions.get(key).copied()
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        Some(&self.entries[self.position(key)?].1)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let position = self.position(key)?;
+        Some(&mut self.entries[position].1)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.position(&key) {
+            Some(position) => Some(mem::replace(&mut self.entries[position].1, value)),
+            None => {
+                self.positions.insert(key, self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.entries.retain_mut(|(k, v)| f(k, v));
+        self.positions.clear();
+        for (position, (key, _)) in self.entries.iter().enumerate() {
+            self.positions.insert(*key, position);
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.iter_from(0)
+    }
+
+    // Iterates over the entries from the given position onwards.
+    fn iter_from(&self, position: usize) -> impl Iterator<Item = (&K, &V)> {
+        self.entries
+            .get(position..)
+            .unwrap_or(&[])
+            .iter()
+            .map(|(k, v)| (k, v))
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
@@ -76,6 +76,11 @@ pub enum ThreadError {
     ExpectedVariable(bool),
     BadCall(TypeError),
     BadYield,
+    /// A chain of `__index` or `__newindex` metamethods, named here, was too long to follow.
+    MetaChainTooLong(&'static str),
+    /// Metamethods called each other more deeply than the thread's limit allows.
+    MetaDepthExceeded,
+    ForStepZero,
 }
 
 impl StdError for ThreadError {}
@@ -89,8 +94,15 @@ impl fmt::Display for ThreadError {
             ThreadError::ExpectedVariable(false) => {
                 write!(fmt, "operation expects constant lua thread")
             }
-            ThreadError::BadCall(type_error) => fmt::Display::fmt(type_error, fmt),
+            ThreadError::BadCall(type_error) => {
+                write!(fmt, "attempt to call a {} value", type_error.found)
+            }
             ThreadError::BadYield => write!(fmt, "yield from unyieldable function"),
+            ThreadError::MetaChainTooLong(event) => {
+                write!(fmt, "'{}' chain too long; possible loop", event)
+            }
+            ThreadError::MetaDepthExceeded => write!(fmt, "metamethod calls nested too deeply"),
+            ThreadError::ForStepZero => write!(fmt, "'for' step is zero"),
         }
     }
 }
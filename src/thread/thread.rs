@@ -4,11 +4,12 @@ use std::fmt::{self, Debug};
 use std::hash::{Hash, Hasher};
 
 use gc_arena::{Collect, GcCell, MutationContext};
-use gc_sequence::Sequence;
+use gc_sequence::{BoxSequence, Sequence};
 
 use crate::{
-    thread::run_vm, BadThreadMode, CallbackResult, CallbackReturn, Closure, Continuation, Error,
-    Function, RegisterIndex, ThreadError, TypeError, UpValue, UpValueState, Value, VarCount,
+    thread::run_vm, BadThreadMode, CallbackResult, CallbackReturn, Closure, Continuation,
+    DivZeroPolicy, Error, Function, RegisterIndex, String, ThreadError, TypeError, TypeMetatables,
+    UpValue, UpValueState, Value, VarCount,
 };
 
 #[derive(Clone, Copy, Collect)]
@@ -59,8 +60,17 @@ pub(crate) struct ThreadState<'gc> {
     open_upvalues: BTreeMap<usize, UpValue<'gc>>,
     result: Option<Result<Vec<Value<'gc>>, Error<'gc>>>,
     allow_yield: bool,
+    integer_div_zero: DivZeroPolicy,
+    metatables: TypeMetatables<'gc>,
+    // The number of metamethod calls made by Lua frames that have not yet returned, which is the
+    // number of Lua frames whose `expected_returns` is `LuaReturn::Meta`.
+    meta_depth: usize,
+    max_meta_depth: usize,
 }
 
+// The default limit on nested metamethod calls, the same as PUC-Rio Lua's limit on nested C calls.
+const DEFAULT_MAX_META_DEPTH: usize = 200;
+
 pub(crate) struct LuaFrame<'gc, 'a> {
     thread: Thread<'gc>,
     state: &'a mut ThreadState<'gc>,
@@ -117,6 +127,10 @@ impl<'gc> Thread<'gc> {
                 open_upvalues: BTreeMap::new(),
                 result: None,
                 allow_yield,
+                integer_div_zero: DivZeroPolicy::default(),
+                metatables: TypeMetatables::new(mc),
+                meta_depth: 0,
+                max_meta_depth: DEFAULT_MAX_META_DEPTH,
             },
         ))
     }
@@ -129,6 +143,42 @@ impl<'gc> Thread<'gc> {
         }
     }
 
+    /// The policy followed by integer `//` and `%` by zero in this thread.  Coroutines created
+    /// with `coroutine.create` inherit the policy of the thread that created them.
+    pub fn integer_div_zero(self) -> DivZeroPolicy {
+        self.0.read().integer_div_zero
+    }
+
+    /// Sets what integer `//` and `%` by zero do in this thread.  Any policy other than
+    /// `DivZeroPolicy::Error` deviates from Lua.
+    pub fn set_integer_div_zero(self, mc: MutationContext<'gc, '_>, policy: DivZeroPolicy) {
+        self.0.write(mc).integer_div_zero = policy;
+    }
+
+    /// The metatables of values other than tables which the VM consults for metamethods in this
+    /// thread.  A new thread has its own empty set, and coroutines created with `coroutine.create`
+    /// share the set of the thread that created them.
+    pub fn metatables(self) -> TypeMetatables<'gc> {
+        self.0.read().metatables
+    }
+
+    /// Sets the metatables of values other than tables used by this thread.
+    pub fn set_metatables(self, mc: MutationContext<'gc, '_>, metatables: TypeMetatables<'gc>) {
+        self.0.write(mc).metatables = metatables;
+    }
+
+    /// The number of metamethod calls that may be nested inside each other in this thread before
+    /// the next one raises an error.  Coroutines created with `coroutine.create` inherit the limit
+    /// of the thread that created them.
+    pub fn max_meta_depth(self) -> usize {
+        self.0.read().max_meta_depth
+    }
+
+    /// Sets the limit on nested metamethod calls in this thread.
+    pub fn set_max_meta_depth(self, mc: MutationContext<'gc, '_>, max_meta_depth: usize) {
+        self.0.write(mc).max_meta_depth = max_meta_depth;
+    }
+
     /// If this thread is `Stopped`, start a new function with the given arguments.
     pub fn start(
         self,
@@ -201,6 +251,32 @@ impl<'gc> Thread<'gc> {
         Ok(())
     }
 
+    /// Returns the name and value of the `n`th active local variable (counting from 1) of the Lua
+    /// function at the given stack level, where level 1 is the innermost Lua function.
+    ///
+    /// Panics if the thread is currently executing Lua code, but may be called from a sequence
+    /// callback running on this thread.
+    pub fn get_local(self, level: usize, n: usize) -> Option<(String<'gc>, Value<'gc>)> {
+        let state = self.0.read();
+        let (name, index) = find_local(&state, level, n)?;
+        Some((name, state.values[index]))
+    }
+
+    /// Sets the `n`th active local variable of the Lua function at the given stack level, as in
+    /// `get_local`, returning the local's name.
+    pub fn set_local(
+        self,
+        mc: MutationContext<'gc, '_>,
+        level: usize,
+        n: usize,
+        value: Value<'gc>,
+    ) -> Option<String<'gc>> {
+        let mut state = self.0.write(mc);
+        let (name, index) = find_local(&state, level, n)?;
+        state.values[index] = value;
+        Some(name)
+    }
+
     /// If the thread is in `Running` mode, either run the Lua VM for a while or step any callback
     /// that we are waiting on.
     pub fn step(self, mc: MutationContext<'gc, '_>) -> Result<(), BadThreadMode> {
@@ -262,6 +338,14 @@ impl<'gc> Thread<'gc> {
 }
 
 impl<'gc, 'a> LuaFrame<'gc, 'a> {
+    pub(crate) fn integer_div_zero(&self) -> DivZeroPolicy {
+        self.state.integer_div_zero
+    }
+
+    pub(crate) fn metatables(&self) -> TypeMetatables<'gc> {
+        self.state.metatables
+    }
+
     // Returns the active closure for this Lua frame
     pub(crate) fn closure(&self) -> Closure<'gc> {
         match self.state.frames.last() {
@@ -352,12 +436,13 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     return Err(ThreadError::ExpectedVariable(*is_variable));
                 }
 
-                *expected_returns = Some(returns);
+                *expected_returns = Some(LuaReturn::Normal(returns));
                 let function_index = *base + func.0 as usize;
                 let arg_count = args
                     .to_constant()
                     .map(|c| c as usize)
                     .unwrap_or(self.state.values.len() - function_index - 1);
+                let arg_count = resolve_call(&mut self.state.values, function_index, arg_count);
 
                 match self.state.values[function_index] {
                     Value::Function(Function::Closure(closure)) => {
@@ -386,6 +471,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     }
                     Value::Function(Function::Callback(callback)) => {
                         let ret = callback.call(
+                            mc,
                             self.state.values[function_index + 1..function_index + 1 + arg_count]
                                 .to_vec(),
                         );
@@ -425,7 +511,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                 }
 
                 let arg_count = arg_count as usize;
-                *expected_returns = Some(returns);
+                *expected_returns = Some(LuaReturn::Normal(returns));
                 let given_function_index = *base + func.0 as usize;
                 let function_index = given_function_index + 1 + arg_count;
                 self.state
@@ -435,6 +521,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     self.state.values[function_index + i] =
                         self.state.values[given_function_index + i];
                 }
+                let arg_count = resolve_call(&mut self.state.values, function_index, arg_count);
 
                 match self.state.values[function_index] {
                     Value::Function(Function::Closure(closure)) => {
@@ -462,6 +549,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     }
                     Value::Function(Function::Callback(callback)) => {
                         let ret = callback.call(
+                            mc,
                             self.state.values[function_index + 1..function_index + 1 + arg_count]
                                 .to_vec(),
                         );
@@ -505,6 +593,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     .to_constant()
                     .map(|c| c as usize)
                     .unwrap_or(self.state.values.len() - function_index - 1);
+                let arg_count = resolve_call(&mut self.state.values, function_index, arg_count);
 
                 match self.state.values[function_index] {
                     Value::Function(Function::Closure(closure)) => {
@@ -539,6 +628,7 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                     }
                     Value::Function(Function::Callback(callback)) => {
                         let ret = callback.call(
+                            mc,
                             self.state.values[function_index + 1..function_index + 1 + arg_count]
                                 .to_vec(),
                         );
@@ -556,6 +646,70 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
         }
     }
 
+    // Calls a metamethod from the middle of an instruction, with the given arguments.  On return,
+    // the first result is placed in the `dest` register, or all results are discarded if `dest` is
+    // `None`.
+    pub(crate) fn call_meta_function(
+        self,
+        mc: MutationContext<'gc, '_>,
+        function: Function<'gc>,
+        args: &[Value<'gc>],
+        dest: Option<RegisterIndex>,
+    ) -> Result<(), ThreadError> {
+        match self.state.frames.last_mut() {
+            Some(Frame::Lua {
+                expected_returns,
+                is_variable,
+                ..
+            }) => {
+                if *is_variable {
+                    return Err(ThreadError::ExpectedVariable(false));
+                }
+                if self.state.meta_depth >= self.state.max_meta_depth {
+                    return Err(ThreadError::MetaDepthExceeded);
+                }
+
+                *expected_returns = Some(LuaReturn::Meta(dest));
+                self.state.meta_depth += 1;
+                let function_index = self.state.values.len();
+                self.state.values.push(Value::Function(function));
+                self.state.values.extend_from_slice(args);
+
+                match function {
+                    Function::Closure(closure) => {
+                        let fixed_params = closure.0.proto.fixed_params as usize;
+                        let stack_size = closure.0.proto.stack_size as usize;
+
+                        let base = if args.len() > fixed_params {
+                            self.state.values[function_index + 1..].rotate_left(fixed_params);
+                            function_index + 1 + (args.len() - fixed_params)
+                        } else {
+                            function_index + 1
+                        };
+
+                        self.state.values.resize(base + stack_size, Value::Nil);
+
+                        self.state.frames.push(Frame::Lua {
+                            bottom: function_index,
+                            base,
+                            is_variable: false,
+                            pc: 0,
+                            stack_size,
+                            expected_returns: None,
+                        });
+                    }
+                    Function::Callback(callback) => {
+                        let ret = callback.call(mc, args.to_vec());
+                        self.state.values.truncate(function_index);
+                        callback_return(self.thread, self.state, mc, ret);
+                    }
+                }
+                Ok(())
+            }
+            _ => panic!("top frame is not lua frame"),
+        }
+    }
+
     // Return to the upper frame with results starting at the given register index.
     pub(crate) fn return_upper(
         mut self,
@@ -597,8 +751,26 @@ impl<'gc, 'a> LuaFrame<'gc, 'a> {
                         stack_size,
                         ..
                     }) => {
-                        let expected_returns =
-                            expected_returns.expect("no expected returns for upper lua frame");
+                        let expected_returns = match expected_returns
+                            .take()
+                            .expect("no expected returns for upper lua frame")
+                        {
+                            LuaReturn::Normal(expected_returns) => expected_returns,
+                            LuaReturn::Meta(dest) => {
+                                self.state.meta_depth -= 1;
+                                let ret = if count > 0 {
+                                    self.state.values[start]
+                                } else {
+                                    Value::Nil
+                                };
+                                self.state.values.resize(*base + *stack_size, Value::Nil);
+                                if let Some(dest) = dest {
+                                    self.state.values[*base + dest.0 as usize] = ret;
+                                }
+                                *is_variable = false;
+                                return Ok(());
+                            }
+                        };
                         let returning = expected_returns
                             .to_constant()
                             .map(|c| c as usize)
@@ -718,7 +890,7 @@ enum Frame<'gc> {
         is_variable: bool,
         pc: usize,
         stack_size: usize,
-        expected_returns: Option<VarCount>,
+        expected_returns: Option<LuaReturn>,
     },
     Continuation {
         bottom: usize,
@@ -726,9 +898,38 @@ enum Frame<'gc> {
     },
     StartCoroutine(Function<'gc>),
     ResumeCoroutine,
-    Callback(
-        Option<Box<dyn Sequence<'gc, Output = Result<CallbackResult<'gc>, Error<'gc>>> + 'gc>>,
-    ),
+    Callback(Option<BoxSequence<'gc, Result<CallbackResult<'gc>, Error<'gc>>>>),
+}
+
+// Where the results of a call made by a Lua frame should go.
+#[derive(Clone, Copy, Collect)]
+#[collect(require_static)]
+enum LuaReturn {
+    // Results are placed starting at the called function's register.
+    Normal(VarCount),
+    // The call was to a metamethod, in the middle of an instruction.  Its first result is placed in
+    // the given register, or all results are discarded if there is none.
+    Meta(Option<RegisterIndex>),
+}
+
+// If the value at `function_index` is not a function but has a `__call` metamethod, inserts the
+// metamethod in its place so that the value becomes the first argument, and returns the new argument
+// count.  Anything else that cannot be called is left to fail as a bad call.
+fn resolve_call<'gc>(
+    values: &mut Vec<Value<'gc>>,
+    function_index: usize,
+    arg_count: usize,
+) -> usize {
+    match values[function_index] {
+        Value::Function(_) => arg_count,
+        value => match value.as_callable() {
+            Some(function) => {
+                values.insert(function_index, Value::Function(function));
+                arg_count + 1
+            }
+            None => arg_count,
+        },
+    }
 }
 
 fn get_mode<'gc>(state: &ThreadState<'gc>) -> ThreadMode {
@@ -754,6 +955,42 @@ fn get_mode<'gc>(state: &ThreadState<'gc>) -> ThreadMode {
     }
 }
 
+// Finds the name and stack index of the `n`th active local of the Lua frame at the given level.
+fn find_local<'gc>(
+    state: &ThreadState<'gc>,
+    level: usize,
+    n: usize,
+) -> Option<(String<'gc>, usize)> {
+    let (bottom, base, pc) = state
+        .frames
+        .iter()
+        .rev()
+        .filter_map(|frame| match frame {
+            Frame::Lua {
+                bottom, base, pc, ..
+            } => Some((*bottom, *base, *pc)),
+            _ => None,
+        })
+        .nth(level.checked_sub(1)?)?;
+
+    let closure = match state.values[bottom] {
+        Value::Function(Function::Closure(c)) => c,
+        _ => panic!("thread bottom is not a closure"),
+    };
+
+    // Lua frames that are not running have their pc pointing just after the instruction which is
+    // waiting on a call.
+    let pc = pc.saturating_sub(1);
+    let local = closure
+        .0
+        .proto
+        .locals
+        .iter()
+        .filter(|local| local.start_pc <= pc && pc < local.end_pc)
+        .nth(n.checked_sub(1)?)?;
+    Some((local.name, base + local.register.0 as usize))
+}
+
 fn check_mode<'gc>(state: &ThreadState<'gc>, expected: ThreadMode) -> Result<(), BadThreadMode> {
     let found = get_mode(state);
     if found != expected {
@@ -793,7 +1030,7 @@ fn ext_call_function<'gc>(
                 state.values[base + i] = args.get(i).cloned().unwrap_or(Value::Nil);
             }
             for i in 0..var_params {
-                state.values[1 + i] = args[fixed_params + i]
+                state.values[bottom + 1 + i] = args[fixed_params + i]
             }
 
             state.frames.push(Frame::Lua {
@@ -806,7 +1043,7 @@ fn ext_call_function<'gc>(
             });
         }
         Function::Callback(callback) => {
-            let ret = callback.call(args.to_vec());
+            let ret = callback.call(mc, args.to_vec());
             callback_return(thread, state, mc, ret);
         }
     }
@@ -822,9 +1059,22 @@ fn return_to_lua<'gc>(state: &mut ThreadState<'gc>, rets: &[Value<'gc>]) {
             stack_size,
             ..
         }) => {
-            let ret_count = expected_returns
+            let ret_count = match expected_returns
                 .take()
-                .expect("no expected returns for lua frame");
+                .expect("no expected returns for lua frame")
+            {
+                LuaReturn::Normal(ret_count) => ret_count,
+                LuaReturn::Meta(dest) => {
+                    state.meta_depth -= 1;
+                    state.values.resize(*base + *stack_size, Value::Nil);
+                    if let Some(dest) = dest {
+                        state.values[*base + dest.0 as usize] =
+                            rets.first().copied().unwrap_or(Value::Nil);
+                    }
+                    *is_variable = false;
+                    return;
+                }
+            };
             let return_len = ret_count
                 .to_constant()
                 .map(|c| c as usize)
@@ -856,6 +1106,13 @@ fn unwind<'gc>(
     error: Error<'gc>,
 ) {
     while let Some(mut top_frame) = state.frames.pop() {
+        if let Frame::Lua {
+            expected_returns: Some(LuaReturn::Meta(_)),
+            ..
+        } = top_frame
+        {
+            state.meta_depth -= 1;
+        }
         if let Frame::Continuation {
             continuation,
             bottom,
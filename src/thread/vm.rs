@@ -2,7 +2,8 @@ use gc_arena::{Gc, MutationContext};
 
 use crate::{
     thread::LuaFrame, BinaryOperatorError, Closure, ClosureState, Error, Function, OpCode,
-    RegisterIndex, String, Table, TypeError, UpValueDescriptor, Value, VarCount,
+    RegisterIndex, String, Table, ThreadError, TypeError, TypeMetatables, UpValueDescriptor, Value,
+    VarCount,
 };
 
 // Runs the VM for the given number of instructions or until the current LuaFrame may have been
@@ -16,8 +17,24 @@ pub(crate) fn run_vm<'gc>(
     assert_ne!(instructions, 0);
 
     let current_function = lua_frame.closure();
+    let div_zero = lua_frame.integer_div_zero();
+    let metatables = lua_frame.metatables();
     let mut registers = lua_frame.registers();
 
+    // Stores the result of an arithmetic or bitwise operation in `dest`, or else calls its
+    // metamethod with the result going to `dest` and breaks out of the loop.
+    macro_rules! arithmetic {
+        ($dest:expr, $result:expr, $left:expr, $right:expr, $event:expr, $error:expr $(,)?) => {
+            match arithmetic(metatables, $result, $left, $right, $event, $error)? {
+                MetaResult::Value(v) => registers.stack_frame[$dest.0 as usize] = v,
+                MetaResult::Call(function, args) => {
+                    lua_frame.call_meta_function(mc, function, &args, Some($dest))?;
+                    break;
+                }
+            }
+        };
+    }
+
     loop {
         let op = current_function.0.proto.opcodes[*registers.pc];
         *registers.pc += 1;
@@ -54,96 +71,131 @@ pub(crate) fn run_vm<'gc>(
             }
 
             OpCode::GetTableR { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] =
-                    get_table(registers.stack_frame[table.0 as usize])?
-                        .get(registers.stack_frame[key.0 as usize]);
+                let table = registers.stack_frame[table.0 as usize];
+                let key = registers.stack_frame[key.0 as usize];
+                match index(metatables, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(function, args) => {
+                        lua_frame.call_meta_function(mc, function, &args, Some(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::GetTableC { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] =
-                    get_table(registers.stack_frame[table.0 as usize])?
-                        .get(current_function.0.proto.constants[key.0 as usize].to_value())
+                let table = registers.stack_frame[table.0 as usize];
+                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                match index(metatables, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(function, args) => {
+                        lua_frame.call_meta_function(mc, function, &args, Some(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SetTableRR { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
-                    mc,
-                    registers.stack_frame[key.0 as usize],
-                    registers.stack_frame[value.0 as usize],
-                )?;
+                let table = registers.stack_frame[table.0 as usize];
+                let key = registers.stack_frame[key.0 as usize];
+                let value = registers.stack_frame[value.0 as usize];
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::SetTableRC { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
-                    mc,
-                    registers.stack_frame[key.0 as usize],
-                    current_function.0.proto.constants[value.0 as usize].to_value(),
-                )?;
+                let table = registers.stack_frame[table.0 as usize];
+                let key = registers.stack_frame[key.0 as usize];
+                let value = current_function.0.proto.constants[value.0 as usize].to_value();
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::SetTableCR { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
-                    mc,
-                    current_function.0.proto.constants[key.0 as usize].to_value(),
-                    registers.stack_frame[value.0 as usize],
-                )?;
+                let table = registers.stack_frame[table.0 as usize];
+                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                let value = registers.stack_frame[value.0 as usize];
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::SetTableCC { table, key, value } => {
-                get_table(registers.stack_frame[table.0 as usize])?.set(
-                    mc,
-                    current_function.0.proto.constants[key.0 as usize].to_value(),
-                    current_function.0.proto.constants[value.0 as usize].to_value(),
-                )?;
+                let table = registers.stack_frame[table.0 as usize];
+                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                let value = current_function.0.proto.constants[value.0 as usize].to_value();
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::GetUpTableR { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] = get_table(
-                    registers.get_upvalue(current_function.0.upvalues[table.0 as usize]),
-                )?
-                .get(registers.stack_frame[key.0 as usize]);
+                let table = registers.get_upvalue(current_function.0.upvalues[table.0 as usize]);
+                let key = registers.stack_frame[key.0 as usize];
+                match index(metatables, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(function, args) => {
+                        lua_frame.call_meta_function(mc, function, &args, Some(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::GetUpTableC { dest, table, key } => {
-                registers.stack_frame[dest.0 as usize] =
-                    get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                        .get(current_function.0.proto.constants[key.0 as usize].to_value())
+                let table = registers.get_upvalue(current_function.0.upvalues[table.0 as usize]);
+                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                match index(metatables, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[dest.0 as usize] = v,
+                    MetaResult::Call(function, args) => {
+                        lua_frame.call_meta_function(mc, function, &args, Some(dest))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SetUpTableRR { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        registers.stack_frame[key.0 as usize],
-                        registers.stack_frame[value.0 as usize],
-                    )?;
+                let table = registers.get_upvalue(current_function.0.upvalues[table.0 as usize]);
+                let key = registers.stack_frame[key.0 as usize];
+                let value = registers.stack_frame[value.0 as usize];
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::SetUpTableRC { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        registers.stack_frame[key.0 as usize],
-                        current_function.0.proto.constants[value.0 as usize].to_value(),
-                    )?;
+                let table = registers.get_upvalue(current_function.0.upvalues[table.0 as usize]);
+                let key = registers.stack_frame[key.0 as usize];
+                let value = current_function.0.proto.constants[value.0 as usize].to_value();
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::SetUpTableCR { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        current_function.0.proto.constants[key.0 as usize].to_value(),
-                        registers.stack_frame[value.0 as usize],
-                    )?;
+                let table = registers.get_upvalue(current_function.0.upvalues[table.0 as usize]);
+                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                let value = registers.stack_frame[value.0 as usize];
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::SetUpTableCC { table, key, value } => {
-                get_table(registers.get_upvalue(current_function.0.upvalues[table.0 as usize]))?
-                    .set(
-                        mc,
-                        current_function.0.proto.constants[key.0 as usize].to_value(),
-                        current_function.0.proto.constants[value.0 as usize].to_value(),
-                    )?;
+                let table = registers.get_upvalue(current_function.0.upvalues[table.0 as usize]);
+                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                let value = current_function.0.proto.constants[value.0 as usize].to_value();
+                if let Some((function, args)) = new_index(mc, metatables, table, key, value)? {
+                    lua_frame.call_meta_function(mc, function, &args, None)?;
+                    break;
+                }
             }
 
             OpCode::Call {
@@ -223,52 +275,98 @@ pub(crate) fn run_vm<'gc>(
             }
 
             OpCode::NumericForPrep { base, jump } => {
-                registers.stack_frame[base.0 as usize] = registers.stack_frame[base.0 as usize]
-                    .subtract(registers.stack_frame[base.0 as usize + 2])
-                    .ok_or(BinaryOperatorError::Subtract)?;
-                *registers.pc = add_offset(*registers.pc, jump);
+                // The loop body directly follows this instruction, so the first iteration is entered
+                // by falling through, and an empty loop jumps past the `NumericForLoop`.
+                let base = base.0 as usize;
+                let run = match (
+                    registers.stack_frame[base],
+                    registers.stack_frame[base + 1],
+                    registers.stack_frame[base + 2],
+                ) {
+                    (_, _, Value::Integer(0)) => {
+                        return Err(ThreadError::ForStepZero.into());
+                    }
+                    (Value::Integer(init), limit, Value::Integer(step))
+                        if limit.to_number().is_some() =>
+                    {
+                        match for_limit(limit, init, step) {
+                            Some(limit) => {
+                                // Lua stores the number of remaining iterations in place of the
+                                // limit, computed as an unsigned quantity so that it cannot
+                                // overflow even when the loop runs to the edge of the integer
+                                // range.
+                                let count = if step > 0 {
+                                    (limit as u64).wrapping_sub(init as u64) / step as u64
+                                } else {
+                                    (init as u64).wrapping_sub(limit as u64)
+                                        / ((-(step + 1)) as u64 + 1)
+                                };
+                                registers.stack_frame[base + 1] = Value::Integer(count as i64);
+                                registers.stack_frame[base + 3] = Value::Integer(init);
+                                true
+                            }
+                            None => false,
+                        }
+                    }
+                    (init, limit, step) => {
+                        if let (Some(init), Some(limit), Some(step)) =
+                            (init.to_number(), limit.to_number(), step.to_number())
+                        {
+                            if step == 0.0 {
+                                return Err(ThreadError::ForStepZero.into());
+                            }
+                            registers.stack_frame[base] = Value::Number(init);
+                            registers.stack_frame[base + 1] = Value::Number(limit);
+                            registers.stack_frame[base + 2] = Value::Number(step);
+                            registers.stack_frame[base + 3] = Value::Number(init);
+                            if step < 0.0 {
+                                limit <= init
+                            } else {
+                                init <= limit
+                            }
+                        } else {
+                            return Err(BinaryOperatorError::Subtract.into());
+                        }
+                    }
+                };
+                if !run {
+                    *registers.pc = add_offset(*registers.pc, jump) + 1;
+                }
             }
 
             OpCode::NumericForLoop { base, jump } => {
+                let base = base.0 as usize;
                 match (
-                    registers.stack_frame[base.0 as usize],
-                    registers.stack_frame[base.0 as usize + 1],
-                    registers.stack_frame[base.0 as usize + 2],
+                    registers.stack_frame[base],
+                    registers.stack_frame[base + 1],
+                    registers.stack_frame[base + 2],
                 ) {
-                    (Value::Integer(index), Value::Integer(limit), Value::Integer(step)) => {
+                    (Value::Integer(index), Value::Integer(count), Value::Integer(step)) => {
+                        if count != 0 {
+                            // The count is unsigned, so it must be decremented as one.
+                            let index = index.wrapping_add(step);
+                            registers.stack_frame[base] = Value::Integer(index);
+                            registers.stack_frame[base + 1] =
+                                Value::Integer((count as u64 - 1) as i64);
+                            registers.stack_frame[base + 3] = Value::Integer(index);
+                            *registers.pc = add_offset(*registers.pc, jump);
+                        }
+                    }
+                    (Value::Number(index), Value::Number(limit), Value::Number(step)) => {
                         let index = index + step;
-                        registers.stack_frame[base.0 as usize] = Value::Integer(index);
+                        registers.stack_frame[base] = Value::Number(index);
 
-                        let past_end = if step < 0 {
+                        let past_end = if step < 0.0 {
                             index < limit
                         } else {
                             limit < index
                         };
                         if !past_end {
                             *registers.pc = add_offset(*registers.pc, jump);
-                            registers.stack_frame[base.0 as usize + 3] = Value::Integer(index);
-                        }
-                    }
-                    (index, limit, step) => {
-                        if let (Some(index), Some(limit), Some(step)) =
-                            (index.to_number(), limit.to_number(), step.to_number())
-                        {
-                            let index = index + step;
-                            registers.stack_frame[base.0 as usize] = Value::Number(index);
-
-                            let past_end = if step < 0.0 {
-                                index < limit
-                            } else {
-                                limit < index
-                            };
-                            if !past_end {
-                                *registers.pc = add_offset(*registers.pc, jump);
-                                registers.stack_frame[base.0 as usize + 3] = Value::Number(index);
-                            }
-                        } else {
-                            return Err(BinaryOperatorError::Add.into());
+                            registers.stack_frame[base + 3] = Value::Number(index);
                         }
                     }
+                    _ => return Err(BinaryOperatorError::Add.into()),
                 }
             }
 
@@ -292,16 +390,28 @@ pub(crate) fn run_vm<'gc>(
 
             OpCode::SelfR { base, table, key } => {
                 let table = registers.stack_frame[table.0 as usize];
-                let key = current_function.0.proto.constants[key.0 as usize].to_value();
+                let key = registers.stack_frame[key.0 as usize];
                 registers.stack_frame[base.0 as usize + 1] = table;
-                registers.stack_frame[base.0 as usize] = get_table(table)?.get(key);
+                match index(metatables, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[base.0 as usize] = v,
+                    MetaResult::Call(function, args) => {
+                        lua_frame.call_meta_function(mc, function, &args, Some(base))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::SelfC { base, table, key } => {
                 let table = registers.stack_frame[table.0 as usize];
                 let key = current_function.0.proto.constants[key.0 as usize].to_value();
                 registers.stack_frame[base.0 as usize + 1] = table;
-                registers.stack_frame[base.0 as usize] = get_table(table)?.get(key);
+                match index(metatables, table, key)? {
+                    MetaResult::Value(v) => registers.stack_frame[base.0 as usize] = v,
+                    MetaResult::Call(function, args) => {
+                        lua_frame.call_meta_function(mc, function, &args, Some(base))?;
+                        break;
+                    }
+                }
             }
 
             OpCode::Concat {
@@ -504,370 +614,650 @@ pub(crate) fn run_vm<'gc>(
 
             OpCode::Minus { dest, source } => {
                 let value = registers.stack_frame[source.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    value.negate().ok_or(BinaryOperatorError::UnaryNegate)?;
+                arithmetic!(
+                    dest,
+                    value.negate(),
+                    value,
+                    value,
+                    b"__unm",
+                    BinaryOperatorError::UnaryNegate
+                );
             }
 
             OpCode::BitNot { dest, source } => {
                 let value = registers.stack_frame[source.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    value.bitwise_not().ok_or(BinaryOperatorError::BitNot)?;
+                arithmetic!(
+                    dest,
+                    value.bitwise_not(),
+                    value,
+                    value,
+                    b"__bnot",
+                    BinaryOperatorError::BitNot
+                );
             }
 
             OpCode::AddRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                arithmetic!(
+                    dest,
+                    left.add(right),
+                    left,
+                    right,
+                    b"__add",
+                    BinaryOperatorError::Add
+                );
             }
 
             OpCode::AddRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                arithmetic!(
+                    dest,
+                    left.add(right),
+                    left,
+                    right,
+                    b"__add",
+                    BinaryOperatorError::Add
+                );
             }
 
             OpCode::AddCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                arithmetic!(
+                    dest,
+                    left.add(right),
+                    left,
+                    right,
+                    b"__add",
+                    BinaryOperatorError::Add
+                );
             }
 
             OpCode::AddCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.add(right).ok_or(BinaryOperatorError::Add)?;
+                arithmetic!(
+                    dest,
+                    left.add(right),
+                    left,
+                    right,
+                    b"__add",
+                    BinaryOperatorError::Add
+                );
             }
 
             OpCode::SubRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Add)?;
+                arithmetic!(
+                    dest,
+                    left.subtract(right),
+                    left,
+                    right,
+                    b"__sub",
+                    BinaryOperatorError::Subtract
+                );
             }
 
             OpCode::SubRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Subtract)?;
+                arithmetic!(
+                    dest,
+                    left.subtract(right),
+                    left,
+                    right,
+                    b"__sub",
+                    BinaryOperatorError::Subtract
+                );
             }
 
             OpCode::SubCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Subtract)?;
+                arithmetic!(
+                    dest,
+                    left.subtract(right),
+                    left,
+                    right,
+                    b"__sub",
+                    BinaryOperatorError::Subtract
+                );
             }
 
             OpCode::SubCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.subtract(right).ok_or(BinaryOperatorError::Subtract)?;
+                arithmetic!(
+                    dest,
+                    left.subtract(right),
+                    left,
+                    right,
+                    b"__sub",
+                    BinaryOperatorError::Subtract
+                );
             }
 
             OpCode::MulRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                arithmetic!(
+                    dest,
+                    left.multiply(right),
+                    left,
+                    right,
+                    b"__mul",
+                    BinaryOperatorError::Multiply
+                );
             }
 
             OpCode::MulRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                arithmetic!(
+                    dest,
+                    left.multiply(right),
+                    left,
+                    right,
+                    b"__mul",
+                    BinaryOperatorError::Multiply
+                );
             }
 
             OpCode::MulCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                arithmetic!(
+                    dest,
+                    left.multiply(right),
+                    left,
+                    right,
+                    b"__mul",
+                    BinaryOperatorError::Multiply
+                );
             }
 
             OpCode::MulCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.multiply(right).ok_or(BinaryOperatorError::Multiply)?;
+                arithmetic!(
+                    dest,
+                    left.multiply(right),
+                    left,
+                    right,
+                    b"__mul",
+                    BinaryOperatorError::Multiply
+                );
             }
 
             OpCode::DivRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                arithmetic!(
+                    dest,
+                    left.float_divide(right),
+                    left,
+                    right,
+                    b"__div",
+                    BinaryOperatorError::FloatDivide
+                );
             }
 
             OpCode::DivRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                arithmetic!(
+                    dest,
+                    left.float_divide(right),
+                    left,
+                    right,
+                    b"__div",
+                    BinaryOperatorError::FloatDivide
+                );
             }
 
             OpCode::DivCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                arithmetic!(
+                    dest,
+                    left.float_divide(right),
+                    left,
+                    right,
+                    b"__div",
+                    BinaryOperatorError::FloatDivide
+                );
             }
 
             OpCode::DivCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .float_divide(right)
-                    .ok_or(BinaryOperatorError::FloatDivide)?;
+                arithmetic!(
+                    dest,
+                    left.float_divide(right),
+                    left,
+                    right,
+                    b"__div",
+                    BinaryOperatorError::FloatDivide
+                );
             }
 
             OpCode::IDivRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                arithmetic!(
+                    dest,
+                    left.floor_divide_with(right, div_zero),
+                    left,
+                    right,
+                    b"__idiv",
+                    BinaryOperatorError::FloorDivide
+                );
             }
 
             OpCode::IDivRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                arithmetic!(
+                    dest,
+                    left.floor_divide_with(right, div_zero),
+                    left,
+                    right,
+                    b"__idiv",
+                    BinaryOperatorError::FloorDivide
+                );
             }
 
             OpCode::IDivCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                arithmetic!(
+                    dest,
+                    left.floor_divide_with(right, div_zero),
+                    left,
+                    right,
+                    b"__idiv",
+                    BinaryOperatorError::FloorDivide
+                );
             }
 
             OpCode::IDivCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .floor_divide(right)
-                    .ok_or(BinaryOperatorError::FloorDivide)?;
+                arithmetic!(
+                    dest,
+                    left.floor_divide_with(right, div_zero),
+                    left,
+                    right,
+                    b"__idiv",
+                    BinaryOperatorError::FloorDivide
+                );
             }
 
             OpCode::ModRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                arithmetic!(
+                    dest,
+                    left.modulo_with(right, div_zero),
+                    left,
+                    right,
+                    b"__mod",
+                    BinaryOperatorError::Modulo
+                );
             }
 
             OpCode::ModRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                arithmetic!(
+                    dest,
+                    left.modulo_with(right, div_zero),
+                    left,
+                    right,
+                    b"__mod",
+                    BinaryOperatorError::Modulo
+                );
             }
 
             OpCode::ModCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                arithmetic!(
+                    dest,
+                    left.modulo_with(right, div_zero),
+                    left,
+                    right,
+                    b"__mod",
+                    BinaryOperatorError::Modulo
+                );
             }
 
             OpCode::ModCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.modulo(right).ok_or(BinaryOperatorError::Modulo)?;
+                arithmetic!(
+                    dest,
+                    left.modulo_with(right, div_zero),
+                    left,
+                    right,
+                    b"__mod",
+                    BinaryOperatorError::Modulo
+                );
             }
 
             OpCode::PowRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                arithmetic!(
+                    dest,
+                    left.exponentiate(right),
+                    left,
+                    right,
+                    b"__pow",
+                    BinaryOperatorError::Exponentiate
+                );
             }
 
             OpCode::PowRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                arithmetic!(
+                    dest,
+                    left.exponentiate(right),
+                    left,
+                    right,
+                    b"__pow",
+                    BinaryOperatorError::Exponentiate
+                );
             }
 
             OpCode::PowCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                arithmetic!(
+                    dest,
+                    left.exponentiate(right),
+                    left,
+                    right,
+                    b"__pow",
+                    BinaryOperatorError::Exponentiate
+                );
             }
 
             OpCode::PowCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .exponentiate(right)
-                    .ok_or(BinaryOperatorError::Exponentiate)?;
+                arithmetic!(
+                    dest,
+                    left.exponentiate(right),
+                    left,
+                    right,
+                    b"__pow",
+                    BinaryOperatorError::Exponentiate
+                );
             }
 
             OpCode::BitAndRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_and(right),
+                    left,
+                    right,
+                    b"__band",
+                    BinaryOperatorError::BitAnd
+                );
             }
 
             OpCode::BitAndRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_and(right),
+                    left,
+                    right,
+                    b"__band",
+                    BinaryOperatorError::BitAnd
+                );
             }
 
             OpCode::BitAndCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_and(right),
+                    left,
+                    right,
+                    b"__band",
+                    BinaryOperatorError::BitAnd
+                );
             }
 
             OpCode::BitAndCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_and(right).ok_or(BinaryOperatorError::BitAnd)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_and(right),
+                    left,
+                    right,
+                    b"__band",
+                    BinaryOperatorError::BitAnd
+                );
             }
 
             OpCode::BitOrRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_or(right),
+                    left,
+                    right,
+                    b"__bor",
+                    BinaryOperatorError::BitOr
+                );
             }
 
             OpCode::BitOrRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_or(right),
+                    left,
+                    right,
+                    b"__bor",
+                    BinaryOperatorError::BitOr
+                );
             }
 
             OpCode::BitOrCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_or(right),
+                    left,
+                    right,
+                    b"__bor",
+                    BinaryOperatorError::BitOr
+                );
             }
 
             OpCode::BitOrCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_or(right).ok_or(BinaryOperatorError::BitOr)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_or(right),
+                    left,
+                    right,
+                    b"__bor",
+                    BinaryOperatorError::BitOr
+                );
             }
 
             OpCode::BitXorRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_xor(right),
+                    left,
+                    right,
+                    b"__bxor",
+                    BinaryOperatorError::BitXor
+                );
             }
 
             OpCode::BitXorRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_xor(right),
+                    left,
+                    right,
+                    b"__bxor",
+                    BinaryOperatorError::BitXor
+                );
             }
 
             OpCode::BitXorCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_xor(right),
+                    left,
+                    right,
+                    b"__bxor",
+                    BinaryOperatorError::BitXor
+                );
             }
 
             OpCode::BitXorCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] =
-                    left.bitwise_xor(right).ok_or(BinaryOperatorError::BitXor)?;
+                arithmetic!(
+                    dest,
+                    left.bitwise_xor(right),
+                    left,
+                    right,
+                    b"__bxor",
+                    BinaryOperatorError::BitXor
+                );
             }
 
             OpCode::ShiftLeftRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                arithmetic!(
+                    dest,
+                    left.shift_left(right),
+                    left,
+                    right,
+                    b"__shl",
+                    BinaryOperatorError::ShiftLeft
+                );
             }
 
             OpCode::ShiftLeftRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                arithmetic!(
+                    dest,
+                    left.shift_left(right),
+                    left,
+                    right,
+                    b"__shl",
+                    BinaryOperatorError::ShiftLeft
+                );
             }
 
             OpCode::ShiftLeftCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                arithmetic!(
+                    dest,
+                    left.shift_left(right),
+                    left,
+                    right,
+                    b"__shl",
+                    BinaryOperatorError::ShiftLeft
+                );
             }
 
             OpCode::ShiftLeftCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_left(right)
-                    .ok_or(BinaryOperatorError::ShiftLeft)?;
+                arithmetic!(
+                    dest,
+                    left.shift_left(right),
+                    left,
+                    right,
+                    b"__shl",
+                    BinaryOperatorError::ShiftLeft
+                );
             }
 
             OpCode::ShiftRightRR { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                arithmetic!(
+                    dest,
+                    left.shift_right(right),
+                    left,
+                    right,
+                    b"__shr",
+                    BinaryOperatorError::ShiftRight
+                );
             }
 
             OpCode::ShiftRightRC { dest, left, right } => {
                 let left = registers.stack_frame[left.0 as usize];
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                arithmetic!(
+                    dest,
+                    left.shift_right(right),
+                    left,
+                    right,
+                    b"__shr",
+                    BinaryOperatorError::ShiftRight
+                );
             }
 
             OpCode::ShiftRightCR { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = registers.stack_frame[right.0 as usize];
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                arithmetic!(
+                    dest,
+                    left.shift_right(right),
+                    left,
+                    right,
+                    b"__shr",
+                    BinaryOperatorError::ShiftRight
+                );
             }
 
             OpCode::ShiftRightCC { dest, left, right } => {
                 let left = current_function.0.proto.constants[left.0 as usize].to_value();
                 let right = current_function.0.proto.constants[right.0 as usize].to_value();
-                registers.stack_frame[dest.0 as usize] = left
-                    .shift_right(right)
-                    .ok_or(BinaryOperatorError::ShiftRight)?;
+                arithmetic!(
+                    dest,
+                    left.shift_right(right),
+                    left,
+                    right,
+                    b"__shr",
+                    BinaryOperatorError::ShiftRight
+                );
             }
         }
 
@@ -881,6 +1271,138 @@ pub(crate) fn run_vm<'gc>(
     Ok(instructions)
 }
 
+// The longest chain of `__index` or `__newindex` tables that is followed, as in PUC-Rio Lua.
+const MAX_META_CHAIN: usize = 2000;
+
+enum MetaResult<'gc> {
+    Value(Value<'gc>),
+    Call(Function<'gc>, [Value<'gc>; 2]),
+}
+
+// Looks up the metamethod for `event` in the metatable of `value`, which for anything but a table is
+// the metatable shared by its type.
+fn metamethod<'gc>(
+    metatables: TypeMetatables<'gc>,
+    value: Value<'gc>,
+    event: &'static [u8],
+) -> Value<'gc> {
+    match value.metatable(metatables) {
+        Some(mt) => mt.get(String::new_static(event)),
+        None => Value::Nil,
+    }
+}
+
+// Finishes an arithmetic or bitwise operation: if the operation on the values themselves failed,
+// the metamethod for `event` is taken from the left operand, or else the right, and must be called
+// with both operands.  Unary operations pass their operand twice, as PUC-Rio Lua does.
+fn arithmetic<'gc>(
+    metatables: TypeMetatables<'gc>,
+    result: Option<Value<'gc>>,
+    left: Value<'gc>,
+    right: Value<'gc>,
+    event: &'static [u8],
+    error: BinaryOperatorError,
+) -> Result<MetaResult<'gc>, BinaryOperatorError> {
+    if let Some(result) = result {
+        return Ok(MetaResult::Value(result));
+    }
+    let handler = match metamethod(metatables, left, event) {
+        Value::Nil => metamethod(metatables, right, event),
+        handler => handler,
+    };
+    match handler {
+        Value::Function(function) => Ok(MetaResult::Call(function, [left, right])),
+        _ => Err(error),
+    }
+}
+
+// Reads `table[key]`, following `__index` tables for missing keys until a value is found or an
+// `__index` function is reached, which must then be called with the table and key.  Values other
+// than tables can only be indexed through the `__index` metamethod of their type.
+fn index<'gc>(
+    metatables: TypeMetatables<'gc>,
+    mut table: Value<'gc>,
+    key: Value<'gc>,
+) -> Result<MetaResult<'gc>, Error<'gc>> {
+    for _ in 0..MAX_META_CHAIN {
+        if let Value::Table(t) = table {
+            let value = t.get(key);
+            if value != Value::Nil {
+                return Ok(MetaResult::Value(value));
+            }
+        }
+        match metamethod(metatables, table, b"__index") {
+            Value::Nil => {
+                get_table(table)?;
+                return Ok(MetaResult::Value(Value::Nil));
+            }
+            Value::Function(function) => return Ok(MetaResult::Call(function, [table, key])),
+            handler => table = handler,
+        }
+    }
+    Err(ThreadError::MetaChainTooLong("__index").into())
+}
+
+// Performs `table[key] = value`, following `__newindex` tables for missing keys until the value is
+// set or a `__newindex` function is reached, which is returned along with the arguments to call it
+// with.
+fn new_index<'gc>(
+    mc: MutationContext<'gc, '_>,
+    metatables: TypeMetatables<'gc>,
+    mut table: Value<'gc>,
+    key: Value<'gc>,
+    value: Value<'gc>,
+) -> Result<Option<(Function<'gc>, [Value<'gc>; 3])>, Error<'gc>> {
+    for _ in 0..MAX_META_CHAIN {
+        if let Value::Table(t) = table {
+            if t.get(key) != Value::Nil {
+                t.set(mc, key, value)?;
+                return Ok(None);
+            }
+        }
+        match metamethod(metatables, table, b"__newindex") {
+            Value::Nil => {
+                get_table(table)?.set(mc, key, value)?;
+                return Ok(None);
+            }
+            Value::Function(function) => return Ok(Some((function, [table, key, value]))),
+            handler => table = handler,
+        }
+    }
+    Err(ThreadError::MetaChainTooLong("__newindex").into())
+}
+
+// Converts the limit of an integer numeric for loop to an integer, rounding a float limit towards
+// the start of the loop and clamping it to the integer range.  Returns `None` if the loop should
+// not run at all.
+fn for_limit<'gc>(limit: Value<'gc>, init: i64, step: i64) -> Option<i64> {
+    let limit = match limit {
+        Value::Integer(limit) => limit,
+        limit => {
+            let limit = limit.to_number()?;
+            if limit.is_nan() {
+                return None;
+            }
+            // Float to integer casts saturate, which is exactly the clamping wanted here.
+            if step < 0 {
+                limit.ceil() as i64
+            } else {
+                limit.floor() as i64
+            }
+        }
+    };
+    let runs = if step < 0 {
+        limit <= init
+    } else {
+        init <= limit
+    };
+    if runs {
+        Some(limit)
+    } else {
+        None
+    }
+}
+
 fn get_table<'gc>(value: Value<'gc>) -> Result<Table<'gc>, TypeError> {
     match value {
         Value::Table(t) => Ok(t),
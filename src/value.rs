@@ -1,10 +1,11 @@
-use std::{f64, i64, io};
+use std::cmp::Ordering;
+use std::{f64, fmt, i64, io};
 
 use gc_arena::{Collect, Gc, GcCell, MutationContext};
 
 use crate::{
-    lexer::{read_float, read_hex_float},
-    Callback, Closure, String, Table, Thread,
+    lexer::{is_space, read_float, read_hex_float, read_hex_integer, read_integer},
+    Callback, Closure, String, Table, Thread, TypeMetatables,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Collect)]
@@ -14,6 +15,23 @@ pub enum Function<'gc> {
     Callback(Callback<'gc>),
 }
 
+/// What integer floor division and modulo do when the divisor is zero.
+///
+/// Lua requires both to raise an error, which is the default.  The other policies deviate from Lua
+/// and exist for embedders that would rather produce a result than stop the script.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Collect)]
+#[collect(require_static)]
+pub enum DivZeroPolicy {
+    /// Raise an error, as Lua does.
+    #[default]
+    Error,
+    /// Both `a // 0` and `a % 0` result in 0.
+    Zero,
+    /// `a // 0` results in `i64::MAX` or `i64::MIN` depending on the sign of `a` (or 0 when `a` is
+    /// 0), and `a % 0` results in `a`.
+    Saturate,
+}
+
 #[derive(Debug, Copy, Clone, Collect)]
 #[collect(require_copy)]
 pub enum Value<'gc> {
@@ -37,11 +55,15 @@ impl<'gc> PartialEq for Value<'gc> {
             (Value::Boolean(_), _) => false,
 
             (Value::Integer(a), Value::Integer(b)) => a == b,
-            (Value::Integer(a), Value::Number(b)) => a as f64 == b,
+            (Value::Integer(a), Value::Number(b)) => {
+                compare_integer_number(a, b) == Some(Ordering::Equal)
+            }
             (Value::Integer(_), _) => false,
 
             (Value::Number(a), Value::Number(b)) => a == b,
-            (Value::Number(a), Value::Integer(b)) => b as f64 == a,
+            (Value::Number(a), Value::Integer(b)) => {
+                compare_integer_number(b, a) == Some(Ordering::Equal)
+            }
             (Value::Number(_), _) => false,
 
             (Value::String(a), Value::String(b)) => a == b,
@@ -72,6 +94,74 @@ impl<'gc> Value<'gc> {
         }
     }
 
+    /// Returns the address of the object this value refers to, for tables, functions, and threads.
+    /// Other values, including strings, have no identity and return `None`.
+    ///
+    /// Two values have the same id exactly when they refer to the same object, but the id is only
+    /// stable while that object is alive.  Once it is collected, its address may be reused.
+    pub fn pointer_id(self) -> Option<usize> {
+        match self {
+            Value::Table(t) => Some(t.0.as_ptr() as *const () as usize),
+            Value::Function(Function::Closure(c)) => Some(Gc::as_ptr(c.0) as *const () as usize),
+            Value::Function(Function::Callback(c)) => Some(Gc::as_ptr(c.0) as *const () as usize),
+            Value::Thread(t) => Some(GcCell::as_ptr(t.0) as *const () as usize),
+            _ => None,
+        }
+    }
+
+    /// The metatable of this value: a table's own metatable, or otherwise the metatable shared by
+    /// every value of its type.
+    pub fn metatable(self, type_metatables: TypeMetatables<'gc>) -> Option<Table<'gc>> {
+        match self {
+            Value::Table(t) => t.metatable(),
+            v => type_metatables.get(v.type_name()),
+        }
+    }
+
+    /// The function that calling this value would call: the value itself if it is a function, or
+    /// the `__call` metamethod of a table, which receives the table as an extra first argument.
+    ///
+    /// Only the metatables of tables are consulted for `__call`, so this needs no access to the
+    /// per-type metatables.
+    pub fn as_callable(self) -> Option<Function<'gc>> {
+        match self {
+            Value::Function(f) => Some(f),
+            Value::Table(t) => match t.metatable()?.get(String::new_static(b"__call")) {
+                Value::Function(f) => Some(f),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The `__tostring` metamethod of a table, which `tostring` calls to convert the table rather
+    /// than using `Value::to_display_string`.  As with `Value::as_callable`, only the metatables of
+    /// tables are consulted.
+    pub fn tostring_metamethod(self) -> Option<Function<'gc>> {
+        match self {
+            Value::Table(t) => match t.metatable()?.get(String::new_static(b"__tostring")) {
+                Value::Function(f) => Some(f),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether this value can be called, either because it is a function or because it is a table
+    /// with a `__call` metamethod.
+    pub fn is_callable(self) -> bool {
+        self.as_callable().is_some()
+    }
+
+    /// Compares two values without consulting any `__eq` metamethod, as `rawequal` does.
+    ///
+    /// Integers and floats are equal when they have the same mathematical value, strings are equal
+    /// when they have the same contents, and every other value is only equal to itself.  This is
+    /// the same comparison as `==` on `Value`.
+    pub fn raw_eq(self, other: Value<'gc>) -> bool {
+        self == other
+    }
+
     /// Lua `nil` and `false` are false, anything else is true.
     pub fn to_bool(self) -> bool {
         match self {
@@ -83,23 +173,16 @@ impl<'gc> Value<'gc> {
 
     /// Interprets Numbers, Integers, and Strings as a Number, if possible.
     pub fn to_number(self) -> Option<f64> {
-        match self {
+        match self.coerce_number()? {
             Value::Integer(a) => Some(a as f64),
             Value::Number(a) => Some(a),
-            Value::String(a) => {
-                if let Some(f) = read_hex_float(&a) {
-                    Some(f)
-                } else {
-                    read_float(&a)
-                }
-            }
             _ => None,
         }
     }
 
     /// Interprets Numbers, Integers, and Strings as an Integer, if possible.
     pub fn to_integer(self) -> Option<i64> {
-        match self {
+        match self.coerce_number()? {
             Value::Integer(a) => Some(a),
             Value::Number(a) => {
                 if ((a as i64) as f64) == a {
@@ -108,20 +191,37 @@ impl<'gc> Value<'gc> {
                     None
                 }
             }
-            Value::String(a) => match if let Some(f) = read_hex_float(&a) {
-                Some(f)
-            } else {
-                read_float(&a)
-            } {
-                Some(f) => {
-                    if ((f as i64) as f64) == f {
-                        Some(f as i64)
-                    } else {
-                        None
-                    }
+            _ => None,
+        }
+    }
+
+    /// Interprets Numbers, Integers, and Strings as either an Integer or a Number, as the
+    /// arithmetic operators do.
+    ///
+    /// Strings are read the same way as numeric literals in Lua source, including hexadecimal
+    /// integers and floats, and may have leading and trailing whitespace (as C `isspace` defines it).  A string holding an
+    /// integer that fits in an `i64` becomes an Integer, any other numeric string becomes a Number.
+    pub fn coerce_number(self) -> Option<Value<'gc>> {
+        match self {
+            Value::Integer(_) | Value::Number(_) => Some(self),
+            Value::String(s) => {
+                let s = s.as_bytes();
+                let start = s.iter().position(|&c| !is_space(c)).unwrap_or(s.len());
+                let end = s
+                    .iter()
+                    .rposition(|&c| !is_space(c))
+                    .map_or(start, |i| i + 1);
+                let s = &s[start..end];
+                if s.is_empty() {
+                    None
+                } else if let Some(i) = read_hex_integer(s).or_else(|| read_integer(s)) {
+                    Some(Value::Integer(i))
+                } else {
+                    read_hex_float(s)
+                        .or_else(|| read_float(s))
+                        .map(Value::Number)
                 }
-                _ => None,
-            },
+            }
             _ => None,
         }
     }
@@ -143,26 +243,23 @@ impl<'gc> Value<'gc> {
     // Mathematical operators
 
     pub fn add(self, other: Value<'gc>) -> Option<Value<'gc>> {
-        if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
-            Some(Value::Integer(a.wrapping_add(b)))
-        } else {
-            Some(Value::Number(self.to_number()? + other.to_number()?))
+        match (self.coerce_number()?, other.coerce_number()?) {
+            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a.wrapping_add(b))),
+            (a, b) => Some(Value::Number(a.to_number()? + b.to_number()?)),
         }
     }
 
     pub fn subtract(self, other: Value<'gc>) -> Option<Value<'gc>> {
-        if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
-            Some(Value::Integer(a.wrapping_sub(b)))
-        } else {
-            Some(Value::Number(self.to_number()? - other.to_number()?))
+        match (self.coerce_number()?, other.coerce_number()?) {
+            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a.wrapping_sub(b))),
+            (a, b) => Some(Value::Number(a.to_number()? - b.to_number()?)),
         }
     }
 
     pub fn multiply(self, other: Value<'gc>) -> Option<Value<'gc>> {
-        if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
-            Some(Value::Integer(a.wrapping_mul(b)))
-        } else {
-            Some(Value::Number(self.to_number()? * other.to_number()?))
+        match (self.coerce_number()?, other.coerce_number()?) {
+            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a.wrapping_mul(b))),
+            (a, b) => Some(Value::Number(a.to_number()? * b.to_number()?)),
         }
     }
 
@@ -174,30 +271,62 @@ impl<'gc> Value<'gc> {
     /// This operation returns an Integer only if both arguments are Integers.  Rounding is towards
     /// negative infinity.
     pub fn floor_divide(self, other: Value<'gc>) -> Option<Value<'gc>> {
-        if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
+        self.floor_divide_with(other, DivZeroPolicy::Error)
+    }
+
+    /// Like `Value::floor_divide`, but integer division by zero follows the given policy.
+    pub fn floor_divide_with(self, other: Value<'gc>, policy: DivZeroPolicy) -> Option<Value<'gc>> {
+        let (a, b) = (self.coerce_number()?, other.coerce_number()?);
+        if let (Value::Integer(a), Value::Integer(b)) = (a, b) {
             if b == 0 {
-                None
+                match policy {
+                    DivZeroPolicy::Error => None,
+                    DivZeroPolicy::Zero => Some(Value::Integer(0)),
+                    DivZeroPolicy::Saturate => Some(Value::Integer(match a.cmp(&0) {
+                        Ordering::Less => i64::MIN,
+                        Ordering::Equal => 0,
+                        Ordering::Greater => i64::MAX,
+                    })),
+                }
             } else {
-                Some(Value::Integer(a.wrapping_div(b)))
+                let q = a.wrapping_div(b);
+                if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+                    Some(Value::Integer(q - 1))
+                } else {
+                    Some(Value::Integer(q))
+                }
             }
         } else {
-            Some(Value::Number(
-                (self.to_number()? / other.to_number()?).floor(),
-            ))
+            Some(Value::Number((a.to_number()? / b.to_number()?).floor()))
         }
     }
 
     /// Computes the Lua modulus (`%`) operator.  This is unlike Rust's `%` operator which computes
     /// the remainder.
     pub fn modulo(self, other: Value<'gc>) -> Option<Value<'gc>> {
-        if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
+        self.modulo_with(other, DivZeroPolicy::Error)
+    }
+
+    /// Like `Value::modulo`, but integer modulo by zero follows the given policy.
+    pub fn modulo_with(self, other: Value<'gc>, policy: DivZeroPolicy) -> Option<Value<'gc>> {
+        let (a, b) = (self.coerce_number()?, other.coerce_number()?);
+        if let (Value::Integer(a), Value::Integer(b)) = (a, b) {
             if b == 0 {
-                None
+                match policy {
+                    DivZeroPolicy::Error => None,
+                    DivZeroPolicy::Zero => Some(Value::Integer(0)),
+                    DivZeroPolicy::Saturate => Some(Value::Integer(a)),
+                }
             } else {
-                Some(Value::Integer(((a % b) + b) % b))
+                let r = a.wrapping_rem(b);
+                if r != 0 && (r < 0) != (b < 0) {
+                    Some(Value::Integer(r + b))
+                } else {
+                    Some(Value::Integer(r))
+                }
             }
         } else {
-            let (a, b) = (self.to_number()?, other.to_number()?);
+            let (a, b) = (a.to_number()?, b.to_number()?);
             Some(Value::Number(((a % b) + b) % b))
         }
     }
@@ -208,7 +337,7 @@ impl<'gc> Value<'gc> {
     }
 
     pub fn negate(self) -> Option<Value<'gc>> {
-        match self {
+        match self.coerce_number()? {
             Value::Integer(a) => Some(Value::Integer(a.wrapping_neg())),
             Value::Number(a) => Some(Value::Number(-a)),
             _ => None,
@@ -248,6 +377,10 @@ impl<'gc> Value<'gc> {
     pub fn less_than(self, other: Value<'gc>) -> Option<bool> {
         if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
             Some(a < b)
+        } else if let (Value::Integer(a), Value::Number(b)) = (self, other) {
+            Some(compare_integer_number(a, b) == Some(Ordering::Less))
+        } else if let (Value::Number(a), Value::Integer(b)) = (self, other) {
+            Some(compare_integer_number(b, a) == Some(Ordering::Greater))
         } else if let (Value::String(a), Value::String(b)) = (self, other) {
             Some(a.as_bytes() < b.as_bytes())
         } else {
@@ -258,6 +391,12 @@ impl<'gc> Value<'gc> {
     pub fn less_equal(self, other: Value<'gc>) -> Option<bool> {
         if let (Value::Integer(a), Value::Integer(b)) = (self, other) {
             Some(a <= b)
+        } else if let (Value::Integer(a), Value::Number(b)) = (self, other) {
+            let ord = compare_integer_number(a, b);
+            Some(ord == Some(Ordering::Less) || ord == Some(Ordering::Equal))
+        } else if let (Value::Number(a), Value::Integer(b)) = (self, other) {
+            let ord = compare_integer_number(b, a);
+            Some(ord == Some(Ordering::Greater) || ord == Some(Ordering::Equal))
         } else if let (Value::String(a), Value::String(b)) = (self, other) {
             Some(a.as_bytes() <= b.as_bytes())
         } else {
@@ -265,21 +404,103 @@ impl<'gc> Value<'gc> {
         }
     }
 
+    /// Converts this value to the string `tostring` would produce.  The strings for `nil`, `true`
+    /// and `false` are static and shared, so converting them never allocates.
+    pub fn to_display_string(self, mc: MutationContext<'gc, '_>) -> String<'gc> {
+        match self {
+            Value::Nil => String::new_static(b"nil"),
+            Value::Boolean(true) => String::new_static(b"true"),
+            Value::Boolean(false) => String::new_static(b"false"),
+            Value::String(s) => s,
+            v => {
+                let mut bytes = Vec::new();
+                v.display(&mut bytes).unwrap();
+                String::new(mc, &bytes)
+            }
+        }
+    }
+
+    /// Like `Value::to_display_string`, but returns the string as a `Value`, as `tostring` does.
+    pub fn to_lua_string(self, mc: MutationContext<'gc, '_>) -> Value<'gc> {
+        Value::String(self.to_display_string(mc))
+    }
+
+    /// Writes this value as `tostring` would format it.  Integers are written without a decimal
+    /// point, and floats as C's `%.14g` would write them, with a trailing `.0` if that would
+    /// otherwise make them look like an integer.
     pub fn display<W: io::Write>(self, mut w: W) -> Result<(), io::Error> {
         match self {
             Value::Nil => write!(w, "nil"),
             Value::Boolean(b) => write!(w, "{}", b),
             Value::Integer(i) => write!(w, "{}", i),
-            Value::Number(f) => write!(w, "{}", f),
+            Value::Number(f) => write_number(w, f),
             Value::String(s) => w.write_all(s.as_bytes()),
-            Value::Table(t) => write!(w, "<table {:?}>", t.0.as_ptr()),
-            Value::Function(Function::Closure(c)) => write!(w, "<function {:?}>", Gc::as_ptr(c.0)),
-            Value::Function(Function::Callback(c)) => write!(w, "<function {:?}>", Gc::as_ptr(c.0)),
-            Value::Thread(t) => write!(w, "<thread {:?}>", GcCell::as_ptr(t.0)),
+            Value::Table(t) => write!(w, "table: {:p}", t.0.as_ptr()),
+            Value::Function(Function::Closure(c)) => write!(w, "function: {:p}", Gc::as_ptr(c.0)),
+            Value::Function(Function::Callback(c)) => write!(w, "function: {:p}", Gc::as_ptr(c.0)),
+            Value::Thread(t) => write!(w, "thread: {:p}", GcCell::as_ptr(t.0)),
+        }
+    }
+}
+
+impl<'gc> fmt::Display for Value<'gc> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut bytes = Vec::new();
+        self.display(&mut bytes).unwrap();
+        fmt.write_str(&std::string::String::from_utf8_lossy(&bytes))
+    }
+}
+
+// Writes a float the way Lua 5.3 does, which is with `%.14g` followed by `.0` if the result has
+// neither a decimal point nor an exponent.
+fn write_number<W: io::Write>(mut w: W, n: f64) -> Result<(), io::Error> {
+    const PRECISION: i32 = 14;
+
+    if n.is_nan() {
+        return w.write_all(if n.is_sign_negative() {
+            b"-nan"
+        } else {
+            b"nan"
+        });
+    } else if n.is_infinite() {
+        return w.write_all(if n < 0.0 { b"-inf" } else { b"inf" });
+    } else if n == 0.0 {
+        return w.write_all(if n.is_sign_negative() {
+            b"-0.0"
+        } else {
+            b"0.0"
+        });
+    }
+
+    // The exponent is taken after rounding to the target precision, as `%g` does, so that for
+    // example 99999999999999.9 is written as `1e+14`.
+    let sci = format!("{:.*e}", (PRECISION - 1) as usize, n);
+    let (mantissa, exponent) = sci.split_at(sci.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+
+    if !(-4..PRECISION).contains(&exponent) {
+        let mantissa = strip_fraction_zeros(mantissa);
+        let sign = if exponent < 0 { '-' } else { '+' };
+        write!(w, "{}e{}{:02}", mantissa, sign, exponent.abs())
+    } else {
+        let fixed = format!("{:.*}", (PRECISION - 1 - exponent) as usize, n);
+        let fixed = strip_fraction_zeros(&fixed);
+        if fixed.contains('.') {
+            w.write_all(fixed.as_bytes())
+        } else {
+            write!(w, "{}.0", fixed)
         }
     }
 }
 
+fn strip_fraction_zeros(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
 impl<'gc> From<bool> for Value<'gc> {
     fn from(v: bool) -> Value<'gc> {
         Value::Boolean(v)
@@ -327,3 +548,26 @@ impl<'gc> From<Callback<'gc>> for Value<'gc> {
         Value::Function(Function::Callback(v))
     }
 }
+
+// Compares an integer and a float by their exact mathematical values.  Converting the integer to a
+// float first would lose precision for integers with a magnitude above 2^53, so that for example
+// `math.maxinteger` would compare equal to `2^63`.  Returns None if the float is NaN.
+fn compare_integer_number(i: i64, n: f64) -> Option<Ordering> {
+    // -2^63 and 2^63 are exactly representable as floats.
+    const I64_MIN_F: f64 = -9_223_372_036_854_775_808.0;
+    const I64_MAX_F: f64 = 9_223_372_036_854_775_808.0;
+
+    if n.is_nan() {
+        None
+    } else if n >= I64_MAX_F {
+        Some(Ordering::Less)
+    } else if n < I64_MIN_F {
+        Some(Ordering::Greater)
+    } else {
+        let floor = n.floor();
+        match i.cmp(&(floor as i64)) {
+            Ordering::Equal if floor != n => Some(Ordering::Less),
+            ord => Some(ord),
+        }
+    }
+}
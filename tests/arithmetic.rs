@@ -0,0 +1,130 @@
+use luster::{Lua, String, Value};
+
+#[test]
+fn mixed_arithmetic() {
+    type Op = fn(Value<'static>, Value<'static>) -> Option<Value<'static>>;
+
+    let ops: &[(&str, Op)] = &[
+        ("+", Value::add),
+        ("-", Value::subtract),
+        ("*", Value::multiply),
+        ("/", Value::float_divide),
+        ("//", Value::floor_divide),
+        ("%", Value::modulo),
+    ];
+
+    // The expected results of `-7 op 2` for each operator, in the same order as `ops`.
+    let integer_results = [Some(-5), Some(-9), Some(-14), None, Some(-4), Some(1)];
+    let float_results = [-5.0, -9.0, -14.0, -3.5, -4.0, 1.0];
+
+    let operands = [
+        (Value::Integer(-7), Value::Integer(2)),
+        (Value::Integer(-7), Value::Number(2.0)),
+        (Value::Number(-7.0), Value::Integer(2)),
+        (Value::Number(-7.0), Value::Number(2.0)),
+    ];
+
+    for (i, &(name, op)) in ops.iter().enumerate() {
+        for &(a, b) in &operands {
+            let result = op(a, b).unwrap();
+            match (a, b, integer_results[i]) {
+                (Value::Integer(_), Value::Integer(_), Some(expected)) => {
+                    assert_eq!(result, Value::Integer(expected), "{:?} {} {:?}", a, name, b)
+                }
+                _ => assert_eq!(
+                    result,
+                    Value::Number(float_results[i]),
+                    "{:?} {} {:?}",
+                    a,
+                    name,
+                    b
+                ),
+            }
+        }
+    }
+}
+
+#[test]
+fn integer_floor_division() {
+    let cases = [
+        (7, 2, 3, 1),
+        (-7, 2, -4, 1),
+        (7, -2, -4, -1),
+        (-7, -2, 3, -1),
+        (6, -3, -2, 0),
+        (i64::MIN, -1, i64::MIN, 0),
+    ];
+    for &(a, b, quotient, modulus) in &cases {
+        let (a, b) = (Value::Integer(a), Value::Integer(b));
+        assert_eq!(a.floor_divide(b), Some(Value::Integer(quotient)));
+        assert_eq!(a.modulo(b), Some(Value::Integer(modulus)));
+    }
+}
+
+#[test]
+fn string_coercion() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let string = |s: &[u8]| Value::String(String::new(mc, s));
+
+        assert_eq!(string(b"0x1p4").coerce_number(), Some(Value::Number(16.0)));
+        assert_eq!(string(b"  42 ").coerce_number(), Some(Value::Integer(42)));
+        assert_eq!(
+            string(b"3.14").coerce_number(),
+            Some(Value::Number("3.14".parse().unwrap()))
+        );
+        assert_eq!(
+            string(b"\t0x10\n").coerce_number(),
+            Some(Value::Integer(16))
+        );
+        assert_eq!(
+            string(b"\x0b1\x0c").coerce_number(),
+            Some(Value::Integer(1))
+        );
+        assert_eq!(string(b"-7").coerce_number(), Some(Value::Integer(-7)));
+        assert_eq!(string(b"1e2").coerce_number(), Some(Value::Number(100.0)));
+        assert_eq!(
+            string(b"1e999").coerce_number(),
+            Some(Value::Number(f64::INFINITY))
+        );
+        for bad in [
+            &b"abc"[..],
+            b"",
+            b"  ",
+            b"-",
+            b"0x",
+            b"inf",
+            b"-Infinity",
+            b"+NaN",
+            b"nan",
+            b"1 2",
+        ] {
+            assert_eq!(string(bad).coerce_number(), None);
+        }
+        assert_eq!(Value::Boolean(true).coerce_number(), None);
+
+        assert_eq!(
+            string(b"10").add(Value::Integer(5)),
+            Some(Value::Integer(15))
+        );
+        assert_eq!(
+            string(b"10").add(Value::Number(5.0)),
+            Some(Value::Number(15.0))
+        );
+        assert_eq!(
+            string(b" 0x1p4 ").multiply(string(b"2")),
+            Some(Value::Number(32.0))
+        );
+        assert_eq!(
+            string(b"7").floor_divide(Value::Integer(2)),
+            Some(Value::Integer(3))
+        );
+        assert_eq!(
+            string(b"-7").modulo(Value::Integer(3)),
+            Some(Value::Integer(2))
+        );
+        assert_eq!(string(b"2").negate(), Some(Value::Integer(-2)));
+        assert_eq!(string(b"abc").add(Value::Integer(1)), None);
+        assert_eq!(string(b"abc").negate(), None);
+    });
+}
@@ -1,7 +1,7 @@
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
 use luster::{
-    compile, Callback, CallbackResult, Closure, Error, Function, Lua, StaticError, String,
-    ThreadSequence, Value,
+    compile, Callback, CallbackResult, CallbackReturn, Closure, Error, Function, Lua, StaticError,
+    String, Table, ThreadSequence, TypeError, Value,
 };
 
 #[test]
@@ -95,3 +95,350 @@ fn tail_call_trivial_callback() -> Result<(), Box<StaticError>> {
 
     Ok(())
 }
+
+#[test]
+fn mutable_callback() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let mut sum = 0;
+            let callback = Callback::new_immediate_mut(mc, move |args| {
+                sum += args.first().and_then(|v| v.to_integer()).unwrap_or(0);
+                Ok(CallbackResult::Return(vec![Value::Integer(sum)]))
+            });
+            root.globals
+                .set(mc, String::new_static(b"accumulate"), callback)?;
+            Ok(())
+        })
+        .and_then_with(root, |mc, root, _| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        accumulate(1)
+                        accumulate(2)
+                        return accumulate(3)
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|b| assert_eq!(b, vec![Value::Integer(6)]))
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn allocating_callback() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let callback = Callback::new_with_mc(mc, |mc, args| {
+                let table = Table::new(mc);
+                for (i, arg) in args.into_iter().enumerate() {
+                    table.set(mc, Value::Integer(i as i64 + 1), arg)?;
+                }
+                table.set(mc, String::new_static(b"name"), String::new(mc, b"packed"))?;
+                Ok(CallbackResult::Return(vec![Value::Table(table)]))
+            });
+            root.globals
+                .set(mc, String::new_static(b"pack"), callback)?;
+            Ok(())
+        })
+        .and_then_with(root, |mc, root, _| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local t = pack(1, 2, 3)
+                        return t[1] + t[2] + t[3] == 6 and t.name == "packed" and t ~= pack()
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|b| assert_eq!(b, vec![Value::Boolean(true)]))
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn named_callback() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let callback = Callback::new_named(mc, "my_native_fn", |_| {
+            CallbackReturn::Immediate(Ok(CallbackResult::Return(vec![])))
+        });
+        assert_eq!(callback.name(), Some("my_native_fn"));
+        assert!(format!("{:?}", callback).contains("my_native_fn"));
+        assert_eq!(
+            Callback::new_immediate(mc, |_| Ok(CallbackResult::Return(vec![]))).name(),
+            None
+        );
+    });
+
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let callback = Callback::new_named(mc, "expects_table", |_| {
+                CallbackReturn::Immediate(Err(TypeError {
+                    expected: "table",
+                    found: "nil",
+                }
+                .into()))
+            });
+            root.globals
+                .set(mc, String::new_static(b"expects_table"), callback)?;
+            Ok(())
+        })
+        .and_then_with(root, |mc, root, _| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local _, err = pcall(expects_table)
+                        return err
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| match res[..] {
+            [Value::String(s)] => assert!(s.as_bytes().starts_with(b"expects_table: ")),
+            _ => panic!("error was not a string"),
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn sequence_callback() -> Result<(), Box<StaticError>> {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let steps = Rc::new(Cell::new(0));
+
+    let mut lua = Lua::new();
+    lua.sequence({
+        let steps = steps.clone();
+        move |root| {
+            sequence::from_fn_with(root, move |mc, root| {
+                let callback = Callback::new_sequence(mc, move |args| {
+                    let (first, second) = (steps.clone(), steps.clone());
+                    Ok(sequence::from_fn_with(args, move |_, args| {
+                        first.set(first.get() + 1);
+                        let sum: i64 = args.iter().filter_map(|v| v.to_integer()).sum();
+                        Ok((args.len() as i64, sum))
+                    })
+                    .and_then(move |_, (count, sum)| {
+                        second.set(second.get() + 1);
+                        Ok(CallbackResult::Return(vec![
+                            Value::Integer(count),
+                            Value::Integer(sum),
+                        ]))
+                    }))
+                });
+                root.globals
+                    .set(mc, String::new_static(b"count_and_sum"), callback)?;
+                Ok(())
+            })
+            .and_then_with(root, |mc, root, _| {
+                Ok(Closure::new(
+                    mc,
+                    compile(
+                        mc,
+                        root.interned_strings,
+                        &br#"
+                            local count, sum = count_and_sum(1, 2, 3)
+                            return count, sum + 1
+                        "#[..],
+                    )?,
+                    Some(root.globals),
+                )?)
+            })
+            .and_chain_with(root, |mc, root, closure| {
+                Ok(ThreadSequence::call_function(
+                    mc,
+                    root.main_thread,
+                    Function::Closure(closure),
+                    &[],
+                )?)
+            })
+            .map_ok(|res| assert_eq!(res, vec![Value::Integer(3), Value::Integer(7)]))
+            .map_err(Error::to_static)
+            .boxed()
+        }
+    })?;
+    assert_eq!(steps.get(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn callback_id() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let a = Callback::new_immediate(mc, |_| Ok(CallbackResult::Return(vec![])));
+        let b = Callback::new_immediate(mc, |_| Ok(CallbackResult::Return(vec![])));
+        let c = a;
+
+        assert_ne!(a.id(), b.id());
+        assert_ne!(a, b);
+        assert_eq!(a.id(), c.id());
+        assert_eq!(a, c);
+    });
+}
+
+#[test]
+fn cached_callback_state() -> Result<(), Box<StaticError>> {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let inits = Rc::new(Cell::new(0));
+
+    let mut lua = Lua::new();
+    lua.sequence({
+        let inits = inits.clone();
+        move |root| {
+            sequence::from_fn_with(root, move |mc, root| {
+                let callback = Callback::new_sequence_with(mc, root, move |root, _| {
+                    let inits = inits.clone();
+                    Ok(sequence::from_fn_with(*root, move |mc, root| {
+                        let cache = root.cache_or_init(
+                            mc,
+                            String::new_static(b"cached_callback_state"),
+                            |mc| {
+                                inits.set(inits.get() + 1);
+                                Table::new(mc).into()
+                            },
+                        )?;
+                        Ok(CallbackResult::Return(vec![cache]))
+                    }))
+                });
+                root.globals
+                    .set(mc, String::new_static(b"callback"), callback)?;
+                Ok(())
+            })
+            .and_then_with(root, |mc, root, _| {
+                Ok(Closure::new(
+                    mc,
+                    compile(
+                        mc,
+                        root.interned_strings,
+                        &br#"
+                            local a = callback()
+                            local b = callback()
+                            return type(a) == "table" and a == b
+                        "#[..],
+                    )?,
+                    Some(root.globals),
+                )?)
+            })
+            .and_chain_with(root, |mc, root, closure| {
+                Ok(ThreadSequence::call_function(
+                    mc,
+                    root.main_thread,
+                    Function::Closure(closure),
+                    &[],
+                )?)
+            })
+            .map_ok(|b| assert_eq!(b, vec![Value::Boolean(true)]))
+            .map_err(Error::to_static)
+            .boxed()
+        }
+    })?;
+
+    assert_eq!(inits.get(), 1);
+    Ok(())
+}
+
+#[test]
+fn callback_registry() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let print = root.callbacks.get("print").unwrap();
+        let len = root.callbacks.get("string.len").unwrap();
+        assert!(root.callbacks.get("nonexistent").is_none());
+
+        assert_eq!(
+            root.globals.get(String::new_static(b"print")),
+            Value::Function(Function::Callback(print))
+        );
+        let string = match root.globals.get(String::new_static(b"string")) {
+            Value::Table(t) => t,
+            v => panic!("string library is {:?}", v),
+        };
+        assert_eq!(
+            string.get(String::new_static(b"len")),
+            Value::Function(Function::Callback(len))
+        );
+
+        let library = Table::new(mc);
+        library
+            .set(
+                mc,
+                String::new_static(b"show"),
+                root.callbacks.get("print").unwrap(),
+            )
+            .unwrap();
+        root.globals
+            .set(
+                mc,
+                String::new_static(b"echo"),
+                root.callbacks.get("print").unwrap(),
+            )
+            .unwrap();
+        match (
+            library.get(String::new_static(b"show")),
+            root.globals.get(String::new_static(b"echo")),
+        ) {
+            (Value::Function(Function::Callback(a)), Value::Function(Function::Callback(b))) => {
+                assert_eq!(a, b);
+                assert_eq!(a, print);
+            }
+            v => panic!("unexpected values {:?}", v),
+        }
+
+        let custom = Callback::new_immediate(mc, |_| Ok(CallbackResult::Return(vec![])));
+        assert!(root.callbacks.register(mc, "custom", custom).is_none());
+        assert_eq!(root.callbacks.get("custom"), Some(custom));
+    });
+}
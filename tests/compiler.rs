@@ -0,0 +1,152 @@
+use luster::{compile, CompilerError, Error, Lua};
+
+fn compile_error(source: String) -> Option<CompilerError> {
+    let mut lua = Lua::new();
+    lua.mutate(
+        move |mc, root| match compile(mc, root.interned_strings, source.as_bytes()) {
+            Ok(_) => None,
+            Err(Error::CompilerError(err)) => Some(err),
+            Err(err) => panic!("unexpected error {:?}", err),
+        },
+    )
+}
+
+fn locals(prefix: &str, count: usize) -> String {
+    (0..count)
+        .map(|i| format!("local {}{} = {}\n", prefix, i, i))
+        .collect()
+}
+
+#[test]
+fn too_many_locals() {
+    assert!(compile_error(locals("a", 200)).is_none());
+    assert!(matches!(
+        compile_error(locals("a", 201)),
+        Some(CompilerError::Locals)
+    ));
+
+    let in_function = format!("local function f()\n{}end", locals("a", 210));
+    assert!(matches!(
+        compile_error(in_function),
+        Some(CompilerError::Locals)
+    ));
+
+    // Locals that have gone out of scope no longer count towards the limit.
+    let scoped = format!("do\n{}end\ndo\n{}end", locals("a", 150), locals("b", 150));
+    assert!(compile_error(scoped).is_none());
+}
+
+#[test]
+fn too_many_upvalues() {
+    let upvalues = |count: usize| {
+        let uses: String = (0..count)
+            .map(|i| {
+                if i < 150 {
+                    format!("a{} = 0\n", i)
+                } else {
+                    format!("b{} = 0\n", i - 150)
+                }
+            })
+            .collect();
+        format!(
+            "{}local function f()\n{}local function g()\n{}end\nend",
+            locals("a", 150),
+            locals("b", 150),
+            uses
+        )
+    };
+
+    assert!(compile_error(upvalues(250)).is_none());
+    assert!(matches!(
+        compile_error(upvalues(300)),
+        Some(CompilerError::UpValues)
+    ));
+}
+
+fn run_chunk(source: &'static str, args: Vec<i64>) -> Vec<i64> {
+    use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+    use luster::{Closure, Function, ThreadSequence, Value};
+
+    let mut lua = Lua::new();
+    lua.sequence(move |root| {
+        sequence::from_fn_with(root, move |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(mc, root.interned_strings, source.as_bytes())?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, move |mc, root, closure| {
+            let args: Vec<Value> = args.into_iter().map(Value::Integer).collect();
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &args,
+            )?)
+        })
+        .map(|res| {
+            res.unwrap()
+                .into_iter()
+                .map(|v| v.to_integer().unwrap())
+                .collect()
+        })
+        .boxed()
+    })
+}
+
+#[test]
+fn chunk_returns() {
+    assert_eq!(run_chunk("return 1, 2, 3", vec![]), vec![1, 2, 3]);
+    assert_eq!(run_chunk("return", vec![4, 5]), Vec::<i64>::new());
+    assert_eq!(run_chunk("", vec![4, 5]), Vec::<i64>::new());
+    assert_eq!(run_chunk("return ...", vec![4, 5, 6]), vec![4, 5, 6]);
+    assert_eq!(run_chunk("return 0, ...", vec![4, 5]), vec![0, 4, 5]);
+    assert_eq!(
+        run_chunk(
+            "local function f(...) return ... end return f(...)",
+            vec![7, 8]
+        ),
+        vec![7, 8]
+    );
+}
+
+#[test]
+fn field_access_uses_constant_keys() {
+    use luster::OpCode;
+
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let proto = compile(
+            mc,
+            root.interned_strings,
+            &b"local t = {} t.name = ... return t.name"[..],
+        )
+        .unwrap();
+        assert!(proto
+            .opcodes
+            .iter()
+            .any(|op| matches!(op, OpCode::SetTableCR { .. })));
+        assert!(proto
+            .opcodes
+            .iter()
+            .any(|op| matches!(op, OpCode::GetTableC { .. })));
+        assert!(!proto
+            .opcodes
+            .iter()
+            .any(|op| matches!(op, OpCode::LoadConstant { .. })));
+    });
+
+    assert_eq!(
+        run_chunk(
+            r#"
+                local base = { x = 1 }
+                local t = setmetatable({}, { __index = setmetatable({}, { __index = base }) })
+                t.y = ...
+                return t.x, t.y
+            "#,
+            vec![2]
+        ),
+        vec![1, 2]
+    );
+}
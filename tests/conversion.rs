@@ -0,0 +1,129 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use luster::{Error, FromLua, IntoLua, Lua, TypedArgs, Value};
+
+#[test]
+fn duration() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let duration = Duration::from_lua(Value::Number(1.5)).unwrap();
+        assert_eq!(duration, Duration::from_millis(1500));
+        match duration.into_lua(mc).unwrap() {
+            Value::Number(n) => assert_eq!(n, 1.5),
+            value => panic!("unexpected value {:?}", value),
+        }
+
+        assert_eq!(
+            Duration::from_lua(Value::Integer(3)).unwrap(),
+            Duration::from_secs(3)
+        );
+        assert!(matches!(
+            Duration::from_lua(Value::Number(-1.0)),
+            Err(Error::ConversionError(_))
+        ));
+        assert!(matches!(
+            Duration::from_lua(Value::Boolean(true)),
+            Err(Error::ConversionError(_))
+        ));
+    });
+}
+
+#[test]
+fn system_time() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let time = SystemTime::from_lua(Value::Number(86400.25)).unwrap();
+        assert_eq!(time, UNIX_EPOCH + Duration::from_millis(86_400_250));
+        match time.into_lua(mc).unwrap() {
+            Value::Number(n) => assert_eq!(n, 86400.25),
+            value => panic!("unexpected value {:?}", value),
+        }
+
+        let before = SystemTime::from_lua(Value::Integer(-60)).unwrap();
+        assert_eq!(before, UNIX_EPOCH - Duration::from_secs(60));
+        match before.into_lua(mc).unwrap() {
+            Value::Number(n) => assert_eq!(n, -60.0),
+            value => panic!("unexpected value {:?}", value),
+        }
+    });
+}
+
+#[test]
+fn primitives() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        assert_eq!(i64::from_lua(Value::Integer(7)).unwrap(), 7);
+        assert_eq!(i64::from_lua(Value::Number(2.0)).unwrap(), 2);
+        assert!(matches!(
+            i64::from_lua(Value::Number(2.5)),
+            Err(Error::ConversionError(_))
+        ));
+        assert!(matches!(42i64.into_lua(mc).unwrap(), Value::Integer(42)));
+
+        assert_eq!(f64::from_lua(Value::Integer(3)).unwrap(), 3.0);
+        assert!(matches!(0.5f64.into_lua(mc).unwrap(), Value::Number(n) if n == 0.5));
+
+        assert!(bool::from_lua(Value::Integer(0)).unwrap());
+        assert!(!bool::from_lua(Value::Nil).unwrap());
+        assert!(matches!(true.into_lua(mc).unwrap(), Value::Boolean(true)));
+
+        let s = "hello".into_lua(mc).unwrap();
+        assert_eq!(luster::String::from_lua(s).unwrap().as_bytes(), b"hello");
+        assert_eq!(std::string::String::from_lua(s).unwrap(), "hello");
+        assert_eq!(
+            std::string::String::from_lua(Value::Number(1.5)).unwrap(),
+            "1.5"
+        );
+        assert!(matches!(
+            luster::String::from_lua(Value::Integer(1)),
+            Err(Error::ConversionError(_))
+        ));
+    });
+}
+
+#[test]
+fn option_and_vec() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        assert_eq!(Option::<i64>::from_lua(Value::Nil).unwrap(), None);
+        assert_eq!(Option::<i64>::from_lua(Value::Integer(1)).unwrap(), Some(1));
+        assert!(matches!(None::<i64>.into_lua(mc).unwrap(), Value::Nil));
+
+        let table = vec![1i64, 2, 3].into_lua(mc).unwrap();
+        assert!(matches!(table, Value::Table(_)));
+        assert_eq!(Vec::<i64>::from_lua(table).unwrap(), vec![1, 2, 3]);
+
+        let mixed = vec![Value::Integer(1), Value::Boolean(true)]
+            .into_lua(mc)
+            .unwrap();
+        match Vec::<i64>::from_lua(mixed) {
+            Err(Error::ConversionError(error)) => {
+                assert_eq!(error.from, "boolean");
+                assert_eq!(error.to, "i64");
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        assert!(matches!(
+            Vec::<i64>::from_lua(Value::Integer(1)),
+            Err(Error::ConversionError(_))
+        ));
+    });
+}
+
+#[test]
+fn typed_args() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let args = [Value::Integer(3), "name".into_lua(mc).unwrap()];
+        assert_eq!(args.get_typed::<i64>(0).unwrap(), 3);
+        assert_eq!(args.get_typed::<std::string::String>(1).unwrap(), "name");
+        assert_eq!(args.get_typed::<Option<f64>>(2).unwrap(), None);
+        match args.get_typed::<i64>(1) {
+            Err(Error::ConversionError(error)) => {
+                assert_eq!(error.from, "string");
+                assert_eq!(error.to, "i64");
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    });
+}
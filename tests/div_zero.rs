@@ -0,0 +1,67 @@
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{
+    compile, Closure, DivZeroPolicy, Error, Function, Lua, StaticError, ThreadSequence, Value,
+};
+
+fn run_with_policy(policy: DivZeroPolicy) -> Result<Option<(i64, i64, i64)>, Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(move |root| {
+        sequence::from_fn_with(root, move |mc, root| {
+            root.main_thread.set_integer_div_zero(mc, policy);
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local co = coroutine.create(function()
+                            return 5 // 0, 5 % 0
+                        end)
+                        local _, a, b = coroutine.resume(co)
+                        return a, b, -5 // 0
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map(|res| match res {
+            Ok(values) => match values[..] {
+                [Value::Integer(a), Value::Integer(b), Value::Integer(c)] => Ok(Some((a, b, c))),
+                _ => panic!("unexpected results {:?}", values),
+            },
+            Err(Error::BinaryOperatorError(_)) => Ok(None),
+            Err(err) => Err(err.to_static()),
+        })
+        .boxed()
+    })
+    .map_err(Box::new)
+}
+
+#[test]
+fn div_zero_error() -> Result<(), Box<StaticError>> {
+    assert_eq!(run_with_policy(DivZeroPolicy::Error)?, None);
+    Ok(())
+}
+
+#[test]
+fn div_zero_zero() -> Result<(), Box<StaticError>> {
+    assert_eq!(run_with_policy(DivZeroPolicy::Zero)?, Some((0, 0, 0)));
+    Ok(())
+}
+
+#[test]
+fn div_zero_saturate() -> Result<(), Box<StaticError>> {
+    assert_eq!(
+        run_with_policy(DivZeroPolicy::Saturate)?,
+        Some((i64::MAX, 5, i64::MIN))
+    );
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{Error, Function, Lua, StaticError, String, Table, ThreadSequence, Value};
+
+#[test]
+fn custom_env() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let env = Table::new(mc);
+            root.registry.set(mc, String::new_static(b"env"), env)?;
+            root.load(mc, &b"x = 1; return x"[..], Some(env))
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| assert_eq!(res, vec![Value::Integer(1)]))
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    lua.mutate(|_, root| {
+        let env = match root.registry.get(String::new_static(b"env")) {
+            Value::Table(env) => env,
+            v => panic!("registry entry is {:?}", v),
+        };
+        assert_eq!(env.get(String::new_static(b"x")), Value::Integer(1));
+        assert_eq!(root.globals.get(String::new_static(b"x")), Value::Nil);
+    });
+
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            root.load(mc, &b"y = 2; return print ~= nil"[..], None)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| assert_eq!(res, vec![Value::Boolean(true)]))
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    lua.mutate(|_, root| {
+        assert_eq!(
+            root.globals.get(String::new_static(b"y")),
+            Value::Integer(2)
+        );
+    });
+
+    Ok(())
+}
@@ -1,5 +1,11 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
-use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence};
+use luster::{
+    compile, BadThreadMode, Callback, CallbackReturn, Closure, Error, Function, Lua, RuntimeError,
+    StaticError, String, ThreadError, ThreadMode, ThreadSequence, Value,
+};
 
 #[test]
 fn error_unwind() -> Result<(), Box<StaticError>> {
@@ -52,3 +58,347 @@ fn error_unwind() -> Result<(), Box<StaticError>> {
 
     Ok(())
 }
+
+#[test]
+fn error_table() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let error = Error::table(mc, 7i64, String::new_static(b"bad thing"));
+        let table = error.as_error_table().unwrap();
+        assert_eq!(table.get(String::new_static(b"code")), Value::Integer(7));
+        assert!(Error::from(RuntimeError(Value::Nil))
+            .as_error_table()
+            .is_none());
+    });
+
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let callback = Callback::new_sequence(mc, |_| {
+                Ok(sequence::from_fn(|mc| {
+                    Err(Error::table(mc, 404i64, String::new_static(b"not found")))
+                }))
+            });
+            root.globals
+                .set(mc, String::new_static(b"fail"), callback)?;
+            Ok(())
+        })
+        .and_then_with(root, |mc, root, _| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local ok, err = pcall(fail)
+                        return ok, err.code, err.message
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| {
+            assert_eq!(res.len(), 3);
+            assert_eq!(res[0], Value::Boolean(false));
+            assert_eq!(res[1], Value::Integer(404));
+            match res[2] {
+                Value::String(s) => assert_eq!(s.as_bytes(), b"not found"),
+                _ => panic!("message is not a string"),
+            }
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn index_chain_too_long() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local t = {}
+                        setmetatable(t, { __index = t })
+                        return t.missing
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map(|res| match res {
+                Err(Error::ThreadError(ThreadError::MetaChainTooLong("__index"))) => Ok(()),
+                res => panic!("unexpected result {:?}", res),
+            }))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn meta_depth_exceeded() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            root.main_thread.set_max_meta_depth(mc, 10);
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local function chain(n)
+                            local t = { value = n }
+                            for i = 1, n do
+                                local inner = t
+                                t = setmetatable({}, { __index = function(_, k) return inner[k] end })
+                            end
+                            return t
+                        end
+                        assert(chain(10).value == 10)
+                        return chain(11).value
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map(|res| match res {
+                Err(Error::ThreadError(ThreadError::MetaDepthExceeded)) => Ok(()),
+                res => panic!("unexpected result {:?}", res),
+            }))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn uncaught_coroutine_error_hook() -> Result<(), Box<StaticError>> {
+    let errors = Rc::new(RefCell::new(Vec::new()));
+
+    let mut lua = Lua::new();
+    let hook_errors = errors.clone();
+    lua.mutate(move |mc, root| {
+        root.set_on_uncaught_coroutine_error(
+            mc,
+            Some(Box::new(move |err| {
+                hook_errors.borrow_mut().push(err.to_string())
+            })),
+        );
+    });
+
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local fine = coroutine.create(function() coroutine.yield(1) return 2 end)
+                        coroutine.resume(fine)
+                        coroutine.resume(fine)
+
+                        local failing = coroutine.create(function() error("ignored failure") end)
+                        coroutine.resume(failing)
+                        return coroutine.status(failing) == "dead"
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| assert_eq!(res, vec![Value::Boolean(true)]))
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    let errors = errors.borrow();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("ignored failure"), "{:?}", errors);
+
+    Ok(())
+}
+
+#[test]
+fn bad_call() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local t = {}
+                        t()
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map(|res| match res {
+                Err(Error::ThreadError(err @ ThreadError::BadCall(_))) => {
+                    assert_eq!(format!("{}", err), "attempt to call a table value");
+                    Ok(())
+                }
+                res => panic!("unexpected result {:?}", res),
+            }))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn pcall_passes_fatal_errors() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let fatal = Callback::new_immediate(mc, |_| {
+                Err(BadThreadMode {
+                    expected: Some(ThreadMode::Suspended),
+                    found: ThreadMode::Running,
+                }
+                .into())
+            });
+            root.globals
+                .set(mc, String::new_static(b"fatal"), fatal)
+                .unwrap();
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local ok = pcall(error, "recoverable")
+                        assert(not ok)
+                        pcall(fatal)
+                        return "unreachable"
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map(|res| match res {
+                Err(err @ Error::BadThreadMode(_)) => {
+                    assert!(!err.is_recoverable());
+                    Ok(())
+                }
+                res => panic!("unexpected result {:?}", res),
+            }))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+#[test]
+fn pcall_passes_fatal_errors_from_named_callbacks() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let fatal = Callback::new_named(mc, "fatal", |_| {
+                CallbackReturn::Immediate(Err(BadThreadMode {
+                    expected: Some(ThreadMode::Suspended),
+                    found: ThreadMode::Running,
+                }
+                .into()))
+            });
+            root.globals
+                .set(mc, String::new_static(b"fatal"), fatal)
+                .unwrap();
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local ok = pcall(error, "recoverable")
+                        assert(not ok)
+                        pcall(fatal)
+                        return "unreachable"
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map(|res| match res {
+                Err(err @ Error::BadThreadMode(_)) => {
+                    assert!(!err.is_recoverable());
+                    Ok(())
+                }
+                res => panic!("unexpected result {:?}", res),
+            }))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
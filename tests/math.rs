@@ -0,0 +1,78 @@
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+use luster::{compile, Closure, Error, Function, Lua, StaticError, ThreadSequence};
+
+// Runs `source` in a fresh Lua state and returns its results, formatted so that they can be
+// compared across states.
+fn run(source: &'static str) -> Result<Vec<std::string::String>, StaticError> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, move |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(mc, root.interned_strings, source.as_bytes())?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?)
+        })
+        .map_ok(|res| res.iter().map(|v| format!("{}", v)).collect())
+        .map_err(Error::to_static)
+        .boxed()
+    })
+}
+
+#[test]
+fn seeded_random_is_reproducible() -> Result<(), Box<StaticError>> {
+    const SOURCE: &str = r#"
+        math.randomseed(1234)
+        local results = ""
+        for i = 1, 8 do
+            results = results .. math.random() .. " " .. math.random(6) .. " " ..
+                math.random(-3, 3) .. " "
+        end
+        return results
+    "#;
+
+    let first = run(SOURCE)?;
+    assert_eq!(first, run(SOURCE)?);
+
+    let values: Vec<&str> = first[0].split_whitespace().collect();
+    assert_eq!(values.len(), 24);
+    for chunk in values.chunks(3) {
+        let float: f64 = chunk[0].parse().unwrap();
+        assert!((0.0..1.0).contains(&float));
+        let die: i64 = chunk[1].parse().unwrap();
+        assert!((1..=6).contains(&die));
+        let offset: i64 = chunk[2].parse().unwrap();
+        assert!((-3..=3).contains(&offset));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn random_bounds() -> Result<(), Box<StaticError>> {
+    assert_eq!(
+        run("return math.random(math.maxinteger, math.maxinteger)")?,
+        vec![format!("{}", i64::MAX)]
+    );
+    assert_eq!(
+        run("return pcall(math.random, 0)")?
+            .first()
+            .map(|s| s.as_str()),
+        Some("false")
+    );
+    assert_eq!(
+        run("return pcall(math.random, 3, 2)")?
+            .first()
+            .map(|s| s.as_str()),
+        Some("false")
+    );
+    Ok(())
+}
@@ -77,3 +77,33 @@ fn test_function_call() {
         }
     );
 }
+
+#[test]
+fn test_incomplete_input() {
+    let parse = |source: &str| {
+        parse_chunk(source.as_bytes(), |s| s.to_vec().into_boxed_slice()).map(|_| ())
+    };
+
+    for source in &[
+        "function f()",
+        "function f(",
+        "if x then",
+        "x = 1 +",
+        "local s = [[unfinished",
+        "--[[ unfinished comment",
+    ] {
+        let err = parse(source).unwrap_err();
+        assert!(err.is_incomplete(), "{:?} gave {:?}", source, err);
+    }
+
+    for source in &[
+        "function f(+)",
+        "x = = 1",
+        "end",
+        "local s = 'unfinished",
+        "x = 1 )",
+    ] {
+        let err = parse(source).unwrap_err();
+        assert!(!err.is_incomplete(), "{:?} gave {:?}", source, err);
+    }
+}
@@ -54,6 +54,21 @@ local function test_short_circuit_large()
     return (a + 2 + (a == 1 and (d or (c + (b + 2))))) == 10
 end
 
+local function test_rawequal()
+    local t = {}
+    return
+        rawequal(1, 1.0) and
+        rawequal(2^53, 1 << 53) and
+        not rawequal(1, 1.5) and
+        not rawequal(1, "1") and
+        rawequal("abc", "ab" .. "c") and
+        rawequal(t, t) and
+        not rawequal(t, {}) and
+        rawequal(nil, nil) and
+        not rawequal(false, nil) and
+        not pcall(rawequal, 1)
+end
+
 return
     test1() and
     test2() and
@@ -61,4 +76,5 @@ return
     test4() and
     test5() and
     test6() and
-    test_short_circuit_large()
+    test_short_circuit_large() and
+    test_rawequal()
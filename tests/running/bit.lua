@@ -24,7 +24,9 @@ function test3()
 end
 
 function test4()
-    return ~2   == -3 and
+    return ~0   == -1 and
+           ~5   == -6 and
+           ~2   == -3 and
            ~"2" == -3 and
            ~2.0 == -3
 end
@@ -0,0 +1,36 @@
+local nan = 0/0
+
+function test_nan()
+    return
+        not (1 < nan) and not (1 > nan) and
+        not (1 <= nan) and not (1 >= nan) and
+        not (nan < 1) and not (nan > 1) and
+        not (nan <= 1) and not (nan >= 1) and
+        not (nan > nan) and not (nan >= nan)
+end
+
+function test_greater()
+    local a, b = 1, 2
+    return
+        b > a and not (a > b) and
+        b >= a and a >= a and not (a >= b) and
+        2 > 1 and 2 >= 2 and not (1 > 2) and
+        a > 0 and 3 > a and a >= 1 and 1 >= a and
+        "b" > "a" and "a" >= "a"
+end
+
+function test_not_equal_precedence()
+    local t = true
+    return
+        1 + 1 ~= 1 + 2 and
+        2 * 3 ~= 5 and
+        "a" .. "b" ~= "a" and
+        not (1 + 1 ~= 2) and
+        (1 ~= 2) == t and
+        1 < 2 == true
+end
+
+return
+    test_nan() and
+    test_greater() and
+    test_not_equal_precedence()
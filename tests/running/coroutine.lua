@@ -41,6 +41,39 @@ function test2()
         e2 == false and r2 == 'test error' and s2 == "dead"
 end
 
+function test3()
+    local main, is_main = coroutine.running()
+    local inner, inner_is_main, inner_yieldable
+    local nested, nested_is_main
+    local co
+    co = coroutine.create(function()
+        inner, inner_is_main = coroutine.running()
+        inner_yieldable = coroutine.isyieldable()
+        local nested_co = coroutine.create(function()
+            nested, nested_is_main = coroutine.running()
+        end)
+        coroutine.resume(nested_co)
+        coroutine.yield(coroutine.running() == co)
+        error("stop")
+    end)
+
+    local ok1, same = coroutine.resume(co)
+    local after = coroutine.running()
+    local ok2 = coroutine.resume(co)
+    local after_error = coroutine.running()
+
+    return
+        type(main) == "thread" and is_main == true and
+        coroutine.isyieldable() == false and
+        inner == co and inner_is_main == false and inner_yieldable == true and
+        type(nested) == "thread" and nested ~= co and nested ~= main and
+        nested_is_main == false and
+        ok1 == true and same == true and after == main and
+        ok2 == false and after_error == main and
+        coroutine.isyieldable() == false
+end
+
 return
     test1() and
-    test2()
+    test2() and
+    test3()
@@ -0,0 +1,41 @@
+local function test1()
+    local function inspect()
+        local thread = coroutine.running()
+        local name, value = debug.getlocal(thread, 2, 2)
+        if name ~= "b" or value ~= 2 then
+            return false
+        end
+        if debug.setlocal(thread, 2, 2, 20) ~= "b" then
+            return false
+        end
+        return debug.getlocal(thread, 2, 3) == nil
+    end
+
+    local function caller(a)
+        local b = 2
+        local ok = inspect()
+        return ok and a == 1 and b == 20
+    end
+
+    return caller(1)
+end
+
+local function test2()
+    local function peek(n)
+        local name, value = debug.getlocal(coroutine.running(), 2, n)
+        return name, value
+    end
+
+    local co = coroutine.create(function(x)
+        local y = x * 2
+        local name, value = peek(2)
+        coroutine.yield(name, value)
+    end)
+
+    local _, name, value = coroutine.resume(co, 21)
+    return name == "y" and value == 42 and debug.getlocal(co, 1, 1) == "x"
+end
+
+return
+    test1() and
+    test2()
@@ -81,9 +81,91 @@ function test_break_scope()
     return true
 end
 
+function test_numeric_boundary()
+    local max, min = math.maxinteger, math.mininteger
+
+    local count = 0
+    for i = max - 2, max do
+        count = count + 1
+    end
+    if count ~= 3 then return false end
+
+    count = 0
+    for i = min + 2, min, -1 do
+        count = count + 1
+    end
+    if count ~= 3 then return false end
+
+    count = 0
+    for i = max, min, min do
+        count = count + 1
+    end
+    if count ~= 2 then return false end
+
+    -- Trip counts of 2^63 and more do not fit in a signed integer.
+    count = 0
+    for i = -1, max do
+        count = count + 1
+        if count > 3 then break end
+    end
+    if count ~= 4 then return false end
+
+    count = 0
+    for i = min, max do
+        count = count + 1
+        if count > 3 then break end
+    end
+    if count ~= 4 then return false end
+
+    local last
+    for i = 1, max, max // 2 do
+        last = i
+    end
+    if last ~= max then return false end
+
+    count = 0
+    for i = max, max - 1 do
+        count = count + 1
+    end
+    for i = 1, 0 do
+        count = count + 1
+    end
+    for i = 1, 0 / 0 do
+        count = count + 1
+    end
+    if count ~= 0 then return false end
+
+    -- Float limits are clamped to the integer range, and rounded towards the start.
+    count = 0
+    for i = max - 1, 1e100 do
+        count = count + 1
+    end
+    for i = 1, 3.5 do
+        count = count + 1
+    end
+    for i = -1, -3.5, -1 do
+        count = count + 1
+    end
+    if count ~= 8 then return false end
+
+    local sum = 0
+    for i = 10, 1, -3 do
+        sum = sum + i
+    end
+    for i = 1.0, 2.0, 0.5 do
+        sum = sum + i
+    end
+
+    return
+        sum == 26.5 and
+        not pcall(function() for i = 1, 10, 0 do end end) and
+        not pcall(function() for i = 1.0, 10, 0.0 do end end)
+end
+
 return
     test_generic() and
     test_numeric() and
     test_numeric_closure() and
     test_generic_closure() and
-    test_break_scope()
+    test_break_scope() and
+    test_numeric_boundary()
@@ -0,0 +1,201 @@
+local function test_index_table()
+    local base = { a = 1 }
+    local middle = setmetatable({ b = 2 }, { __index = base })
+    local t = setmetatable({ c = 3 }, { __index = middle })
+    return t.a == 1 and t.b == 2 and t.c == 3 and t.d == nil and rawequal(getmetatable(t).__index, middle)
+end
+
+local function test_index_function()
+    local calls = 0
+    local t = setmetatable({ present = true }, {
+        __index = function(self, key)
+            calls = calls + 1
+            return key .. "!"
+        end
+    })
+    local native = setmetatable({}, { __index = type })
+    return t.present == true and t.missing == "missing!" and t[1] == "1!" and calls == 2 and
+        native.anything == "table"
+end
+
+local function test_methods()
+    local class = {}
+    class.__index = class
+    function class.new(v)
+        return setmetatable({ v = v }, class)
+    end
+    function class:get()
+        return self.v
+    end
+    local obj = class.new(5)
+    return obj:get() == 5
+end
+
+local function test_newindex()
+    local store = {}
+    local log = {}
+    local proxy = setmetatable({ existing = 1 }, { __newindex = store })
+    proxy.existing = 2
+    proxy.new = 3
+
+    local logged = setmetatable({}, {
+        __newindex = function(t, k, v)
+            log[#log + 1] = k
+        end
+    })
+    logged.x = 1
+    logged.y = 2
+
+    return proxy.existing == 2 and rawequal(proxy.new, nil) and store.new == 3 and
+        logged.x == nil and log[1] == "x" and log[2] == "y"
+end
+
+local function test_index_loop()
+    local a = {}
+    local b = {}
+    setmetatable(a, { __index = b })
+    setmetatable(b, { __index = a })
+    local ok = pcall(function() return a.missing end)
+    local ok2 = pcall(function() a.missing = 1 end)
+    return not ok and ok2
+end
+
+local function test_raw()
+    local t = setmetatable({ present = 1 }, {
+        __index = function() return "default" end,
+        __newindex = function() error("no new keys") end,
+    })
+    rawset(t, "added", 2)
+    local ok = pcall(function() t.other = 3 end)
+    return t.missing == "default" and rawget(t, "missing") == nil and rawget(t, "present") == 1 and
+        t.added == 2 and not ok and rawget(t, "other") == nil and rawlen({ 1, 2, 3 }) == 3 and
+        rawlen("abcd") == 4
+end
+
+local function test_arithmetic()
+    local vec = {}
+    vec.__index = vec
+    local function new(x, y)
+        return setmetatable({ x = x, y = y }, vec)
+    end
+    vec.__add = function(a, b) return new(a.x + b.x, a.y + b.y) end
+    vec.__sub = function(a, b) return new(a.x - b.x, a.y - b.y) end
+    vec.__mul = function(a, b)
+        if type(a) == "number" then
+            return new(a * b.x, a * b.y)
+        end
+        return new(a.x * b, a.y * b)
+    end
+    vec.__div = function(a, b) return new(a.x / b, a.y / b) end
+    vec.__idiv = function(a, b) return new(a.x // b, a.y // b) end
+    vec.__mod = function(a, b) return new(a.x % b, a.y % b) end
+    vec.__pow = function(a, b) return new(a.x ^ b, a.y ^ b) end
+    vec.__unm = function(a, b) return new(-a.x, -a.y) end
+
+    local bits = setmetatable({}, {
+        __band = function() return "band" end,
+        __bor = function() return "bor" end,
+        __bxor = function() return "bxor" end,
+        __shl = function() return "shl" end,
+        __shr = function() return "shr" end,
+        __bnot = function(a, b) return rawequal(a, b) end,
+    })
+
+    local a = new(1, 2)
+    local b = new(3, 4)
+    local sum = a + b
+    local diff = b - a
+    local scaled = 2 * a
+    local scaled2 = a * 3
+    local neg = -a
+    local q = b / 2
+    local iq = b // 2
+    local m = b % 2
+    local p = a ^ 2
+    local ok = pcall(function() return {} + 1 end)
+
+    return sum.x == 4 and sum.y == 6 and diff.x == 2 and diff.y == 2 and scaled.x == 2 and
+        scaled2.y == 6 and neg.x == -1 and neg.y == -2 and q.x == 1.5 and iq.y == 2 and
+        m.x == 1 and p.y == 4 and not ok and (bits & 1) == "band" and (1 | bits) == "bor" and
+        (bits ~ bits) == "bxor" and (bits << 1) == "shl" and (bits >> 1) == "shr" and ~bits == true
+end
+
+local function test_protected()
+    local t = setmetatable({}, { __metatable = "locked" })
+    local ok = pcall(setmetatable, t, {})
+    return getmetatable(t) == "locked" and not ok
+end
+
+function test_call()
+    local calls = 0
+    local callable = setmetatable({}, {
+        __call = function(self, a, b)
+            calls = calls + 1
+            return self, a + b
+        end
+    })
+
+    local s, sum = callable(1, 2)
+    local function tail()
+        return callable(3, 4)
+    end
+    local _, tail_sum = tail()
+    local ok, _, pcall_sum = pcall(callable, 5, 6)
+
+    local iterations = 0
+    local iter = setmetatable({}, {
+        __call = function(self, state, i)
+            if i < 3 then
+                return i + 1
+            end
+        end
+    })
+    for i in iter, nil, 0 do
+        iterations = iterations + 1
+    end
+
+    local bad_ok, err = pcall(function() local t = {} t() end)
+    return
+        s == callable and sum == 3 and
+        tail_sum == 7 and
+        ok and pcall_sum == 11 and
+        calls == 3 and
+        iterations == 3 and
+        not bad_ok
+end
+
+function test_meta_depth()
+    local recursive = setmetatable({}, { __index = function(t, k) return t[k] end })
+    local a, b = {}, {}
+    setmetatable(a, { __index = function(t, k) return b[k] end })
+    setmetatable(b, { __index = function(t, k) return a[k] end })
+
+    -- The depth is restored after each error, so the guard fires the same way every time.
+    for i = 1, 3 do
+        if pcall(function() return recursive.x end) then return false end
+        if pcall(function() return a.x end) then return false end
+    end
+
+    local function chain(n)
+        local t = { value = n }
+        for i = 1, n do
+            local inner = t
+            t = setmetatable({}, { __index = function(_, k) return inner[k] end })
+        end
+        return t
+    end
+
+    return chain(100).value == 100
+end
+
+return
+    test_index_table() and
+    test_index_function() and
+    test_methods() and
+    test_newindex() and
+    test_raw() and
+    test_arithmetic() and
+    test_index_loop() and
+    test_protected() and
+    test_call() and
+    test_meta_depth()
@@ -57,10 +57,46 @@ local function test6()
     return a == 1 and b == 2 and c == 3
 end
 
+local function test7()
+    local a, b = 1, 2
+    a, b = b, a
+
+    local t = {1, 2}
+    local i, j = 1, 2
+    t[i], t[j] = t[j], t[i]
+
+    g1, g2 = "a", "b"
+    g1, g2 = g2, g1
+
+    i, t[i] = i + 1, 20
+
+    return a == 2 and b == 1 and t[1] == 20 and t[2] == 1 and g1 == "b" and g2 == "a" and i == 2
+end
+
+local function test8()
+    local a, b, c = 1, 2, 3
+    a, b, c = 4
+    return a == 4 and b == nil and c == nil
+end
+
+local function test9()
+    local calls = 0
+    local function f()
+        calls = calls + 1
+    end
+
+    local a, b = 1, 2
+    a, b = 3, 4, 5, f()
+    return a == 3 and b == 4 and calls == 1
+end
+
 return
     test1() and
     test2() and
     test3() and
     test4() and
     test5() and
-    test6()
+    test6() and
+    test7() and
+    test8() and
+    test9()
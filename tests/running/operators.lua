@@ -142,6 +142,55 @@ function test17()
         "0x10" + "4" == 20
 end
 
+function test18()
+    local max = math.maxinteger
+    local min = math.mininteger
+    return
+        max + 1 == min and
+        min - 1 == max and
+        max * 2 == -2 and
+        min * -1 == min and
+        -min == min and
+        math.type(max + 1) == "integer"
+end
+
+function test19()
+    local big = 1 << 53
+    return
+        1 == 1.0 and
+        1.0 == 1 and
+        big == 2^53 and
+        big + 1 ~= 2^53 and
+        big + 1 ~= 2^53 + 1 and
+        big + 1 > 2^53 and
+        2^53 < big + 1 and
+        big + 1 >= 2^53 and
+        not (big + 1 <= 2^53) and
+        math.maxinteger ~= 2^63 and
+        math.maxinteger < 2^63 and
+        not (2^63 <= math.maxinteger) and
+        math.mininteger == -2^63 and
+        math.mininteger <= -2^63 and
+        math.mininteger > -2^63 - 2^11 and
+        1 < 1.5 and 1.5 < 2 and not (2 < 1.5) and
+        -1 > -1.5 and -2 < -1.5 and
+        not (1 < 0/0) and not (1 > 0/0) and not (1 == 0/0) and
+        not (1 <= 0/0) and not (1 >= 0/0) and 1 ~= 0/0 and
+        1 + 1 ~= 1 + 2
+end
+
+function test20()
+    return
+        3 // 2 == 1 and math.type(3 // 2) == "integer" and
+        -7 // 2 == -4 and math.type(-7 // 2) == "integer" and
+        3 / 2 == 1.5 and math.type(4 / 2) == "float" and
+        math.type(3.0 // 2) == "float" and
+        math.type(3 % 2.0) == "float" and
+        math.type(1 + 2.0) == "float" and
+        math.type(2.0 * 3) == "float" and
+        math.type(1 - 1) == "integer"
+end
+
 return
     test1() and
     test2() and
@@ -159,4 +208,7 @@ return
     test14() and
     test15() and
     test16() and
-    test17()
+    test17() and
+    test18() and
+    test19() and
+    test20()
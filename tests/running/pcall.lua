@@ -41,6 +41,27 @@ function test2()
         e4 == true and r4 == nil and s4 == "dead"
 end
 
+function test3()
+    local t = {}
+    local r1, e1 = pcall(error, t)
+    local r2, e2 = pcall(error, "leveled", 2)
+    local r3, e3 = pcall(error, "unleveled", 0)
+    local r4, e4 = pcall(error)
+    local r5 = pcall(error, "bad level", {})
+    local r6, a, b, c = pcall(function(...) return ... end, 1, 2, 3)
+    local r7, r8, e8 = pcall(pcall, error, "inner")
+
+    return
+        r1 == false and e1 == t and
+        r2 == false and e2 == "leveled" and
+        r3 == false and e3 == "unleveled" and
+        r4 == false and e4 == nil and
+        r5 == false and
+        r6 == true and a == 1 and b == 2 and c == 3 and
+        r7 == true and r8 == false and e8 == "inner"
+end
+
 return
     test1() and
-    test2()
+    test2() and
+    test3()
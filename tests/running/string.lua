@@ -35,5 +35,171 @@ function test_len()
         string.len(-2147483648) == 11
 end
 
+function test_sub()
+    return
+        is_err(function() return string.sub(nil) end) and
+        is_err(function() return string.sub({}, 1) end) and
+        is_err(function() return string.sub("abc", 1.5) end) and
+        string.sub("hello", 2, 4) == "ell" and
+        string.sub("hello", 2) == "ello" and
+        string.sub("hello", -3) == "llo" and
+        string.sub("hello", -3, -2) == "ll" and
+        string.sub("hello", 0) == "hello" and
+        string.sub("hello", 4, 2) == "" and
+        string.sub("hello", 2, 100) == "ello" and
+        string.sub("hello", -100, 2) == "he" and
+        string.sub(12345, 2, 3) == "23" and
+        string.sub(-1.5, 1, 2) == "-1" and
+        string.len(100) == 3
+end
+
+function test_tostring()
+    return
+        is_err(function() return tostring() end) and
+        tostring(nil) == "nil" and
+        tostring(true) == "true" and
+        tostring(false) == "false" and
+        tostring(12) == "12" and
+        tostring("abc") == "abc" and
+        tostring(true) .. tostring(nil) == "truenil"
+end
+
+function test_tostring_metamethod()
+    local named = setmetatable({}, { __tostring = function(t) return "named" end })
+    local numbered = setmetatable({}, { __tostring = function(t) return 42 end })
+    local bad = setmetatable({}, { __tostring = function(t) return {} end })
+    return
+        tostring(named) == "named" and
+        tostring(numbered) == "42" and
+        is_err(function() return tostring(bad) end) and
+        string.format("%s", named) == "named" and
+        string.format("[%s %d %s]", named, 7, numbered) == "[named 7 42]" and
+        string.format("%s%s", named, named) == "namednamed" and
+        is_err(function() return string.format("%s", bad) end)
+end
+
+function test_format()
+    local t = {}
+    local s = string.format("%s", t)
+    return
+        string.format("%s and %s", "a", 1) == "a and 1" and
+        string.format("%d%%", 50) == "50%" and
+        string.format("%s %s", nil, true) == "nil true" and
+        string.sub(s, 1, 9) == "table: 0x" and
+        s == tostring(t) and
+        not pcall(string.format, "%d", {}) and
+        not pcall(string.format, "%s")
+end
+
+function test_case()
+    return
+        is_err(function() return string.upper(nil) end) and
+        string.upper("Hello, World!") == "HELLO, WORLD!" and
+        string.lower("Hello, World!") == "hello, world!" and
+        string.upper("caf\xe9") == "CAF\xe9" and
+        string.lower(12) == "12"
+end
+
+function test_rep()
+    return
+        is_err(function() return string.rep("ab") end) and
+        is_err(function() return string.rep("ab", "x") end) and
+        is_err(function() return string.rep("ab", 1 << 40) end) and
+        string.rep("ab", 3) == "ababab" and
+        string.rep("ab", 3, ",") == "ab,ab,ab" and
+        string.rep("ab", 1, ",") == "ab" and
+        string.rep("ab", 0) == "" and
+        string.rep("ab", -1) == "" and
+        string.rep(1, 2) == "11"
+end
+
+function test_byte_char()
+    local a, b, c = string.byte("abc", 1, -1)
+    return
+        string.byte("abc") == 97 and
+        string.byte("abc", -1) == 99 and
+        a == 97 and b == 98 and c == 99 and
+        string.byte("abc", 10) == nil and
+        string.byte("abc", 3, 1) == nil and
+        string.byte("\xff") == 255 and
+        string.char() == "" and
+        string.char(104, 105) == "hi" and
+        string.char(0, 255) == "\0\xff" and
+        string.len(string.char(0, 255)) == 2 and
+        is_err(function() return string.char(256) end) and
+        is_err(function() return string.char(-1) end) and
+        string.char(string.byte("luster", 1, -1)) == "luster"
+end
+
+function test_find()
+    local s, e, k, v = string.find("  key=val", "(%w+)=(%w+)")
+    return
+        s == 3 and e == 9 and k == "key" and v == "val" and
+        string.find("hello world", "o w") == 5 and
+        select(2, string.find("hello world", "o w")) == 7 and
+        string.find("hello", "l", 1) == 3 and
+        string.find("hello", "l", 4) == 4 and
+        string.find("hello", "l", -1) == nil and
+        string.find("hello", "xyz") == nil and
+        string.find("hello", "", 10) == nil and
+        string.find("hello", "") == 1 and
+        string.find("a.b", ".", 1, true) == 2 and
+        string.find("a+b", "+", 1, true) == 2 and
+        string.find("abc", "()b()") == 2 and
+        select(3, string.find("abc", "()b()")) == 2 and
+        select(4, string.find("abc", "()b()")) == 3 and
+        is_err(function() return string.find("abc", "[a") end) and
+        is_err(function() return string.find("abc", "%") end) and
+        is_err(function() return string.find("abc", "(a") end) and
+        is_err(function() return string.match("abc", "a)") end) and
+        is_err(function() return string.find("abc", "%1") end)
+end
+
+function test_match()
+    local k, v = string.match("key=val", "(%w+)=(%w+)")
+    return
+        k == "key" and v == "val" and
+        string.match("hello 123 world", "%d+") == "123" and
+        string.match("hello", "^h") == "h" and
+        string.match("hello", "^e") == nil and
+        string.match("hello", "o$") == "o" and
+        string.match("hello", "l$") == nil and
+        string.match("a$b", "a$b") == "a$b" and
+        string.match("  trim  ", "^%s*(.-)%s*$") == "trim" and
+        string.match("aaa", "a-") == "" and
+        string.match("aaa", "a-$") == "aaa" and
+        string.match("aaab", "a*b") == "aaab" and
+        string.match("b", "a*b") == "b" and
+        string.match("b", "a+b") == nil and
+        string.match("ab", "a?b") == "ab" and
+        string.match("b", "a?b") == "b" and
+        select(2, string.match("x = [==[y]==]", "%[(=*)%[(.-)%]%1%]")) == "y" and
+        string.match("hex ff!", "%x+!") == "ff!" and
+        string.match("ABCdef", "%u+") == "ABC" and
+        string.match("ABCdef", "%U+") == "def" and
+        string.match("a-z", "[%-]") == "-" and
+        string.match("foo_bar9", "[_%a]+") == "foo_bar" and
+        string.match("foo]", "[]]") == "]" and
+        string.match("abc123", "[^%a]+") == "123" and
+        string.match("abcxyz", "[b-y]+") == "bcxy" and
+        string.match("f(a(b)c)d", "%b()") == "(a(b)c)" and
+        string.match("THE (quick) fox", "%f[%a]%a+", 5) == "quick" and
+        string.match("say \"hi\" or 'no'", "([\"'])(.-)%1") == "\"" and
+        select(2, string.match("say \"hi\" or 'no'", "([\"'])(.-)%1")) == "hi" and
+        string.match("hello", ".l", -3) == "ll" and
+        string.match("hello", "()ll()") == 3 and
+        string.match(2024, "%d%d$") == "24" and
+        is_err(function() return string.match("x", string.rep("(", 40) .. "x") end)
+end
+
 return test_concat() and
-       test_len()
+       test_len() and
+       test_sub() and
+       test_tostring() and
+       test_tostring_metamethod() and
+       test_format() and
+       test_case() and
+       test_rep() and
+       test_byte_char() and
+       test_find() and
+       test_match()
@@ -94,9 +94,30 @@ function test5()
     return t[1] == 1 and t[2] == 2 and t[3] == 3 and t.a == "a"
 end
 
+function test6()
+    local t = {}
+    local big = 1 << 53
+
+    t[1] = "one"
+    t[2^53] = "float"
+    t[big + 1] = "integer"
+    t[2^63] = "2^63"
+    t[math.maxinteger] = "maxinteger"
+
+    return
+        t[1.0] == "one" and
+        t[big] == "float" and
+        t[2^53 + 1] == "float" and
+        t[big + 1] == "integer" and
+        t[2^63] == "2^63" and
+        t[math.maxinteger] == "maxinteger" and
+        t[math.maxinteger + 0.0] == "2^63"
+end
+
 return
     test1() and
     test2() and
     test3() and
     test4() and
-    test5()
+    test5() and
+    test6()
@@ -0,0 +1,99 @@
+use gc_arena::Gc;
+use luster::{Lua, String, Table, Value};
+
+#[test]
+fn rope_concat() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let chunk = String::new(mc, &[b'a'; 200]);
+        let mut s = String::new_static(b"");
+        let mut expected = Vec::new();
+        for i in 0..100 {
+            s = String::concat(
+                mc,
+                &[Value::String(s), Value::String(chunk), Value::Integer(i)],
+            )
+            .unwrap();
+            expected.extend_from_slice(&[b'a'; 200]);
+            expected.extend_from_slice(i.to_string().as_bytes());
+        }
+
+        let rope = match s {
+            String::Rope(rope) => rope,
+            _ => panic!("long concatenation did not produce a rope"),
+        };
+        assert_eq!(s.len(), expected.len() as i64);
+        assert!(!rope.is_flattened());
+
+        assert_eq!(s.as_bytes(), &expected[..]);
+        assert!(rope.is_flattened());
+
+        // A rope hashes and compares the same as a flat string with the same contents.
+        let table = Table::new(mc);
+        table.set(mc, String::new(mc, &expected), 1).unwrap();
+        assert_eq!(table.get(s), Value::Integer(1));
+
+        let short = String::concat(mc, &[Value::String(chunk), Value::Integer(1)]).unwrap();
+        assert!(matches!(short, String::Long(_)));
+    });
+}
+
+#[test]
+fn interned_strings_pruned() {
+    let mut lua = Lua::new();
+
+    lua.mutate(|mc, root| {
+        let a = root.interned_strings.new_string(mc, b"interned");
+        let b = root.interned_strings.new_string(mc, b"interned");
+        match (a, b) {
+            (String::Short8(_, a), String::Short8(_, b)) => assert!(Gc::ptr_eq(a, b)),
+            _ => panic!("short strings were not interned as `Short8`"),
+        }
+        root.registry.set(mc, 1, a).unwrap();
+    });
+
+    let mut max_len = 0;
+    for i in 0..5000 {
+        let len = lua.mutate(move |mc, root| {
+            for j in 0..20 {
+                root.interned_strings
+                    .new_string(mc, format!("garbage {} {}", i, j).as_bytes());
+            }
+            root.interned_strings.len()
+        });
+        max_len = max_len.max(len);
+    }
+    // Without pruning, every one of the 100,000 strings would still have an entry.
+    assert!(max_len < 100_000 / 8);
+
+    lua.mutate(|mc, root| {
+        let kept = match root.registry.get(1) {
+            Value::String(String::Short8(_, kept)) => kept,
+            _ => panic!("registry entry was lost"),
+        };
+        match root.interned_strings.new_string(mc, b"interned") {
+            String::Short8(_, found) => assert!(Gc::ptr_eq(found, kept)),
+            _ => panic!("short strings were not interned as `Short8`"),
+        }
+    });
+}
+
+#[test]
+fn intern() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let a = root.intern(mc, b"a longer string than fits in a short string");
+        let b = root.intern(mc, b"a longer string than fits in a short string");
+        assert!(String::ptr_eq(a, b));
+        assert!(Value::String(a).raw_eq(Value::String(b)));
+
+        let c = root.intern(mc, b"a different string");
+        assert!(!String::ptr_eq(a, c));
+        assert!(!Value::String(a).raw_eq(Value::String(c)));
+
+        // A string that was not interned is still equal by contents, but is its own allocation.
+        let d = String::new(mc, b"a longer string than fits in a short string");
+        assert!(!String::ptr_eq(a, d));
+        assert!(Value::String(a).raw_eq(Value::String(d)));
+    });
+}
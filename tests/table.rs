@@ -0,0 +1,661 @@
+use gc_arena::{make_arena, ArenaParameters, Collect};
+use gc_sequence::{self as sequence, SequenceExt, SequenceResultExt};
+
+use luster::{
+    compile, Closure, Error, Function, Lua, StaticError, String, Table, ThreadSequence, Value,
+    WeakMode,
+};
+
+#[test]
+fn to_array_vec() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let dense = Table::new(mc);
+        for i in 1..=5i64 {
+            dense.set(mc, i, i * 10).unwrap();
+        }
+        dense
+            .set(mc, luster::String::new_static(b"key"), true)
+            .unwrap();
+        assert_eq!(
+            dense.to_array_vec(),
+            vec![
+                Value::Integer(10),
+                Value::Integer(20),
+                Value::Integer(30),
+                Value::Integer(40),
+                Value::Integer(50),
+            ]
+        );
+
+        let holey = Table::new(mc);
+        holey.set(mc, 1i64, 1i64).unwrap();
+        holey.set(mc, 2i64, 2i64).unwrap();
+        holey.set(mc, 4i64, 4i64).unwrap();
+        assert_eq!(
+            holey.to_array_vec(),
+            vec![Value::Integer(1), Value::Integer(2)]
+        );
+
+        assert!(Table::new(mc).to_array_vec().is_empty());
+    });
+}
+
+#[test]
+fn pointer_id() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let a = Table::new(mc);
+        let b = Table::new(mc);
+        let same = Value::Table(a);
+        assert!(Value::Table(a).pointer_id().is_some());
+        assert_eq!(Value::Table(a).pointer_id(), same.pointer_id());
+        assert_ne!(Value::Table(a).pointer_id(), Value::Table(b).pointer_id());
+
+        let print = root.globals.get(String::new_static(b"print"));
+        assert!(print.pointer_id().is_some());
+        assert_ne!(print.pointer_id(), Value::Table(a).pointer_id());
+        assert!(Value::Thread(root.main_thread).pointer_id().is_some());
+
+        assert_eq!(Value::Nil.pointer_id(), None);
+        assert_eq!(Value::Integer(1).pointer_id(), None);
+        assert_eq!(Value::String(String::new(mc, b"str")).pointer_id(), None);
+    });
+}
+
+#[test]
+fn raw_eq() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        assert!(Value::Integer(1).raw_eq(Value::Number(1.0)));
+        assert!(Value::Number(1.0).raw_eq(Value::Integer(1)));
+        assert!(!Value::Integer(1).raw_eq(Value::Number(1.5)));
+        assert!(!Value::Number(f64::NAN).raw_eq(Value::Number(f64::NAN)));
+
+        let a = Value::String(String::new(mc, b"content"));
+        let b = Value::String(String::new(mc, b"content"));
+        assert!(a.raw_eq(b));
+        assert!(!a.raw_eq(Value::String(String::new(mc, b"other"))));
+        assert!(!a.raw_eq(Value::Integer(1)));
+
+        let t = Value::Table(Table::new(mc));
+        assert!(t.raw_eq(t));
+        assert!(!t.raw_eq(Value::Table(Table::new(mc))));
+    });
+}
+
+#[test]
+fn array_and_map_parts() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let table = Table::new(mc);
+        for i in 1..=100i64 {
+            table.set(mc, i, i).unwrap();
+        }
+        assert!(table.0.read().array_len() >= 100);
+        assert_eq!(table.0.read().map_len(), 0);
+        assert_eq!(table.length(), 100);
+
+        let sparse = Table::new(mc);
+        for i in 0..20 {
+            sparse.set(mc, 1i64 << (i * 3), true).unwrap();
+        }
+        assert!(sparse.0.read().array_len() < 16);
+        assert!(sparse.0.read().map_len() >= 16);
+        for i in 0..20 {
+            assert_eq!(sparse.get(1i64 << (i * 3)), Value::Boolean(true));
+        }
+
+        // Float keys with an integer value share the integer's slot.
+        assert_eq!(table.get(50.0), Value::Integer(50));
+        table.set(mc, 50.0, false).unwrap();
+        assert_eq!(table.get(50i64), Value::Boolean(false));
+
+        let name = String::new_static(b"name");
+        sparse.set(mc, name, 1i64).unwrap();
+        let before = sparse.0.read().map_len();
+        assert_eq!(sparse.set(mc, name, Value::Nil).unwrap(), Value::Integer(1));
+        assert_eq!(sparse.0.read().map_len(), before - 1);
+        assert_eq!(sparse.get(name), Value::Nil);
+
+        table.set(mc, 100i64, Value::Nil).unwrap();
+        assert_eq!(table.get(100i64), Value::Nil);
+        assert_eq!(table.length(), 99);
+    });
+}
+
+#[test]
+fn length_border() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        assert_eq!(Table::new(mc).length(), 0);
+
+        let dense = Table::new(mc);
+        for i in 1..=10i64 {
+            dense.set(mc, i, i).unwrap();
+        }
+        assert_eq!(dense.length(), 10);
+
+        // Removing the last element leaves a trailing nil in the array part.
+        dense.set(mc, 10i64, Value::Nil).unwrap();
+        assert_eq!(dense.length(), 9);
+
+        let holey = Table::new(mc);
+        for i in 1..=8i64 {
+            holey.set(mc, i, i).unwrap();
+        }
+        holey.set(mc, 3i64, Value::Nil).unwrap();
+        holey.set(mc, 8i64, Value::Nil).unwrap();
+        let border = holey.length();
+        assert!(border == 2 || border == 7, "{} is not a border", border);
+
+        let hashed = Table::new(mc);
+        hashed.set(mc, 1i64, true).unwrap();
+        hashed.set(mc, String::new_static(b"x"), true).unwrap();
+        assert_eq!(hashed.length(), 1);
+    });
+}
+
+#[test]
+fn metatables() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let table = Table::new(mc);
+        assert_eq!(table.metatable(), None);
+        assert_eq!(Value::Table(table).metatable(root.metatables), None);
+
+        let mt = Table::new(mc);
+        assert_eq!(table.set_metatable(mc, Some(mt)), None);
+        assert_eq!(table.metatable(), Some(mt));
+        assert_eq!(Value::Table(table).metatable(root.metatables), Some(mt));
+        assert_eq!(table.set_metatable(mc, None), Some(mt));
+        assert_eq!(table.metatable(), None);
+
+        let s = Value::String(String::new_static(b"s"));
+        assert_eq!(s.metatable(root.metatables), None);
+        let string_mt = Table::new(mc);
+        root.metatables.set(mc, "string", Some(string_mt));
+        assert_eq!(s.metatable(root.metatables), Some(string_mt));
+        assert_eq!(Value::Integer(1).metatable(root.metatables), None);
+        assert_eq!(root.metatables.set(mc, "string", None), Some(string_mt));
+        assert_eq!(s.metatable(root.metatables), None);
+    });
+}
+
+#[test]
+fn string_metatable_in_vm() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            let string_mt = Table::new(mc);
+            string_mt
+                .set(
+                    mc,
+                    String::new_static(b"__index"),
+                    root.globals.get(String::new_static(b"string")),
+                )
+                .unwrap();
+            root.metatables.set(mc, "string", Some(string_mt));
+
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        local s = "abc"
+                        local co = coroutine.create(function() return s:len() end)
+                        local _, len = coroutine.resume(co)
+                        return ("x"):upper() == "X" and s.missing == nil and len == 3 and
+                            not pcall(function() local n = 1; return n.x end)
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map_ok(|res| assert_eq!(res, vec![Value::Boolean(true)])))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    Ok(())
+}
+
+make_arena!(TableArena, Table);
+
+#[test]
+fn metatable_survives_collection() {
+    let mut arena = TableArena::new(ArenaParameters::default(), |mc| {
+        let table = Table::new(mc);
+        let mt = Table::new(mc);
+        mt.set(
+            mc,
+            String::new_static(b"tag"),
+            String::new(mc, b"metatable"),
+        )
+        .unwrap();
+        table.set_metatable(mc, Some(mt));
+        table
+    });
+
+    arena.collect_all();
+    arena.mutate(|mc, table| {
+        // Replace the metatable's contents after a full collection, so the barrier must keep the
+        // new string alive through the next one.
+        let mt = table.metatable().unwrap();
+        mt.set(mc, 1, String::new(mc, b"replaced")).unwrap();
+        Table::new(mc);
+    });
+    arena.collect_all();
+
+    arena.mutate(|_, table| {
+        let mt = table.metatable().unwrap();
+        match (mt.get(String::new_static(b"tag")), mt.get(1)) {
+            (Value::String(tag), Value::String(replaced)) => {
+                assert_eq!(tag.as_bytes(), b"metatable");
+                assert_eq!(replaced.as_bytes(), b"replaced");
+            }
+            v => panic!("metatable contents lost: {:?}", v),
+        }
+    });
+}
+
+#[test]
+fn deep_clone() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let inner = Table::new(mc);
+        inner.set(mc, 1, 10).unwrap();
+        let name = String::new(mc, b"name");
+        let outer = Table::new(mc);
+        outer.set(mc, String::new_static(b"inner"), inner).unwrap();
+        outer.set(mc, String::new_static(b"again"), inner).unwrap();
+        outer.set(mc, String::new_static(b"name"), name).unwrap();
+        let mt = Table::new(mc);
+        outer.set_metatable(mc, Some(mt));
+
+        let clone = outer.deep_clone(mc, false);
+        assert_ne!(clone, outer);
+        assert_eq!(clone.metatable(), Some(mt));
+        assert_eq!(clone.get(String::new_static(b"name")), Value::String(name));
+
+        let cloned_inner = match clone.get(String::new_static(b"inner")) {
+            Value::Table(t) => t,
+            v => panic!("unexpected value {:?}", v),
+        };
+        assert_ne!(cloned_inner, inner);
+        assert_eq!(
+            clone.get(String::new_static(b"again")),
+            Value::Table(cloned_inner)
+        );
+        assert_eq!(cloned_inner.get(1), Value::Integer(10));
+
+        cloned_inner.set(mc, 1, 20).unwrap();
+        clone
+            .set(mc, String::new_static(b"name"), Value::Nil)
+            .unwrap();
+        assert_eq!(inner.get(1), Value::Integer(10));
+        assert_eq!(outer.get(String::new_static(b"name")), Value::String(name));
+
+        let with_mt = outer.deep_clone(mc, true);
+        let cloned_mt = with_mt.metatable().unwrap();
+        assert_ne!(cloned_mt, mt);
+
+        let cyclic = Table::new(mc);
+        cyclic.set(mc, String::new_static(b"self"), cyclic).unwrap();
+        cyclic.set_metatable(mc, Some(cyclic));
+        let clone = cyclic.deep_clone(mc, true);
+        assert_ne!(clone, cyclic);
+        assert_eq!(clone.get(String::new_static(b"self")), Value::Table(clone));
+        assert_eq!(clone.metatable(), Some(clone));
+    });
+}
+
+fn visit<'gc>(table: Table<'gc>) -> Vec<Value<'gc>> {
+    let mut seen = Vec::new();
+    let mut key = None;
+    while let Some((k, v)) = table.next_entry(key) {
+        assert_ne!(v, Value::Nil);
+        assert_eq!(table.get(k), v);
+        seen.push(k);
+        key = Some(k);
+    }
+    seen
+}
+
+#[test]
+fn next_entry() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        assert_eq!(Table::new(mc).next_entry(None), None);
+
+        let table = Table::new(mc);
+        for i in 1..=10i64 {
+            table.set(mc, i, i * 2).unwrap();
+        }
+        table.set(mc, 4i64, Value::Nil).unwrap();
+        table.set(mc, 100i64, 1).unwrap();
+        table.set(mc, 1.5, 2).unwrap();
+        for name in &[&b"a"[..], b"b", b"c", b"d"] {
+            table.set(mc, String::new(mc, name), true).unwrap();
+        }
+
+        let seen = visit(table);
+        assert_eq!(seen.len(), 9 + 1 + 1 + 4);
+        for (i, a) in seen.iter().enumerate() {
+            for b in &seen[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+        assert!(!seen.contains(&Value::Integer(4)));
+        assert_eq!(visit(table), seen);
+
+        // Clearing each entry as it is visited still visits every entry.
+        let mut key = None;
+        let mut cleared = 0;
+        while let Some((k, _)) = table.next_entry(key) {
+            table.set(mc, k, Value::Nil).unwrap();
+            cleared += 1;
+            key = Some(k);
+        }
+        assert_eq!(cleared, seen.len());
+        assert_eq!(table.next_entry(None), None);
+
+        assert_eq!(table.next_entry(Some(Value::Boolean(true))), None);
+    });
+}
+
+#[test]
+fn next_entry_large_table() {
+    // Each step of a traversal must resume from the previous key directly, or visiting every entry
+    // of a table this size would take quadratic time.
+    const COUNT: usize = 100_000;
+
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let keys = (0..COUNT)
+            .map(|i| String::new(mc, i.to_string().as_bytes()))
+            .collect::<Vec<_>>();
+
+        let table = Table::new(mc);
+        for &key in &keys {
+            table.set(mc, key, true).unwrap();
+        }
+        assert_eq!(visit(table).len(), COUNT);
+
+        let weak = Table::new(mc);
+        let mt = Table::new(mc);
+        mt.set(mc, String::new_static(b"__mode"), String::new_static(b"k"))
+            .unwrap();
+        weak.set_metatable(mc, Some(mt));
+        for &key in &keys {
+            weak.set(mc, key, true).unwrap();
+        }
+        assert_eq!(visit(weak).len(), COUNT);
+    });
+}
+
+#[test]
+fn raw_access() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let fallback = Table::new(mc);
+        fallback
+            .set(mc, String::new_static(b"inherited"), 1)
+            .unwrap();
+        let mt = Table::new(mc);
+        mt.set(mc, String::new_static(b"__index"), fallback)
+            .unwrap();
+
+        let table = Table::new(mc);
+        table.set_metatable(mc, Some(mt));
+        table.raw_set(mc, 1, true).unwrap();
+        assert_eq!(table.raw_get(String::new_static(b"inherited")), Value::Nil);
+        assert_eq!(table.raw_get(1), Value::Boolean(true));
+        assert_eq!(table.raw_len(), 1);
+        assert!(!Value::Table(table).raw_eq(Value::Table(fallback)));
+    });
+}
+
+#[test]
+fn is_callable() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let function = root.globals.get(String::new_static(b"print"));
+        assert!(function.is_callable());
+
+        let plain = Table::new(mc);
+        assert!(!Value::Table(plain).is_callable());
+        assert!(!Value::Integer(1).is_callable());
+
+        let callable = Table::new(mc);
+        let metatable = Table::new(mc);
+        callable.set_metatable(mc, Some(metatable));
+        assert!(!Value::Table(callable).is_callable());
+        metatable
+            .set(mc, String::new_static(b"__call"), function)
+            .unwrap();
+        assert!(Value::Table(callable).is_callable());
+        assert_eq!(
+            Value::Table(callable).as_callable().map(Value::Function),
+            Some(function)
+        );
+    });
+}
+
+#[derive(Collect)]
+#[collect(empty_drop)]
+struct WeakRoot<'gc> {
+    weak: Table<'gc>,
+    // Holds the objects which should survive collection.
+    kept: Table<'gc>,
+}
+
+make_arena!(WeakArena, WeakRoot);
+
+fn weak_arena(mode: &'static [u8]) -> WeakArena {
+    WeakArena::new(ArenaParameters::default(), |mc| {
+        let weak = Table::new(mc);
+        let mt = Table::new(mc);
+        mt.set(mc, String::new_static(b"__mode"), String::new_static(mode))
+            .unwrap();
+        weak.set_metatable(mc, Some(mt));
+        WeakRoot {
+            weak,
+            kept: Table::new(mc),
+        }
+    })
+}
+
+#[test]
+fn weak_values() {
+    let mut arena = weak_arena(b"v");
+    arena.mutate(|mc, root| {
+        assert_eq!(root.weak.weak_mode(), Some(WeakMode::Values));
+        let kept = Table::new(mc);
+        root.kept.set(mc, 1, kept).unwrap();
+        root.weak.set(mc, 1, kept).unwrap();
+        root.weak.set(mc, 2, Table::new(mc)).unwrap();
+        // Strings and other plain values are never held weakly.
+        root.weak.set(mc, 3, String::new(mc, b"string")).unwrap();
+        // Keys are held strongly, so a table only referenced as a key keeps its entry.
+        root.weak.set(mc, Table::new(mc), true).unwrap();
+        assert_eq!(visit(root.weak).len(), 4);
+    });
+
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        assert_eq!(root.weak.get(1), root.kept.get(1));
+        assert_eq!(root.weak.get(2), Value::Nil);
+        assert!(matches!(root.weak.get(3), Value::String(s) if s.as_bytes() == b"string"));
+        assert_eq!(visit(root.weak).len(), 3);
+        assert_eq!(root.weak.length(), 1);
+    });
+
+    arena.mutate(|mc, root| {
+        root.kept.set(mc, 1, Value::Nil).unwrap();
+    });
+    arena.collect_all();
+    arena.mutate(|mc, root| {
+        assert_eq!(root.weak.get(1), Value::Nil);
+        assert_eq!(root.weak.length(), 0);
+        root.weak.prune(mc);
+        assert_eq!(root.weak.0.read().map_len(), 2);
+    });
+}
+
+#[test]
+fn weak_keys() {
+    let mut arena = weak_arena(b"k");
+    arena.mutate(|mc, root| {
+        assert_eq!(root.weak.weak_mode(), Some(WeakMode::Keys));
+        let kept = Table::new(mc);
+        root.kept.set(mc, 1, kept).unwrap();
+        root.weak.set(mc, kept, 1).unwrap();
+        root.weak.set(mc, Table::new(mc), 2).unwrap();
+
+        // A value which refers back to its own key does not keep the key alive.
+        let key = Table::new(mc);
+        let value = Table::new(mc);
+        value.set(mc, 1, key).unwrap();
+        root.weak.set(mc, key, value).unwrap();
+
+        // Values are held strongly for as long as their keys are alive.
+        root.weak.set(mc, 1, Table::new(mc)).unwrap();
+        assert_eq!(visit(root.weak).len(), 4);
+    });
+
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        let kept = root.kept.get(1);
+        assert_eq!(root.weak.get(kept), Value::Integer(1));
+        assert!(matches!(root.weak.get(1), Value::Table(_)));
+        assert_eq!(visit(root.weak).len(), 2);
+    });
+
+    arena.mutate(|mc, root| {
+        root.kept.set(mc, 1, Value::Nil).unwrap();
+    });
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        assert_eq!(visit(root.weak), vec![Value::Integer(1)]);
+    });
+}
+
+#[test]
+fn weak_keys_and_values() {
+    let mut arena = weak_arena(b"kv");
+    arena.mutate(|mc, root| {
+        assert_eq!(root.weak.weak_mode(), Some(WeakMode::KeysAndValues));
+        let (a, b) = (Table::new(mc), Table::new(mc));
+        root.kept.set(mc, 1, a).unwrap();
+        root.kept.set(mc, 2, b).unwrap();
+        // Both alive.
+        root.weak.set(mc, a, b).unwrap();
+        // The value dies.
+        root.weak.set(mc, b, Table::new(mc)).unwrap();
+        // The key dies.
+        root.weak.set(mc, Table::new(mc), a).unwrap();
+        assert_eq!(visit(root.weak).len(), 3);
+    });
+
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        let (a, b) = (root.kept.get(1), root.kept.get(2));
+        assert_eq!(root.weak.get(a), b);
+        assert_eq!(root.weak.get(b), Value::Nil);
+        assert_eq!(visit(root.weak), vec![a]);
+    });
+
+    arena.mutate(|mc, root| {
+        root.kept.set(mc, 2, Value::Nil).unwrap();
+    });
+    arena.collect_all();
+    arena.mutate(|_, root| {
+        assert!(visit(root.weak).is_empty());
+    });
+}
+
+#[test]
+fn weak_mode_changes() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        let table = Table::new(mc);
+        for i in 1..=4i64 {
+            table.set(mc, i, i * 10).unwrap();
+        }
+        table.set(mc, String::new_static(b"key"), true).unwrap();
+
+        let mt = Table::new(mc);
+        mt.set(mc, String::new_static(b"__mode"), String::new_static(b"k"))
+            .unwrap();
+        table.set_metatable(mc, Some(mt));
+        assert_eq!(table.weak_mode(), Some(WeakMode::Keys));
+        assert_eq!(table.length(), 4);
+        assert_eq!(table.get(3), Value::Integer(30));
+        assert_eq!(visit(table).len(), 5);
+
+        table.set_metatable(mc, None);
+        assert_eq!(table.weak_mode(), None);
+        assert_eq!(table.length(), 4);
+        assert_eq!(table.get(String::new_static(b"key")), Value::Boolean(true));
+        assert_eq!(visit(table).len(), 5);
+    });
+}
+
+#[test]
+fn weak_table_from_lua() -> Result<(), Box<StaticError>> {
+    let mut lua = Lua::new();
+    lua.sequence(|root| {
+        sequence::from_fn_with(root, |mc, root| {
+            Ok(Closure::new(
+                mc,
+                compile(
+                    mc,
+                    root.interned_strings,
+                    &br#"
+                        cache = setmetatable({}, { __mode = "v" })
+                        local kept = {}
+                        cache[1] = kept
+                        cache[2] = {}
+                        for i = 1, 100000 do
+                            local garbage = {}
+                        end
+                        return cache[1] == kept and cache[2] == nil and #cache == 1
+                    "#[..],
+                )?,
+                Some(root.globals),
+            )?)
+        })
+        .and_chain_with(root, |mc, root, closure| {
+            Ok(ThreadSequence::call_function(
+                mc,
+                root.main_thread,
+                Function::Closure(closure),
+                &[],
+            )?
+            .map_ok(|res| assert_eq!(res, vec![Value::Boolean(true)])))
+        })
+        .map_err(Error::to_static)
+        .boxed()
+    })?;
+
+    lua.mutate(|_, root| {
+        // `setmetatable` registers the tables it makes weak, so that Lua can prune them.
+        assert_eq!(root.weak_tables.len(), 1);
+        match root.globals.get(String::new_static(b"cache")) {
+            Value::Table(cache) => assert_eq!(cache.weak_mode(), Some(WeakMode::Values)),
+            v => panic!("cache lost: {:?}", v),
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use luster::{Lua, String, Table, Value};
+
+#[test]
+fn tostring_singletons() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, _| {
+        for v in &[Value::Nil, Value::Boolean(true), Value::Boolean(false)] {
+            let a = v.to_display_string(mc);
+            let b = v.to_display_string(mc);
+            assert!(String::ptr_eq(a, b));
+        }
+
+        let a = Value::Integer(1).to_display_string(mc);
+        let b = Value::Integer(1).to_display_string(mc);
+        assert_eq!(a, b);
+        assert!(!String::ptr_eq(a, b));
+    });
+}
+
+#[test]
+fn tostring_formatting() {
+    let mut lua = Lua::new();
+    lua.mutate(|mc, root| {
+        let cases: &[(Value, &str)] = &[
+            (Value::Nil, "nil"),
+            (Value::Boolean(true), "true"),
+            (Value::Boolean(false), "false"),
+            (Value::Integer(0), "0"),
+            (Value::Integer(-42), "-42"),
+            (Value::Integer(i64::MIN), "-9223372036854775808"),
+            (Value::Number(1.0), "1.0"),
+            (Value::Number(-0.0), "-0.0"),
+            (Value::Number(0.1), "0.1"),
+            (Value::Number(1.5), "1.5"),
+            (Value::Number(1.0 / 3.0), "0.33333333333333"),
+            (Value::Number(100.0), "100.0"),
+            (Value::Number(1e20), "1e+20"),
+            (Value::Number(1e14), "1e+14"),
+            (Value::Number(1e13), "10000000000000.0"),
+            (Value::Number(99999999999999.9), "1e+14"),
+            (Value::Number(123456.789e-10), "1.23456789e-05"),
+            (Value::Number(0.0001), "0.0001"),
+            (Value::Number(1e-10), "1e-10"),
+            (Value::Number(1e15), "1e+15"),
+            (Value::Number(100000000000000.0), "1e+14"),
+            (Value::Number(123456789012345.0), "1.2345678901234e+14"),
+            (Value::Number(1.25e100), "1.25e+100"),
+            (Value::Number(2f64.powi(63)), "9.2233720368548e+18"),
+            (Value::Number(f64::INFINITY), "inf"),
+            (Value::Number(f64::NEG_INFINITY), "-inf"),
+            (Value::Number(f64::NAN), "nan"),
+            (Value::String(String::new(mc, b"str")), "str"),
+        ];
+
+        for &(value, expected) in cases {
+            assert_eq!(format!("{}", value), expected);
+            match value.to_lua_string(mc) {
+                Value::String(s) => assert_eq!(s.as_bytes(), expected.as_bytes()),
+                v => panic!("to_lua_string returned {:?}", v),
+            }
+            if let Value::Integer(_) | Value::Number(_) = value {
+                let concat = String::concat(mc, &[value]).unwrap();
+                assert_eq!(concat.as_bytes(), expected.as_bytes());
+            }
+        }
+
+        let table = Value::Table(root.globals);
+        assert!(format!("{}", table).starts_with("table: 0x"));
+        let print = root.globals.get(String::new_static(b"print"));
+        assert!(format!("{}", print).starts_with("function: 0x"));
+        assert_ne!(
+            format!("{}", table),
+            format!("{}", Value::Table(Table::new(mc)))
+        );
+    });
+}
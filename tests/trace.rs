@@ -0,0 +1,73 @@
+use gc_arena::{make_arena, ArenaParameters, Collect, CollectionContext};
+use luster::{Callback, CallbackResult, Function, String, Table, Value};
+
+// A container with a hand written `Collect` impl, which only needs to forward to each `Value`.
+struct Values<'gc>(Vec<Value<'gc>>);
+
+unsafe impl<'gc> Collect for Values<'gc> {
+    fn trace(&self, cc: CollectionContext) {
+        for value in &self.0 {
+            value.trace(cc);
+        }
+    }
+}
+
+make_arena!(ValuesArena, Values);
+
+#[test]
+fn trace_mixed_values() {
+    let mut arena = ValuesArena::new(ArenaParameters::default(), |mc| {
+        let table = Table::new(mc);
+        table.set(mc, 1, String::new(mc, b"nested")).unwrap();
+        Values(vec![
+            Value::Nil,
+            Value::Boolean(true),
+            Value::Integer(3),
+            Value::Number(0.5),
+            Value::String(String::new(
+                mc,
+                b"a string which is too long to be stored inline",
+            )),
+            Value::Table(table),
+            Value::Function(Function::Callback(Callback::new_immediate(mc, |_| {
+                Ok(CallbackResult::Return(vec![Value::Integer(7)]))
+            }))),
+        ])
+    });
+
+    for _ in 0..3 {
+        arena.mutate(|mc, _| {
+            for i in 0..100 {
+                Table::new(mc)
+                    .set(mc, i, String::new(mc, b"garbage"))
+                    .unwrap();
+            }
+        });
+        arena.collect_all();
+    }
+
+    arena.mutate(|_, values| {
+        let values = &values.0;
+        assert_eq!(values[0], Value::Nil);
+        assert_eq!(values[1], Value::Boolean(true));
+        assert_eq!(values[2], Value::Integer(3));
+        assert_eq!(values[3], Value::Number(0.5));
+        match values[4] {
+            Value::String(s) => {
+                assert_eq!(
+                    s.as_bytes(),
+                    b"a string which is too long to be stored inline"
+                )
+            }
+            v => panic!("unexpected value {:?}", v),
+        }
+        match values[5] {
+            Value::Table(t) => match t.get(1) {
+                Value::String(s) => assert_eq!(s.as_bytes(), b"nested"),
+                v => panic!("unexpected value {:?}", v),
+            },
+            v => panic!("unexpected value {:?}", v),
+        }
+        assert!(matches!(values[6], Value::Function(Function::Callback(_))));
+    });
+}